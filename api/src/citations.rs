@@ -0,0 +1,182 @@
+//! Citation lookup
+//!
+//! Scholars cite a page by book, volume and printed page: "al-Ṭabarī, Taʾrīkh,
+//! 3:145". A citation names the book by ID, `original_id` or title and resolves
+//! to the page printed with that number. Books without print pagination
+//! (`paginated = false`) have no printed numbers to match, so there the number
+//! is read as a page ID, the part label may also be the part's position, and a
+//! number out of range gives the closest page of the part. A name matching
+//! several books only by part of their titles is ambiguous: the candidates are
+//! returned instead of a page, to be cited by ID.
+
+use crate::search::{normalize_arabic, same_part_label, BookPart, PageDirection, SearchEngine, SearchResult};
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// Books listed for an ambiguous name, shortest title first
+const MAX_BOOK_CANDIDATES: usize = 20;
+
+/// A page cited by book, volume and printed page number
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CitationRequest {
+    /// Book ID, `original_id` or title
+    pub book: String,
+    /// Volume as printed; None for single-volume books or any volume
+    #[serde(default)]
+    pub part_label: Option<String>,
+    pub page_number: String,
+}
+
+/// How a citation was matched to a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationMatch {
+    /// The page is printed with the cited number
+    PageNumber,
+    /// The cited number is the page ID (books without print pagination)
+    PageId,
+    /// The closest page of the cited part
+    Nearest,
+}
+
+/// The book a citation names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CitedBook {
+    pub id: u64,
+    pub title: String,
+    pub original_id: Option<String>,
+    pub paginated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedCitation {
+    pub book: CitedBook,
+    pub matched_by: CitationMatch,
+    pub page: SearchResult,
+}
+
+/// The page a citation resolves to, or why there is none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CitationResolution {
+    Resolved(Box<ResolvedCitation>),
+    /// The name matches several books; which one is meant?
+    Ambiguous { candidates: Vec<CitedBook> },
+    NotFound,
+}
+
+/// Find the books a citation names: by ID, then `original_id`, then title.
+/// Several only when the name matches none exactly.
+pub fn find_books(conn: &Connection, book: &str) -> Result<Vec<CitedBook>> {
+    let mut stmt = conn.prepare("SELECT id, title, original_id, paginated FROM books")?;
+    let books = stmt
+        .query_map([], |row| {
+            Ok(CitedBook {
+                id: row.get(0)?,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                original_id: row.get(2)?,
+                // Books not marked otherwise are taken to follow the print edition
+                paginated: row.get::<_, Option<i64>>(3)?.is_none_or(|v| v != 0),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(match_books(&books, book).into_iter().cloned().collect())
+}
+
+/// The exact match; otherwise the books whose title or `original_id` contains
+/// the name ("Tarikh" in "0310Tabari.Tarikh"), shortest title first
+fn match_books<'a>(books: &'a [CitedBook], book: &str) -> Vec<&'a CitedBook> {
+    let book = book.trim();
+    if book.is_empty() {
+        return Vec::new();
+    }
+    if let Ok(id) = book.parse::<u64>() {
+        if let Some(found) = books.iter().find(|b| b.id == id) {
+            return vec![found];
+        }
+    }
+    if let Some(found) = books.iter().find(|b| b.original_id.as_deref().is_some_and(|o| o.eq_ignore_ascii_case(book))) {
+        return vec![found];
+    }
+
+    let title = normalize_arabic(book).to_lowercase();
+    let titles: Vec<String> = books.iter().map(|b| normalize_arabic(&b.title).to_lowercase()).collect();
+    if let Some(position) = titles.iter().position(|t| t.trim() == title) {
+        return vec![&books[position]];
+    }
+    let mut found: Vec<&CitedBook> = books
+        .iter()
+        .zip(&titles)
+        .filter(|(b, t)| t.contains(&title) || b.original_id.as_deref().is_some_and(|o| o.to_lowercase().contains(&title)))
+        .map(|(b, _)| b)
+        .collect();
+    found.sort_by_key(|b| (b.title.chars().count(), b.id));
+    found.truncate(MAX_BOOK_CANDIDATES);
+    found
+}
+
+/// Resolve a citation to a page, unless the book is ambiguous or the book or page is not found
+pub fn resolve_citation(engine: &SearchEngine, conn: &Connection, request: &CitationRequest) -> Result<CitationResolution> {
+    let mut books = find_books(conn, &request.book)?;
+    if books.len() > 1 {
+        return Ok(CitationResolution::Ambiguous { candidates: books });
+    }
+    let Some(book) = books.pop() else {
+        return Ok(CitationResolution::NotFound);
+    };
+    Ok(match resolve_in_book(engine, book, request)? {
+        Some(citation) => CitationResolution::Resolved(Box::new(citation)),
+        None => CitationResolution::NotFound,
+    })
+}
+
+/// The cited page of a book; None if not found
+fn resolve_in_book(engine: &SearchEngine, book: CitedBook, request: &CitationRequest) -> Result<Option<ResolvedCitation>> {
+    let part_label = request.part_label.as_deref().map(str::trim).filter(|label| !label.is_empty());
+
+    if let Some(page) = engine.page_by_number(book.id, part_label, &request.page_number)? {
+        return Ok(Some(ResolvedCitation { book, matched_by: CitationMatch::PageNumber, page }));
+    }
+    if book.paginated {
+        return Ok(None);
+    }
+
+    let parts = engine.book_parts(book.id)?;
+    let page_id = request.page_number.trim().parse::<u64>().ok();
+    let Some(part) = cited_part(&parts, part_label, page_id) else {
+        return Ok(None);
+    };
+    Ok(page_in_part(engine, book.id, part, page_id)?.map(|(page, matched_by)| ResolvedCitation { book, matched_by, page }))
+}
+
+/// The part with the cited label, else the nth part for a label "n". Without
+/// a label, the part whose page IDs span the cited one, else the first part.
+fn cited_part<'a>(parts: &'a [BookPart], part_label: Option<&str>, page_id: Option<u64>) -> Option<&'a BookPart> {
+    match part_label {
+        Some(label) => parts.iter().find(|part| same_part_label(&part.part_label, label)).or_else(|| {
+            let position = label.parse::<usize>().ok()?;
+            parts.get(position.checked_sub(1)?)
+        }),
+        None => page_id
+            .and_then(|page_id| parts.iter().find(|part| (part.first_page_id..=part.last_page_id).contains(&page_id)))
+            .or(parts.first()),
+    }
+}
+
+/// The page of a part with the cited page ID, else the first page at or after
+/// it once clamped to the part's range, so it never leaves the part
+fn page_in_part(engine: &SearchEngine, id: u64, part: &BookPart, page_id: Option<u64>) -> Result<Option<(SearchResult, CitationMatch)>> {
+    if let Some(page_id) = page_id {
+        if let Some(page) = engine.get_page(id, part.part_index, page_id)? {
+            return Ok(Some((page, CitationMatch::PageId)));
+        }
+    }
+    let target = page_id.unwrap_or(part.first_page_id).clamp(part.first_page_id, part.last_page_id);
+    let page = match engine.get_page(id, part.part_index, target)? {
+        Some(page) => Some(page),
+        None => engine.adjacent_page(id, part.part_index, target, PageDirection::Next)?,
+    };
+    Ok(page.map(|page| (page, CitationMatch::Nearest)))
+}
@@ -0,0 +1,146 @@
+//! Cursor (search_after) pagination
+//!
+//! Results are ordered by death_ah, then by page (text_id, part_index, page_id).
+//! The sort key of the last hit on a page is handed out as an opaque cursor;
+//! the next request only collects docs sorting after it, so deep pages cost the
+//! same as the first one instead of collecting `limit + offset` docs.
+
+use anyhow::{anyhow, Result};
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::columnar::ColumnValues;
+use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+/// Position of a page in the death_ah result order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey {
+    /// Books without a death date sort last, like TopDocs::order_by_u64_field
+    pub death_ah: u64,
+    pub text_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+}
+
+impl SortKey {
+    /// Encode as an opaque cursor string
+    pub fn to_cursor(self) -> String {
+        format!(
+            "{:016x}{:016x}{:016x}{:016x}",
+            self.death_ah, self.text_id, self.part_index, self.page_id
+        )
+    }
+
+    /// Decode a cursor produced by to_cursor
+    pub fn from_cursor(cursor: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid cursor: {}", cursor);
+        if cursor.len() != 64 || !cursor.is_ascii() {
+            return Err(invalid());
+        }
+
+        let part = |i: usize| u64::from_str_radix(&cursor[i * 16..(i + 1) * 16], 16).map_err(|_| invalid());
+        Ok(Self {
+            death_ah: part(0)?,
+            text_id: part(1)?,
+            part_index: part(2)?,
+            page_id: part(3)?,
+        })
+    }
+}
+
+/// Collects the first `limit` docs in SortKey order, optionally starting after a cursor
+pub struct SortKeyTopDocs {
+    limit: usize,
+    after: Option<SortKey>,
+}
+
+impl SortKeyTopDocs {
+    pub fn new(limit: usize, after: Option<SortKey>) -> Self {
+        Self { limit, after }
+    }
+}
+
+pub struct SortKeySegmentCollector {
+    segment_ord: SegmentOrdinal,
+    limit: usize,
+    after: Option<SortKey>,
+    death_ah: Arc<dyn ColumnValues<u64>>,
+    text_id: Arc<dyn ColumnValues<u64>>,
+    part_index: Arc<dyn ColumnValues<u64>>,
+    page_id: Arc<dyn ColumnValues<u64>>,
+    /// Max-heap of the smallest keys seen so far
+    heap: BinaryHeap<(SortKey, DocId)>,
+}
+
+fn fast_column(segment_reader: &SegmentReader, field: &str, default_value: u64) -> tantivy::Result<Arc<dyn ColumnValues<u64>>> {
+    let (column, _column_type) = segment_reader
+        .fast_fields()
+        .u64_lenient(field)?
+        .ok_or_else(|| tantivy::TantivyError::SchemaError(format!("Field {} is not a fast field", field)))?;
+    Ok(column.first_or_default_col(default_value))
+}
+
+impl Collector for SortKeyTopDocs {
+    type Fruit = Vec<(SortKey, DocAddress)>;
+    type Child = SortKeySegmentCollector;
+
+    fn for_segment(&self, segment_ord: SegmentOrdinal, segment_reader: &SegmentReader) -> tantivy::Result<Self::Child> {
+        Ok(SortKeySegmentCollector {
+            segment_ord,
+            limit: self.limit,
+            after: self.after,
+            death_ah: fast_column(segment_reader, "death_ah", u64::MAX)?,
+            text_id: fast_column(segment_reader, "text_id", 0)?,
+            part_index: fast_column(segment_reader, "part_index", 0)?,
+            page_id: fast_column(segment_reader, "page_id", 0)?,
+            heap: BinaryHeap::with_capacity(self.limit.min(10_000)),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<Self::Fruit>) -> tantivy::Result<Self::Fruit> {
+        let mut merged: Vec<(SortKey, DocAddress)> = segment_fruits.into_iter().flatten().collect();
+        merged.sort_unstable_by_key(|&(key, _)| key);
+        merged.truncate(self.limit);
+        Ok(merged)
+    }
+}
+
+impl SegmentCollector for SortKeySegmentCollector {
+    type Fruit = Vec<(SortKey, DocAddress)>;
+
+    fn collect(&mut self, doc: DocId, _score: Score) {
+        if self.limit == 0 {
+            return;
+        }
+
+        let key = SortKey {
+            death_ah: self.death_ah.get_val(doc),
+            text_id: self.text_id.get_val(doc),
+            part_index: self.part_index.get_val(doc),
+            page_id: self.page_id.get_val(doc),
+        };
+        if self.after.is_some_and(|after| key <= after) {
+            return;
+        }
+
+        if self.heap.len() < self.limit {
+            self.heap.push((key, doc));
+        } else if self.heap.peek().is_some_and(|&(largest, _)| key < largest) {
+            self.heap.pop();
+            self.heap.push((key, doc));
+        }
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        let segment_ord = self.segment_ord;
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(key, doc)| (key, DocAddress::new(segment_ord, doc)))
+            .collect()
+    }
+}
@@ -1,6 +1,13 @@
+mod budget;
 mod cache;
+mod citations;
+mod collocations;
+mod cursor;
 mod error;
+mod names;
+mod quran;
 mod search;
+mod snippets;
 mod tokens;
 
 use axum::{
@@ -9,8 +16,14 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
+use budget::SearchBudget;
 use cache::TokenCache;
-use search::{SearchEngine, SearchFilters, SearchMode, SearchResults, SearchTerm, PageWithMatches};
+use citations::{CitationRequest, CitationResolution};
+use collocations::{CollocationRequest, CollocationResults};
+use names::{NameForm, NamePatterns, NameSearchForm};
+use quran::{PageCitationsRequest, QuranCitation, QuranStore, VerseSearchRequest, VerseSearchResults, QURAN_FILE};
+use search::{BookPart, ExplainRequest, MatchPositions, MatchQuery, SearchEngine, SearchFilters, SearchMode, SearchOptions, SearchResults, SearchTerm, PageDirection, PageWithMatches, QueryExplanation};
+use snippets::SnippetOptions;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -20,11 +33,40 @@ use tower_http::cors::{Any, CorsLayer};
 struct AppState {
     search_engine: SearchEngine,
     token_cache: TokenCache,
+    quran: QuranStore,
     db_path: PathBuf,
 }
 
 // === Request/Response types ===
 
+/// Upper bounds on client-supplied search budgets, so one query can't stall the server
+const BUDGET_CAPS: SearchBudget = SearchBudget { max_millis: Some(10_000), max_expanded_terms: Some(5_000), max_candidates: Some(20_000) };
+
+/// Upper bounds on collocation requests, which load the tokens of every scanned page
+const MAX_COLLOCATION_PAGES: usize = 5_000;
+const MAX_COLLOCATION_WINDOW: usize = 20;
+
+/// Upper bound on the pages a verse search reads the tokens of
+const MAX_VERSE_SEARCH_PAGES: usize = 5_000;
+
+/// Search options from the `snippets`/`include_body`/`cursor` query parameters of GET routes
+fn query_search_options(snippets: Option<bool>, include_body: Option<bool>, cursor: Option<String>) -> SearchOptions {
+    SearchOptions {
+        snippets: snippets.unwrap_or(false).then(SnippetOptions::default),
+        include_body: include_body.unwrap_or(false),
+        cursor,
+        budget: SearchBudget::default().capped_by(BUDGET_CAPS),
+        pattern_hits: false,
+    }
+}
+
+/// Search options from a POST body, with the budget capped
+fn capped_search_options(options: Option<SearchOptions>) -> SearchOptions {
+    let mut options = options.unwrap_or_default();
+    options.budget = options.budget.capped_by(BUDGET_CAPS);
+    options
+}
+
 #[derive(Deserialize)]
 struct SimpleSearchQuery {
     q: String,
@@ -32,6 +74,11 @@ struct SimpleSearchQuery {
     limit: Option<usize>,
     offset: Option<usize>,
     book_ids: Option<String>,
+    /// Return snippets instead of the full page body
+    snippets: Option<bool>,
+    include_body: Option<bool>,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -41,6 +88,7 @@ struct CombinedSearchRequest {
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
 }
 
 #[derive(Deserialize)]
@@ -51,6 +99,7 @@ struct ProximitySearchRequest {
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
 }
 
 #[derive(Deserialize)]
@@ -59,11 +108,7 @@ struct NameSearchRequest {
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
-}
-
-#[derive(Deserialize)]
-struct NameSearchForm {
-    patterns: Vec<String>,
+    options: Option<SearchOptions>,
 }
 
 #[derive(Deserialize)]
@@ -72,6 +117,11 @@ struct WildcardSearchQuery {
     limit: Option<usize>,
     offset: Option<usize>,
     book_ids: Option<String>,
+    /// Return snippets instead of the full page body
+    snippets: Option<bool>,
+    include_body: Option<bool>,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -81,6 +131,26 @@ struct PageQuery {
     page_id: u64,
 }
 
+#[derive(Deserialize)]
+struct BookQuery {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct AdjacentPageQuery {
+    id: u64,
+    part_index: u64,
+    page_id: u64,
+    direction: PageDirection,
+}
+
+#[derive(Deserialize)]
+struct PageByNumberQuery {
+    id: u64,
+    part_label: Option<String>,
+    page_number: String,
+}
+
 #[derive(Deserialize)]
 struct TokensQuery {
     id: u64,
@@ -122,6 +192,16 @@ struct NameMatchPositionsRequest {
     patterns: Vec<String>,
 }
 
+#[derive(Deserialize)]
+struct AllMatchPositionsRequest {
+    id: u64,
+    part_index: u64,
+    page_id: u64,
+    query: MatchQuery,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
 #[derive(Serialize)]
 struct HealthResponse {
     status: String,
@@ -180,7 +260,9 @@ async fn simple_search(
         }),
     };
 
-    state.search_engine.search(&params.q, mode, &filters, limit, offset)
+    let options = query_search_options(params.snippets, params.include_body, params.cursor);
+
+    state.search_engine.search(&params.q, mode, &filters, limit, offset, &options)
         .map(Json)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
@@ -193,7 +275,9 @@ async fn combined_search(
     let limit = req.limit.unwrap_or(50).min(100);
     let offset = req.offset.unwrap_or(0);
 
-    state.search_engine.combined_search(&req.and_terms, &req.or_terms, &filters, limit, offset)
+    let options = capped_search_options(req.options);
+
+    state.search_engine.combined_search(&req.and_terms, &req.or_terms, &filters, limit, offset, &options)
         .map(Json)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
@@ -206,7 +290,9 @@ async fn proximity_search(
     let limit = req.limit.unwrap_or(50).min(100);
     let offset = req.offset.unwrap_or(0);
 
-    state.search_engine.proximity_search(&req.term1, &req.term2, req.distance, &filters, limit, offset)
+    let options = capped_search_options(req.options);
+
+    state.search_engine.proximity_search(&req.term1, &req.term2, req.distance, &filters, limit, offset, &options)
         .map(Json)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
@@ -219,13 +305,18 @@ async fn name_search(
     let limit = req.limit.unwrap_or(50).min(100);
     let offset = req.offset.unwrap_or(0);
 
-    let patterns_by_form: Vec<Vec<String>> = req.forms.into_iter().map(|f| f.patterns).collect();
+    let options = capped_search_options(req.options);
 
-    state.search_engine.name_search(&patterns_by_form, &filters, limit, offset)
+    state.search_engine.name_search(&req.forms, &filters, limit, offset, &options)
         .map(Json)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
 
+/// Search and preview patterns of structured name forms
+async fn generate_name_patterns(Json(forms): Json<Vec<NameForm>>) -> Json<Vec<NamePatterns>> {
+    Json(forms.iter().map(NamePatterns::for_form).collect())
+}
+
 async fn wildcard_search(
     State(state): State<Arc<AppState>>,
     Query(params): Query<WildcardSearchQuery>,
@@ -244,7 +335,9 @@ async fn wildcard_search(
         }),
     };
 
-    state.search_engine.wildcard_search(&params.q, &filters, limit, offset)
+    let options = query_search_options(params.snippets, params.include_body, params.cursor);
+
+    state.search_engine.wildcard_search(&params.q, &filters, limit, offset, &options)
         .map(Json)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
@@ -258,6 +351,44 @@ async fn get_page(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
 
+async fn get_book_parts(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<BookQuery>,
+) -> Result<Json<Vec<BookPart>>, (StatusCode, Json<ErrorResponse>)> {
+    state.search_engine.book_parts(params.id)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+async fn get_adjacent_page(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AdjacentPageQuery>,
+) -> Result<Json<Option<search::SearchResult>>, (StatusCode, Json<ErrorResponse>)> {
+    state.search_engine.adjacent_page(params.id, params.part_index, params.page_id, params.direction)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+async fn get_page_by_number(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PageByNumberQuery>,
+) -> Result<Json<Option<search::SearchResult>>, (StatusCode, Json<ErrorResponse>)> {
+    state.search_engine.page_by_number(params.id, params.part_label.as_deref(), &params.page_number)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+async fn resolve_citation(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CitationRequest>,
+) -> Result<Json<CitationResolution>, (StatusCode, Json<ErrorResponse>)> {
+    let conn = rusqlite::Connection::open(&state.db_path)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))?;
+    citations::resolve_citation(&state.search_engine, &conn, &params)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
 async fn get_page_tokens(
     State(state): State<Arc<AppState>>,
     Query(params): Query<TokensQuery>,
@@ -306,6 +437,57 @@ async fn get_name_match_positions(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
 }
 
+async fn get_all_match_positions(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AllMatchPositionsRequest>,
+) -> Result<Json<MatchPositions>, (StatusCode, Json<ErrorResponse>)> {
+    state.search_engine.get_all_match_positions(req.id, req.part_index, req.page_id, &req.query, req.offset.unwrap_or(0), req.limit)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+/// How a search is interpreted: normalized terms, the Tantivy query, term document frequencies, effective filters and, for a page, where each term matches on it
+async fn explain_search(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ExplainRequest>,
+) -> Result<Json<QueryExplanation>, (StatusCode, Json<ErrorResponse>)> {
+    state.search_engine.explain(&req)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+async fn find_collocations(
+    State(state): State<Arc<AppState>>,
+    Json(mut req): Json<CollocationRequest>,
+) -> Result<Json<CollocationResults>, (StatusCode, Json<ErrorResponse>)> {
+    req.options.max_pages = req.options.max_pages.min(MAX_COLLOCATION_PAGES);
+    req.options.window = req.options.window.min(MAX_COLLOCATION_WINDOW);
+    collocations::find_collocations(&state.search_engine, &state.token_cache, &req)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+async fn get_page_quran_citations(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PageCitationsRequest>,
+) -> Result<Json<Vec<QuranCitation>>, (StatusCode, Json<ErrorResponse>)> {
+    state.quran.get()
+        .and_then(|quran| quran::page_quran_citations(&quran, &state.token_cache, &params))
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
+async fn search_verse_citations(
+    State(state): State<Arc<AppState>>,
+    Json(mut req): Json<VerseSearchRequest>,
+) -> Result<Json<VerseSearchResults>, (StatusCode, Json<ErrorResponse>)> {
+    req.max_pages = req.max_pages.min(MAX_VERSE_SEARCH_PAGES);
+    state.quran.get()
+        .and_then(|quran| quran::search_verse_citations(&quran, &state.search_engine, &state.token_cache, &req))
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e.to_string() })))
+}
+
 async fn get_all_books(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<BookMetadata>>, (StatusCode, Json<ErrorResponse>)> {
@@ -384,6 +566,7 @@ async fn main() -> anyhow::Result<()> {
 
     let index_path = PathBuf::from("/opt/kashshaf/data/tantivy_index");
     let db_path = PathBuf::from("/opt/kashshaf/data/corpus.db");
+    let quran_path = PathBuf::from("/opt/kashshaf/data").join(QURAN_FILE);
 
     let search_engine = SearchEngine::open(&index_path)?;
     let token_cache = TokenCache::new(db_path.clone(), 1000);
@@ -391,6 +574,7 @@ async fn main() -> anyhow::Result<()> {
     let state = Arc::new(AppState {
         search_engine,
         token_cache,
+        quran: QuranStore::new(quran_path),
         db_path,
     });
 
@@ -405,13 +589,23 @@ async fn main() -> anyhow::Result<()> {
         .route("/search/combined", post(combined_search))
         .route("/search/proximity", post(proximity_search))
         .route("/search/name", post(name_search))
+        .route("/names/patterns", post(generate_name_patterns))
         .route("/search/wildcard", get(wildcard_search))
+        .route("/search/explain", post(explain_search))
         .route("/page", get(get_page))
+        .route("/page/adjacent", get(get_adjacent_page))
+        .route("/page/by-number", get(get_page_by_number))
+        .route("/cite", get(resolve_citation))
+        .route("/book/parts", get(get_book_parts))
         .route("/page/tokens", get(get_page_tokens))
         .route("/page/matches", get(get_match_positions))
         .route("/page/with-matches", get(get_page_with_matches))
         .route("/page/matches/combined", post(get_match_positions_combined))
         .route("/page/matches/name", post(get_name_match_positions))
+        .route("/page/matches/all", post(get_all_match_positions))
+        .route("/collocations", post(find_collocations))
+        .route("/page/quran", get(get_page_quran_citations))
+        .route("/search/verse", post(search_verse_citations))
         .route("/books", get(get_all_books))
         .route("/authors", get(get_all_authors))
         .route("/genres", get(get_all_genres))
@@ -0,0 +1,313 @@
+//! Arabic personal name patterns
+//!
+//! A name form gives a person's kunyas (أبو منصور), nasab (معمر بن أحمد بن زياد),
+//! nisbas (الأصبهاني) and shuhra; the patterns are the ways the name is likely to
+//! be written in running text: kunya + nasab, nasab + nisba, kunya + بن + father,
+//! "المعروف بـ" + shuhra, and so on. Search patterns are further expanded with
+//! the proclitics a name can take (وأبو، فابن ...).
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Proclitics attached to the first word of a pattern
+const PROCLITICS: &[&str] = &["و", "ف", "ب", "ل", "ك"];
+
+/// Kunya particles, in every case
+const KUNYA_PARTICLES: &[&str] = &["ابو", "ابا", "ابي"];
+
+/// Words linking the parts of a nasab (normalized)
+const SON_CONNECTORS: &[&str] = &["بن", "ابن", "ibn", "bin", "b."];
+const DAUGHTER_CONNECTORS: &[&str] = &["بنت", "ابنة", "bint", "bt."];
+
+/// Nasab parts used in patterns
+const MAX_NASAB_PARTS: usize = 3;
+
+/// One person's name, as entered in the name search form
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NameForm {
+    pub kunyas: Vec<String>,
+    /// e.g. "معمر بن أحمد بن زياد"; ابن/بن/بنت and ibn/bin/bint all link parts
+    pub nasab: String,
+    pub nisbas: Vec<String>,
+    pub shuhra: String,
+    /// Kunya + nisba, without any nasab
+    pub allow_rare_kunya_nisba: bool,
+    /// Kunya + first nasab name
+    pub allow_kunya_nasab: bool,
+    /// First nasab name alone
+    pub allow_one_nasab: bool,
+    /// First nasab name + nisba
+    pub allow_one_nasab_nisba: bool,
+    /// Two-part nasab without nisba
+    pub allow_two_nasab: bool,
+}
+
+impl NameForm {
+    /// No kunya, nasab, nisba or shuhra filled in
+    pub fn is_empty(&self) -> bool {
+        filled(&self.kunyas).is_empty() && filled(&self.nisbas).is_empty() && normalize(&self.nasab).is_empty() && normalize(&self.shuhra).is_empty()
+    }
+}
+
+/// A form of the name search: either structured or already expanded to patterns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NameSearchForm {
+    /// Expanded patterns as a bare list (stored queries)
+    Patterns(Vec<String>),
+    /// Expanded patterns, as sent by the search form before the engine generated them
+    Expanded { patterns: Vec<String> },
+    #[serde(deserialize_with = "non_empty_form")]
+    Structured(NameForm),
+}
+
+impl NameSearchForm {
+    /// Patterns searched for this form, proclitic variants included
+    pub fn search_patterns(&self) -> Vec<String> {
+        match self {
+            NameSearchForm::Patterns(patterns) | NameSearchForm::Expanded { patterns } => patterns.clone(),
+            NameSearchForm::Structured(form) => generate_search_patterns(form),
+        }
+    }
+}
+
+/// Every field of a structured form is optional, so without this any JSON
+/// object would pass as one and search nothing
+fn non_empty_form<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NameForm, D::Error> {
+    let form = NameForm::deserialize(deserializer)?;
+    if form.is_empty() {
+        return Err(serde::de::Error::custom("Name form has no kunya, nasab, nisba or shuhra"));
+    }
+    Ok(form)
+}
+
+/// Patterns of one name form, for searching and for the preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamePatterns {
+    /// With proclitic variants
+    pub search: Vec<String>,
+    /// Without proclitics, kunya variants collapsed to اب*
+    pub display: Vec<String>,
+    pub valid: bool,
+}
+
+impl NamePatterns {
+    pub fn for_form(form: &NameForm) -> Self {
+        Self { search: generate_search_patterns(form), display: generate_display_patterns(form), valid: is_form_valid(form) }
+    }
+}
+
+/// Hamza carriers to bare alif, tashkeel removed, whitespace collapsed
+fn normalize(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter_map(|c| match c {
+            'أ' | 'إ' | 'آ' => Some('ا'),
+            '\u{064B}'..='\u{065F}' => None,
+            _ => Some(c),
+        })
+        .collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Names of a nasab, and whether it is a woman's (linked by بنت)
+fn nasab_parts(nasab: &str) -> (Vec<String>, bool) {
+    let normalized = normalize(nasab);
+    let mut parts: Vec<Vec<&str>> = vec![Vec::new()];
+    let mut is_female = false;
+    for word in normalized.split(' ').filter(|word| !word.is_empty()) {
+        let lower = word.to_lowercase();
+        let is_son = SON_CONNECTORS.contains(&lower.as_str());
+        let is_daughter = DAUGHTER_CONNECTORS.contains(&lower.as_str());
+        // A leading ابن belongs to the name ("ابن عمر")
+        let current = parts.last_mut().unwrap();
+        if (is_son || is_daughter) && !current.is_empty() {
+            is_female |= is_daughter;
+            parts.push(Vec::new());
+        } else {
+            current.push(word);
+        }
+    }
+    let parts = parts.into_iter().filter(|part| !part.is_empty()).map(|part| part.join(" ")).collect();
+    (parts, is_female)
+}
+
+fn connector(is_female: bool) -> &'static str {
+    if is_female {
+        " بنت "
+    } else {
+        " بن "
+    }
+}
+
+/// The name after a kunya particle ("ابو منصور" -> "منصور")
+fn kunya_base(kunya: &str) -> Option<&str> {
+    let (particle, base) = kunya.split_once(' ')?;
+    KUNYA_PARTICLES.contains(&particle).then_some(base)
+}
+
+/// ابو/ابا/ابي forms of a kunya
+fn kunya_variants(kunya: &str) -> Vec<String> {
+    let normalized = normalize(kunya);
+    match kunya_base(&normalized) {
+        Some(base) => KUNYA_PARTICLES.iter().map(|particle| format!("{} {}", particle, base)).collect(),
+        None => vec![normalized],
+    }
+}
+
+/// A pattern and its proclitic variants; the proclitic attaches to the first word
+fn with_proclitics(pattern: &str) -> Vec<String> {
+    let mut patterns = vec![pattern.to_string()];
+    patterns.extend(PROCLITICS.iter().map(|proclitic| format!("{}{}", proclitic, pattern)));
+    patterns
+}
+
+fn filled(values: &[String]) -> Vec<String> {
+    values.iter().map(|value| normalize(value)).filter(|value| !value.is_empty()).collect()
+}
+
+/// Insertion-ordered set of patterns
+#[derive(Default)]
+struct Patterns(Vec<String>);
+
+impl Patterns {
+    fn add(&mut self, pattern: &str) {
+        let pattern = normalize(pattern);
+        if !pattern.is_empty() && !self.0.contains(&pattern) {
+            self.0.push(pattern);
+        }
+    }
+
+    /// The pattern alone and followed by each nisba
+    fn add_with_nisbas(&mut self, pattern: &str, nisbas: &[String]) {
+        self.add(pattern);
+        self.add_nisbas(pattern, nisbas);
+    }
+
+    /// The pattern followed by each nisba
+    fn add_nisbas(&mut self, pattern: &str, nisbas: &[String]) {
+        for nisba in nisbas {
+            self.add(&format!("{} {}", pattern, nisba));
+        }
+    }
+}
+
+/// Patterns of one name form, without proclitics
+pub fn generate_patterns(form: &NameForm) -> Vec<String> {
+    let mut patterns = Patterns::default();
+
+    let kunyas: Vec<String> = filled(&form.kunyas).iter().flat_map(|kunya| kunya_variants(kunya)).collect();
+    let nisbas = filled(&form.nisbas);
+    let (mut nasab, is_female) = nasab_parts(&form.nasab);
+    nasab.truncate(MAX_NASAB_PARTS);
+    let link = connector(is_female);
+    let nasab_of = |len: usize| nasab[..len].join(link);
+
+    // Kunya + nasab
+    if nasab.len() >= 2 {
+        for kunya in &kunyas {
+            for len in 2..=nasab.len() {
+                patterns.add_with_nisbas(&format!("{} {}", kunya, nasab_of(len)), &nisbas);
+            }
+            // Kunya + first name + nisba ("ابو منصور معمر الاصبهاني")
+            patterns.add_nisbas(&format!("{} {}", kunya, nasab[0]), &nisbas);
+            // Kunya + بن + father and grandfather ("ابو منصور بن احمد")
+            for end in 2..=nasab.len() {
+                patterns.add_with_nisbas(&format!("{}{}{}", kunya, link, nasab[1..end].join(link)), &nisbas);
+            }
+        }
+    }
+
+    // Full nasab
+    if nasab.len() >= 3 {
+        patterns.add_with_nisbas(&nasab_of(3), &nisbas);
+    }
+
+    // Two-part nasab + nisba
+    if nasab.len() >= 2 {
+        patterns.add_nisbas(&nasab_of(2), &nisbas);
+    }
+
+    if form.allow_rare_kunya_nisba {
+        for kunya in &kunyas {
+            patterns.add_nisbas(kunya, &nisbas);
+        }
+    }
+
+    if form.allow_kunya_nasab && !nasab.is_empty() {
+        for kunya in &kunyas {
+            patterns.add_with_nisbas(&format!("{} {}", kunya, nasab[0]), &nisbas);
+        }
+    }
+
+    if form.allow_one_nasab && !nasab.is_empty() {
+        patterns.add(&nasab[0]);
+    }
+
+    if form.allow_one_nasab_nisba && !nasab.is_empty() {
+        patterns.add_nisbas(&nasab[0], &nisbas);
+    }
+
+    if form.allow_two_nasab && nasab.len() >= 2 {
+        patterns.add(&nasab_of(2));
+    }
+
+    // Shuhra, with a kunya in the genitive ("المعروف بابي ...")
+    let shuhra = normalize(&form.shuhra);
+    if !shuhra.is_empty() {
+        let known_as = match kunya_base(&shuhra) {
+            Some(base) => format!("بابي {}", base),
+            None => format!("ب{}", shuhra),
+        };
+        patterns.add(&format!("المعروف {}", known_as));
+        patterns.add(&format!("المشهور {}", known_as));
+    }
+
+    patterns.0
+}
+
+/// Patterns of one name form with every proclitic variant
+pub fn generate_search_patterns(form: &NameForm) -> Vec<String> {
+    let mut patterns: Vec<String> = Vec::new();
+    for pattern in generate_patterns(form) {
+        for expanded in with_proclitics(&pattern) {
+            if !patterns.contains(&expanded) {
+                patterns.push(expanded);
+            }
+        }
+    }
+    patterns
+}
+
+/// Patterns for the preview, with ابو/ابا/ابي collapsed to اب*
+pub fn generate_display_patterns(form: &NameForm) -> Vec<String> {
+    let mut patterns: Vec<String> = Vec::new();
+    for pattern in generate_patterns(form) {
+        let display = match kunya_base(&pattern) {
+            Some(rest) => format!("اب* {}", rest),
+            None => pattern,
+        };
+        if !patterns.contains(&display) {
+            patterns.push(display);
+        }
+    }
+    patterns
+}
+
+/// Whether a form has enough to search: a shuhra, two of kunya/nasab/nisba,
+/// or one of them with the option searching it alone
+pub fn is_form_valid(form: &NameForm) -> bool {
+    if !normalize(&form.shuhra).is_empty() {
+        return true;
+    }
+    let has_kunya = !filled(&form.kunyas).is_empty();
+    let has_nisba = !filled(&form.nisbas).is_empty();
+    let (nasab, _) = nasab_parts(&form.nasab);
+
+    let filled_fields = [has_kunya, !nasab.is_empty(), has_nisba].iter().filter(|&&filled| filled).count();
+    filled_fields >= 2
+        || (!nasab.is_empty() && form.allow_one_nasab)
+        || (!nasab.is_empty() && has_nisba && form.allow_one_nasab_nisba)
+        || (nasab.len() >= 2 && form.allow_two_nasab)
+}
+
@@ -0,0 +1,317 @@
+//! Qur'an citation detection
+//!
+//! The Qur'an text is `quran.txt` in the data directory, one verse per line in
+//! Tanzil's `sura|aya|text` format (unvocalized). Verses and pages
+//! are compared word by word after `normalize_arabic`: every run of
+//! SHINGLE_WORDS verse words found on a page seeds a match, matches on the same
+//! verse a word or two apart are joined, and a citation needs MIN_CITED_WORDS
+//! words of the verse (or all of a shorter one). Where several verses fit the
+//! same words (ان الله كان غفورا رحيما), only the best covered ones are kept.
+//! Verses of fewer than SHINGLE_WORDS words are not detected.
+
+use crate::cache::TokenCache;
+use crate::cursor::SortKey;
+use crate::search::{normalize_arabic, SearchEngine, SearchFilters};
+use crate::tokens::{PageKey, Token};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// File name of the Qur'an text in the corpus data directory
+pub const QURAN_FILE: &str = "quran.txt";
+
+/// Words of a verse that must appear in a row to seed a match
+const SHINGLE_WORDS: usize = 4;
+
+/// Verse words a citation needs (verses this short or shorter must be quoted whole)
+const MIN_CITED_WORDS: usize = 5;
+
+/// Words a quotation may skip or insert between two matching runs
+const MAX_GAP: usize = 2;
+
+#[derive(Debug, Clone)]
+struct Verse {
+    sura: u32,
+    aya: u32,
+    words: Vec<String>,
+}
+
+/// The Qur'an text with an index of its word shingles
+#[derive(Debug, Default)]
+pub struct Quran {
+    verses: Vec<Verse>,
+    /// Shingle -> (verse, position of its first word)
+    shingles: HashMap<Vec<String>, Vec<(usize, usize)>>,
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(normalize_arabic).filter(|word| !word.is_empty()).collect()
+}
+
+impl Quran {
+    /// Verses in Tanzil's `sura|aya|text` format; empty lines and # comments are skipped
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut quran = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(3, '|');
+            let (Some(sura), Some(aya), Some(text)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(anyhow!("Line {}: expected sura|aya|text", number + 1));
+            };
+            let sura = sura.trim().parse().with_context(|| format!("Line {}: invalid sura", number + 1))?;
+            let aya = aya.trim().parse().with_context(|| format!("Line {}: invalid aya", number + 1))?;
+            quran.verses.push(Verse { sura, aya, words: words(text) });
+        }
+
+        for (index, verse) in quran.verses.iter().enumerate() {
+            // Shorter verses are too ambiguous to detect (الرحمن، والعصر)
+            for (position, shingle) in verse.words.windows(SHINGLE_WORDS).enumerate() {
+                quran.shingles.entry(shingle.to_vec()).or_default().push((index, position));
+            }
+        }
+        Ok(quran)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("No Qur'an text at {}", path.display()))?;
+        Self::from_text(&text).with_context(|| format!("Invalid Qur'an text {}", path.display()))
+    }
+
+    fn verse(&self, sura: u32, aya: u32) -> Option<(usize, &Verse)> {
+        self.verses.iter().enumerate().find(|(_, verse)| verse.sura == sura && verse.aya == aya)
+    }
+
+    /// Citations in a list of normalized words, without their page location
+    fn find_citations(&self, page: &[String]) -> Vec<QuranCitation> {
+        // Runs of consecutive shingles along one verse: (verse, page start, verse start, shingles)
+        let mut runs: Vec<(usize, usize, usize, usize)> = Vec::new();
+        let mut open: HashMap<(usize, isize), usize> = HashMap::new();
+        for (start, shingle) in page.windows(SHINGLE_WORDS).enumerate() {
+            let Some(hits) = self.shingles.get(shingle) else {
+                continue;
+            };
+            for &(verse, position) in hits {
+                let diagonal = (verse, position as isize - start as isize);
+                match open.get(&diagonal) {
+                    Some(&run) if runs[run].1 + runs[run].3 == start => runs[run].3 += 1,
+                    _ => {
+                        open.insert(diagonal, runs.len());
+                        runs.push((verse, start, position, 1));
+                    }
+                }
+            }
+        }
+
+        // Join runs on the same verse a few words apart
+        runs.sort_unstable_by_key(|&(verse, page_start, _, _)| (verse, page_start));
+        let mut joined: Vec<(usize, QuranCitation, Vec<bool>)> = Vec::new();
+        for (verse_index, page_start, verse_start, shingles) in runs {
+            let verse = &self.verses[verse_index];
+            let len = shingles + SHINGLE_WORDS - 1;
+            let (page_end, verse_end) = (page_start + len, verse_start + len);
+            let last = joined.last_mut().filter(|(last_verse, last, _)| {
+                *last_verse == verse_index
+                    && page_start <= last.end + MAX_GAP
+                    && verse_start >= last.verse_start
+                    && verse_start <= last.verse_end + MAX_GAP
+            });
+            match last {
+                Some((_, last, covered)) => {
+                    last.end = last.end.max(page_end);
+                    last.verse_end = last.verse_end.max(verse_end);
+                    covered[verse_start..verse_end].fill(true);
+                }
+                None => {
+                    let mut covered = vec![false; verse.words.len()];
+                    covered[verse_start..verse_end].fill(true);
+                    let citation = QuranCitation {
+                        book_id: 0,
+                        part_index: 0,
+                        page_id: 0,
+                        sura: verse.sura,
+                        aya: verse.aya,
+                        start: page_start,
+                        end: page_end,
+                        verse_start,
+                        verse_end,
+                        verse_words: verse.words.len(),
+                        cited_words: 0,
+                        coverage: 0.0,
+                    };
+                    joined.push((verse_index, citation, covered));
+                }
+            }
+        }
+
+        let citations: Vec<QuranCitation> = joined
+            .into_iter()
+            .map(|(_, mut citation, covered)| {
+                citation.cited_words = covered.iter().filter(|&&word| word).count();
+                citation.coverage = citation.cited_words as f64 / citation.verse_words as f64;
+                citation
+            })
+            .filter(|citation| citation.cited_words >= MIN_CITED_WORDS.min(citation.verse_words))
+            .collect();
+
+        // Of verses fitting overlapping words, keep the best covered
+        let overlapping_best = |citation: &QuranCitation| {
+            citations
+                .iter()
+                .filter(|other| other.start < citation.end && citation.start < other.end)
+                .map(|other| other.cited_words)
+                .max()
+                .unwrap_or(0)
+        };
+        let mut best: Vec<QuranCitation> = citations
+            .iter()
+            .filter(|citation| citation.cited_words >= overlapping_best(citation))
+            .cloned()
+            .collect();
+        best.sort_by_key(|citation| (citation.start, citation.sura, citation.aya));
+        best
+    }
+}
+
+/// The Qur'an text of the data directory, read on first use
+pub struct QuranStore {
+    path: PathBuf,
+    loaded: Mutex<Option<Arc<Quran>>>,
+}
+
+impl QuranStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, loaded: Mutex::new(None) }
+    }
+
+    pub fn get(&self) -> Result<Arc<Quran>> {
+        let mut loaded = self.loaded.lock().map_err(|_| anyhow!("Qur'an text lock poisoned"))?;
+        if let Some(quran) = loaded.as_ref() {
+            return Ok(quran.clone());
+        }
+        let quran = Arc::new(Quran::load(&self.path)?);
+        *loaded = Some(quran.clone());
+        Ok(quran)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuranCitation {
+    pub book_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+    pub sura: u32,
+    pub aya: u32,
+    /// Token range [start, end) of the quotation on the page
+    pub start: usize,
+    pub end: usize,
+    /// Word range [verse_start, verse_end) of the verse quoted
+    pub verse_start: usize,
+    pub verse_end: usize,
+    pub verse_words: usize,
+    /// Verse words found in the quotation
+    pub cited_words: usize,
+    /// cited_words / verse_words
+    pub coverage: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageCitationsRequest {
+    pub book_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerseSearchRequest {
+    pub sura: u32,
+    pub aya: u32,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_max_pages() -> usize {
+    2000
+}
+
+fn default_limit() -> usize {
+    200
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerseSearchResults {
+    /// Pages sharing a shingle with the verse
+    pub total_pages: usize,
+    pub pages_scanned: usize,
+    pub citations: Vec<QuranCitation>,
+    pub elapsed_ms: u64,
+}
+
+fn page_words(tokens: &[Token]) -> Vec<String> {
+    tokens.iter().map(|token| normalize_arabic(&token.surface)).collect()
+}
+
+fn locate(mut citations: Vec<QuranCitation>, key: &SortKey) -> Vec<QuranCitation> {
+    for citation in &mut citations {
+        citation.book_id = key.text_id;
+        citation.part_index = key.part_index;
+        citation.page_id = key.page_id;
+    }
+    citations
+}
+
+/// Verses quoted on one page, in page order
+pub fn page_quran_citations(quran: &Quran, token_cache: &TokenCache, request: &PageCitationsRequest) -> Result<Vec<QuranCitation>> {
+    let tokens = token_cache.get(&PageKey::new(request.book_id, request.part_index, request.page_id))?;
+    let key = SortKey { death_ah: 0, text_id: request.book_id, part_index: request.part_index, page_id: request.page_id };
+    Ok(locate(quran.find_citations(&page_words(&tokens)), &key))
+}
+
+/// Pages quoting a verse
+pub fn search_verse_citations(
+    quran: &Quran,
+    engine: &SearchEngine,
+    token_cache: &TokenCache,
+    request: &VerseSearchRequest,
+) -> Result<VerseSearchResults> {
+    let start = std::time::Instant::now();
+    let (_, verse) = quran
+        .verse(request.sura, request.aya)
+        .ok_or_else(|| anyhow!("No verse {}:{}", request.sura, request.aya))?;
+
+    if verse.words.len() < SHINGLE_WORDS {
+        return Err(anyhow!("Verse {}:{} is too short to detect quotations of", request.sura, request.aya));
+    }
+
+    // Candidate pages share at least one shingle with the verse
+    let mut shingles: Vec<String> = Vec::new();
+    for shingle in verse.words.windows(SHINGLE_WORDS) {
+        let shingle = shingle.join(" ");
+        if !shingles.contains(&shingle) {
+            shingles.push(shingle);
+        }
+    }
+    let (total_pages, pages) = engine.pattern_pages(&[shingles], &request.filters, request.max_pages)?;
+
+    let mut citations = Vec::new();
+    for key in &pages {
+        let tokens = token_cache.get(&PageKey::new(key.text_id, key.part_index, key.page_id))?;
+        let found = locate(quran.find_citations(&page_words(&tokens)), key);
+        citations.extend(found.into_iter().filter(|citation| citation.sura == request.sura && citation.aya == request.aya));
+        if citations.len() >= request.limit {
+            citations.truncate(request.limit);
+            break;
+        }
+    }
+
+    Ok(VerseSearchResults { total_pages, pages_scanned: pages.len(), citations, elapsed_ms: start.elapsed().as_millis() as u64 })
+}
+
@@ -0,0 +1,215 @@
+//! Per-query time and resource budgets
+//!
+//! Wildcard expansion, the proximity over-fetch and long collections can stall
+//! the app. A SearchBudget bounds each search call; when a limit is reached the
+//! search stops and returns what it has, flagged as truncated with the reason.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader};
+
+/// Docs collected between clock reads
+const DEADLINE_CHECK_INTERVAL: u32 = 1024;
+
+/// Limits for one search call (None = unlimited)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchBudget {
+    /// Wall-clock limit in milliseconds
+    pub max_millis: Option<u64>,
+    /// Maximum number of index terms a wildcard may expand to
+    pub max_expanded_terms: Option<usize>,
+    /// Maximum candidate documents fetched for proximity/wildcard verification
+    pub max_candidates: Option<usize>,
+}
+
+impl Default for SearchBudget {
+    fn default() -> Self {
+        Self {
+            max_millis: Some(30_000),
+            max_expanded_terms: Some(10_000),
+            max_candidates: Some(50_000),
+        }
+    }
+}
+
+fn min_limit<T: Ord>(value: Option<T>, cap: Option<T>) -> Option<T> {
+    match (value, cap) {
+        (Some(value), Some(cap)) => Some(value.min(cap)),
+        (value, cap) => value.or(cap),
+    }
+}
+
+impl SearchBudget {
+    pub fn unlimited() -> Self {
+        Self {
+            max_millis: None,
+            max_expanded_terms: None,
+            max_candidates: None,
+        }
+    }
+
+    /// This budget, tightened so no limit exceeds the corresponding one in `caps`
+    pub fn capped_by(self, caps: SearchBudget) -> Self {
+        Self {
+            max_millis: min_limit(self.max_millis, caps.max_millis),
+            max_expanded_terms: min_limit(self.max_expanded_terms, caps.max_expanded_terms),
+            max_candidates: min_limit(self.max_candidates, caps.max_candidates),
+        }
+    }
+
+    /// Start the wall-clock budget of a search call
+    pub fn start(&self) -> Deadline {
+        Deadline {
+            at: self.max_millis.map(|ms| Instant::now() + Duration::from_millis(ms)),
+            hit: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Cap a candidate over-fetch; the flag tells whether the budget was the binding limit
+    pub fn cap_candidates(&self, wanted: usize) -> (usize, bool) {
+        match self.max_candidates {
+            Some(max) if max < wanted => (max, true),
+            _ => (wanted, false),
+        }
+    }
+}
+
+/// Why a search returned partial results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationReason {
+    /// max_millis elapsed
+    Deadline,
+    /// The wildcard matched more than max_expanded_terms index terms
+    ExpandedTerms,
+    /// Candidate verification stopped at max_candidates documents
+    Candidates,
+}
+
+/// Wall-clock deadline of one search call
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    at: Option<Instant>,
+    /// Set once some work was skipped because the deadline passed
+    hit: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// Whether the deadline has passed; marks the deadline as hit, so only
+    /// call this right before skipping work
+    pub fn expired(&self) -> bool {
+        if self.hit.load(Ordering::Relaxed) {
+            return true;
+        }
+        let expired = self.at.is_some_and(|at| Instant::now() >= at);
+        if expired {
+            self.hit.store(true, Ordering::Relaxed);
+        }
+        expired
+    }
+
+    /// Whether any work was skipped because of the deadline
+    pub fn was_hit(&self) -> bool {
+        self.hit.load(Ordering::Relaxed)
+    }
+
+    /// Wrap a collector so it stops collecting once the deadline passes
+    pub fn collector<C: Collector>(&self, inner: C) -> DeadlineCollector<C> {
+        DeadlineCollector {
+            inner,
+            deadline: self.clone(),
+        }
+    }
+}
+
+pub struct DeadlineCollector<C> {
+    inner: C,
+    deadline: Deadline,
+}
+
+pub struct DeadlineSegmentCollector<S> {
+    inner: S,
+    deadline: Deadline,
+    until_check: u32,
+    stopped: bool,
+}
+
+impl<S: SegmentCollector> DeadlineSegmentCollector<S> {
+    /// Read the clock every DEADLINE_CHECK_INTERVAL docs
+    fn tick(&mut self, docs: u32) -> bool {
+        if self.stopped {
+            return false;
+        }
+        if self.until_check <= docs {
+            self.until_check = DEADLINE_CHECK_INTERVAL;
+            if self.deadline.expired() {
+                self.stopped = true;
+                return false;
+            }
+        } else {
+            self.until_check -= docs;
+        }
+        true
+    }
+}
+
+impl<C: Collector> Collector for DeadlineCollector<C> {
+    type Fruit = C::Fruit;
+    type Child = DeadlineSegmentCollector<C::Child>;
+
+    fn for_segment(&self, segment_ord: SegmentOrdinal, segment_reader: &SegmentReader) -> tantivy::Result<Self::Child> {
+        Ok(DeadlineSegmentCollector {
+            inner: self.inner.for_segment(segment_ord, segment_reader)?,
+            deadline: self.deadline.clone(),
+            until_check: 0,
+            stopped: false,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        self.inner.requires_scoring()
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<<Self::Child as SegmentCollector>::Fruit>) -> tantivy::Result<Self::Fruit> {
+        self.inner.merge_fruits(segment_fruits)
+    }
+}
+
+impl<S: SegmentCollector> SegmentCollector for DeadlineSegmentCollector<S> {
+    type Fruit = S::Fruit;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        if self.tick(1) {
+            self.inner.collect(doc, score);
+        }
+    }
+
+    fn collect_block(&mut self, docs: &[DocId]) {
+        if self.tick(docs.len() as u32) {
+            self.inner.collect_block(docs);
+        }
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.inner.harvest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_caps() {
+        let caps = SearchBudget { max_millis: Some(1_000), max_expanded_terms: None, max_candidates: Some(100) };
+        let budget = SearchBudget { max_millis: None, max_expanded_terms: Some(50), max_candidates: Some(500) }.capped_by(caps);
+        assert_eq!(budget, SearchBudget { max_millis: Some(1_000), max_expanded_terms: Some(50), max_candidates: Some(100) });
+
+        assert_eq!(budget.cap_candidates(5_000), (100, true));
+        assert_eq!(budget.cap_candidates(40), (40, false));
+    }
+}
@@ -0,0 +1,276 @@
+//! Citation lookup
+//!
+//! Scholars cite a page by book, volume and printed page: "al-Ṭabarī, Taʾrīkh,
+//! 3:145". A citation names the book by ID, `original_id` or title and resolves
+//! to the page printed with that number. Books without print pagination
+//! (`paginated = false`) have no printed numbers to match, so there the number
+//! is read as a page ID, the part label may also be the part's position, and a
+//! number out of range gives the closest page of the part.
+//!
+//! A name matching several books only by part of their titles is ambiguous:
+//! the candidates are returned instead of a page, to be cited by ID.
+//!
+//! Deep links carry citations: `kashshaf://cite?book=...&part=3&page=145`
+//! (or `page=3:145`).
+
+use crate::search::{normalize_arabic, same_part_label, BookPart, PageDirection, SearchEngine, SearchResult};
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// URL scheme of deep links
+pub const DEEP_LINK_SCHEME: &str = "kashshaf";
+
+/// Books listed for an ambiguous name, shortest title first
+const MAX_BOOK_CANDIDATES: usize = 20;
+
+/// A page cited by book, volume and printed page number
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CitationRequest {
+    /// Book ID, `original_id` or title
+    pub book: String,
+    /// Volume as printed; None for single-volume books or any volume
+    #[serde(default)]
+    pub part_label: Option<String>,
+    pub page_number: String,
+}
+
+/// How a citation was matched to a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationMatch {
+    /// The page is printed with the cited number
+    PageNumber,
+    /// The cited number is the page ID (books without print pagination)
+    PageId,
+    /// The closest page of the cited part
+    Nearest,
+}
+
+/// The book a citation names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CitedBook {
+    pub id: u64,
+    pub title: String,
+    pub original_id: Option<String>,
+    pub paginated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedCitation {
+    pub book: CitedBook,
+    pub matched_by: CitationMatch,
+    pub page: SearchResult,
+}
+
+/// The page a citation resolves to, or why there is none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CitationResolution {
+    Resolved(Box<ResolvedCitation>),
+    /// The name matches several books; which one is meant?
+    Ambiguous { candidates: Vec<CitedBook> },
+    NotFound,
+}
+
+/// Find the books a citation names: by ID, then `original_id`, then title.
+/// Several only when the name matches none exactly.
+pub fn find_books(conn: &Connection, book: &str) -> Result<Vec<CitedBook>> {
+    let mut stmt = conn.prepare("SELECT id, title, original_id, paginated FROM books")?;
+    let books = stmt
+        .query_map([], |row| {
+            Ok(CitedBook {
+                id: row.get(0)?,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                original_id: row.get(2)?,
+                // Books not marked otherwise are taken to follow the print edition
+                paginated: row.get::<_, Option<i64>>(3)?.is_none_or(|v| v != 0),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(match_books(&books, book).into_iter().cloned().collect())
+}
+
+/// The exact match; otherwise the books whose title or `original_id` contains
+/// the name ("Tarikh" in "0310Tabari.Tarikh"), shortest title first
+fn match_books<'a>(books: &'a [CitedBook], book: &str) -> Vec<&'a CitedBook> {
+    let book = book.trim();
+    if book.is_empty() {
+        return Vec::new();
+    }
+    if let Ok(id) = book.parse::<u64>() {
+        if let Some(found) = books.iter().find(|b| b.id == id) {
+            return vec![found];
+        }
+    }
+    if let Some(found) = books.iter().find(|b| b.original_id.as_deref().is_some_and(|o| o.eq_ignore_ascii_case(book))) {
+        return vec![found];
+    }
+
+    let title = normalize_arabic(book).to_lowercase();
+    let titles: Vec<String> = books.iter().map(|b| normalize_arabic(&b.title).to_lowercase()).collect();
+    if let Some(position) = titles.iter().position(|t| t.trim() == title) {
+        return vec![&books[position]];
+    }
+    let mut found: Vec<&CitedBook> = books
+        .iter()
+        .zip(&titles)
+        .filter(|(b, t)| t.contains(&title) || b.original_id.as_deref().is_some_and(|o| o.to_lowercase().contains(&title)))
+        .map(|(b, _)| b)
+        .collect();
+    found.sort_by_key(|b| (b.title.chars().count(), b.id));
+    found.truncate(MAX_BOOK_CANDIDATES);
+    found
+}
+
+/// Resolve a citation to a page, unless the book is ambiguous or the book or page is not found
+pub fn resolve_citation(engine: &SearchEngine, conn: &Connection, request: &CitationRequest) -> Result<CitationResolution> {
+    let mut books = find_books(conn, &request.book)?;
+    if books.len() > 1 {
+        return Ok(CitationResolution::Ambiguous { candidates: books });
+    }
+    let Some(book) = books.pop() else {
+        return Ok(CitationResolution::NotFound);
+    };
+    Ok(match resolve_in_book(engine, book, request)? {
+        Some(citation) => CitationResolution::Resolved(Box::new(citation)),
+        None => CitationResolution::NotFound,
+    })
+}
+
+/// The cited page of a book; None if not found
+fn resolve_in_book(engine: &SearchEngine, book: CitedBook, request: &CitationRequest) -> Result<Option<ResolvedCitation>> {
+    let part_label = request.part_label.as_deref().map(str::trim).filter(|label| !label.is_empty());
+
+    if let Some(page) = engine.page_by_number(book.id, part_label, &request.page_number)? {
+        return Ok(Some(ResolvedCitation { book, matched_by: CitationMatch::PageNumber, page }));
+    }
+    if book.paginated {
+        return Ok(None);
+    }
+
+    let parts = engine.book_parts(book.id)?;
+    let page_id = request.page_number.trim().parse::<u64>().ok();
+    let Some(part) = cited_part(&parts, part_label, page_id) else {
+        return Ok(None);
+    };
+    Ok(page_in_part(engine, book.id, part, page_id)?.map(|(page, matched_by)| ResolvedCitation { book, matched_by, page }))
+}
+
+/// The part with the cited label, else the nth part for a label "n". Without
+/// a label, the part whose page IDs span the cited one, else the first part.
+fn cited_part<'a>(parts: &'a [BookPart], part_label: Option<&str>, page_id: Option<u64>) -> Option<&'a BookPart> {
+    match part_label {
+        Some(label) => parts.iter().find(|part| same_part_label(&part.part_label, label)).or_else(|| {
+            let position = label.parse::<usize>().ok()?;
+            parts.get(position.checked_sub(1)?)
+        }),
+        None => page_id
+            .and_then(|page_id| parts.iter().find(|part| (part.first_page_id..=part.last_page_id).contains(&page_id)))
+            .or(parts.first()),
+    }
+}
+
+/// The page of a part with the cited page ID, else the first page at or after
+/// it once clamped to the part's range, so it never leaves the part
+fn page_in_part(engine: &SearchEngine, id: u64, part: &BookPart, page_id: Option<u64>) -> Result<Option<(SearchResult, CitationMatch)>> {
+    if let Some(page_id) = page_id {
+        if let Some(page) = engine.get_page(id, part.part_index, page_id)? {
+            return Ok(Some((page, CitationMatch::PageId)));
+        }
+    }
+    let target = page_id.unwrap_or(part.first_page_id).clamp(part.first_page_id, part.last_page_id);
+    let page = match engine.get_page(id, part.part_index, target)? {
+        Some(page) => Some(page),
+        None => engine.adjacent_page(id, part.part_index, target, PageDirection::Next)?,
+    };
+    Ok(page.map(|page| (page, CitationMatch::Nearest)))
+}
+
+/// Parse a `kashshaf://cite?book=...&part=...&page=...` deep link. Without a
+/// part, a page written "3:145" is page 145 of part 3.
+pub fn parse_citation_link(link: &str) -> Result<CitationRequest> {
+    let url = Url::parse(link).map_err(|e| anyhow!("Invalid link {}: {}", link, e))?;
+    if url.scheme() != DEEP_LINK_SCHEME || url.host_str() != Some("cite") {
+        return Err(anyhow!("Not a citation link: {}", link));
+    }
+
+    let (mut book, mut part_label, mut page_number) = (None, None, None);
+    for (key, value) in url.query_pairs() {
+        let value = value.trim().to_string();
+        match key.as_ref() {
+            "book" => book = Some(value),
+            "part" | "vol" => part_label = Some(value).filter(|v| !v.is_empty()),
+            "page" => page_number = Some(value),
+            _ => {}
+        }
+    }
+
+    let book = book.filter(|b| !b.is_empty()).ok_or_else(|| anyhow!("Citation link has no book: {}", link))?;
+    let mut page_number = page_number.filter(|p| !p.is_empty()).ok_or_else(|| anyhow!("Citation link has no page: {}", link))?;
+    if part_label.is_none() {
+        if let Some((part, page)) = page_number.split_once(':') {
+            part_label = Some(part.trim().to_string());
+            page_number = page.trim().to_string();
+        }
+    }
+    Ok(CitationRequest { book, part_label, page_number })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(id: u64, title: &str, original_id: &str) -> CitedBook {
+        CitedBook { id, title: title.to_string(), original_id: Some(original_id.to_string()), paginated: true }
+    }
+
+    fn part(part_index: u64, part_label: &str, first_page_id: u64, last_page_id: u64) -> BookPart {
+        BookPart {
+            part_index,
+            part_label: part_label.to_string(),
+            first_page_id,
+            last_page_id,
+            page_count: (last_page_id - first_page_id + 1) as usize,
+            first_page_number: String::new(),
+            last_page_number: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_citation_parts() {
+        let books = [
+            book(12, "تاريخ الرسل والملوك", "0310Tabari.Tarikh"),
+            book(7, "تاريخ بغداد", "0463KhatibBaghdadi.TarikhBaghdad"),
+            book(310, "تفسير الطبري", "0310Tabari.Tafsir"),
+        ];
+        let ids = |name: &str| match_books(&books, name).iter().map(|b| b.id).collect::<Vec<_>>();
+        assert_eq!(ids("310"), [310]);
+        assert_eq!(ids("0310tabari.tarikh"), [12]);
+        assert_eq!(ids("تاريخ بغداد"), [7]);
+        assert_eq!(ids("Tafsir"), [310]);
+        assert!(ids("الكامل").is_empty());
+        // A name in several titles is ambiguous, not the shortest of them
+        assert_eq!(ids("تاريخ"), [7, 12]);
+        assert_eq!(ids("Tabari"), [310, 12]);
+
+        let parts = [part(0, "01", 1, 400), part(1, "02", 401, 800), part(2, "مقدمة", 801, 820)];
+        let index = |label: Option<&str>, page_id: Option<u64>| cited_part(&parts, label, page_id).map(|p| p.part_index);
+        assert_eq!(index(Some("2"), None), Some(1));
+        assert_eq!(index(Some("مقدمة"), None), Some(2));
+        assert_eq!(index(Some("3"), None), Some(2));
+        assert_eq!(index(Some("4"), None), None);
+        assert_eq!(index(None, Some(500)), Some(1));
+        assert_eq!(index(None, Some(5000)), Some(0));
+
+        let request = parse_citation_link("kashshaf://cite?book=0310Tabari.Tarikh&page=3:145").unwrap();
+        assert_eq!(request.part_label.as_deref(), Some("3"));
+        assert_eq!(request.page_number, "145");
+        let request = parse_citation_link("kashshaf://cite?book=%D8%AA%D8%A7%D8%B1%D9%8A%D8%AE&part=2&page=17").unwrap();
+        assert_eq!((request.book.as_str(), request.part_label.as_deref()), ("تاريخ", Some("2")));
+        assert!(parse_citation_link("kashshaf://cite?book=12").is_err());
+        assert!(parse_citation_link("https://cite?book=12&page=1").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Token-level collation of two page ranges
+//!
+//! Aligns two editions of a text (e.g. the Shamela and OpenITI copies of a work)
+//! on normalized surface forms and reports the insertions, deletions and
+//! substitutions between them. Matching uses patience anchors (tokens occurring
+//! once on each side) and falls back to an LCS table for short gaps without any.
+
+use crate::cache::TokenCache;
+use crate::search::normalize_arabic;
+use crate::tokens::PageKey;
+use anyhow::{anyhow, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Tokens taken from each side; longer ranges are cut and flagged
+const MAX_COLLATION_TOKENS: usize = 50_000;
+
+/// Largest gap (cells of the LCS table) aligned exactly when it has no anchors
+const LCS_MAX_CELLS: usize = 4_000_000;
+
+/// Pages `first_page_id..=last_page_id` of a book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageRange {
+    pub book_id: u64,
+    pub first_page_id: u64,
+    pub last_page_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollationRequest {
+    pub a: PageRange,
+    pub b: PageRange,
+}
+
+/// Where a page's tokens start in a side's token sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollatedPage {
+    pub page_id: u64,
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollationSide {
+    pub book_id: u64,
+    pub pages: Vec<CollatedPage>,
+    pub tokens: usize,
+    /// The range had more than MAX_COLLATION_TOKENS tokens
+    pub truncated: bool,
+}
+
+/// A token of one side, as indexed by get_page_tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLocation {
+    pub page_id: u64,
+    pub idx: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditKind {
+    /// Tokens only in b
+    Insert,
+    /// Tokens only in a
+    Delete,
+    /// Tokens of a replaced by tokens of b
+    Substitute,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollationEdit {
+    pub kind: EditKind,
+    /// Range in a's token sequence (empty for an insertion)
+    pub a_start: usize,
+    pub a_end: usize,
+    /// Range in b's token sequence (empty for a deletion)
+    pub b_start: usize,
+    pub b_end: usize,
+    pub a_tokens: Vec<TokenLocation>,
+    pub b_tokens: Vec<TokenLocation>,
+    pub a_text: String,
+    pub b_text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollationResults {
+    pub a: CollationSide,
+    pub b: CollationSide,
+    pub equal_tokens: usize,
+    /// 2 × equal tokens / all tokens
+    pub similarity: f64,
+    pub edits: Vec<CollationEdit>,
+    pub elapsed_ms: u64,
+}
+
+/// A differing stretch between two matched tokens: (a range, b range)
+type Gap = (std::ops::Range<usize>, std::ops::Range<usize>);
+
+/// Pairs of equal tokens, in order on both sides
+fn match_tokens(a: &[String], b: &[String]) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    match_range(a, b, 0, a.len(), 0, b.len(), &mut matches);
+    matches
+}
+
+fn match_range(a: &[String], b: &[String], mut a_lo: usize, mut a_hi: usize, mut b_lo: usize, mut b_hi: usize, out: &mut Vec<(usize, usize)>) {
+    while a_lo < a_hi && b_lo < b_hi && a[a_lo] == b[b_lo] {
+        out.push((a_lo, b_lo));
+        a_lo += 1;
+        b_lo += 1;
+    }
+    let mut suffix = Vec::new();
+    while a_lo < a_hi && b_lo < b_hi && a[a_hi - 1] == b[b_hi - 1] {
+        a_hi -= 1;
+        b_hi -= 1;
+        suffix.push((a_hi, b_hi));
+    }
+
+    if a_lo < a_hi && b_lo < b_hi {
+        let anchors = unique_anchors(a, b, a_lo..a_hi, b_lo..b_hi);
+        if !anchors.is_empty() {
+            let (mut a_next, mut b_next) = (a_lo, b_lo);
+            for (a_anchor, b_anchor) in anchors {
+                match_range(a, b, a_next, a_anchor, b_next, b_anchor, out);
+                out.push((a_anchor, b_anchor));
+                (a_next, b_next) = (a_anchor + 1, b_anchor + 1);
+            }
+            match_range(a, b, a_next, a_hi, b_next, b_hi, out);
+        } else if (a_hi - a_lo) * (b_hi - b_lo) <= LCS_MAX_CELLS {
+            lcs(a, b, a_lo..a_hi, b_lo..b_hi, out);
+        }
+    }
+
+    out.extend(suffix.into_iter().rev());
+}
+
+/// Tokens occurring once in each range, longest run in order on both sides
+fn unique_anchors(a: &[String], b: &[String], a_range: std::ops::Range<usize>, b_range: std::ops::Range<usize>) -> Vec<(usize, usize)> {
+    // token -> (count in a, position in a, count in b, position in b)
+    let mut seen: HashMap<&str, (usize, usize, usize, usize)> = HashMap::new();
+    for i in a_range {
+        let entry = seen.entry(a[i].as_str()).or_default();
+        entry.0 += 1;
+        entry.1 = i;
+    }
+    for j in b_range {
+        if let Some(entry) = seen.get_mut(b[j].as_str()) {
+            entry.2 += 1;
+            entry.3 = j;
+        }
+    }
+    let mut candidates: Vec<(usize, usize)> = seen
+        .values()
+        .filter(|(a_count, _, b_count, _)| *a_count == 1 && *b_count == 1)
+        .map(|&(_, i, _, j)| (i, j))
+        .collect();
+    candidates.sort_unstable();
+
+    // Longest increasing subsequence of b positions (patience sorting)
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; candidates.len()];
+    for (k, &(_, j)) in candidates.iter().enumerate() {
+        let pile = tails.partition_point(|&t| candidates[t].1 < j);
+        previous[k] = pile.checked_sub(1).map(|p| tails[p]);
+        if pile == tails.len() {
+            tails.push(k);
+        } else {
+            tails[pile] = k;
+        }
+    }
+    let mut anchors = Vec::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(k) = next {
+        anchors.push(candidates[k]);
+        next = previous[k];
+    }
+    anchors.reverse();
+    anchors
+}
+
+fn lcs(a: &[String], b: &[String], a_range: std::ops::Range<usize>, b_range: std::ops::Range<usize>, out: &mut Vec<(usize, usize)>) {
+    let (n, m) = (a_range.len(), b_range.len());
+    let width = m + 1;
+    let mut lengths = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * width + j] = if a[a_range.start + i] == b[b_range.start + j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[a_range.start + i] == b[b_range.start + j] {
+            out.push((a_range.start + i, b_range.start + j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+}
+
+/// Stretches between matched tokens where the two sides differ
+fn gaps(matches: &[(usize, usize)], a_len: usize, b_len: usize) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    let (mut a_next, mut b_next) = (0, 0);
+    for &(i, j) in matches.iter().chain(std::iter::once(&(a_len, b_len))) {
+        if i > a_next || j > b_next {
+            gaps.push((a_next..i, b_next..j));
+        }
+        (a_next, b_next) = (i + 1, j + 1);
+    }
+    gaps
+}
+
+/// Tokens of a page range, in page order, with where each page starts
+struct SideTokens {
+    side: CollationSide,
+    normalized: Vec<String>,
+    surfaces: Vec<String>,
+    locations: Vec<TokenLocation>,
+}
+
+fn load_side(corpus: &Connection, token_cache: &TokenCache, range: &PageRange) -> Result<SideTokens> {
+    let page_ids: Vec<u64> = corpus
+        .prepare("SELECT page_id FROM page_tokens WHERE book_id = ?1 AND page_id BETWEEN ?2 AND ?3 ORDER BY page_id")?
+        .query_map(
+            [range.book_id as i64, range.first_page_id as i64, range.last_page_id as i64],
+            |row| row.get::<_, i64>(0),
+        )?
+        .map(|id| id.map(|id| id as u64))
+        .collect::<rusqlite::Result<_>>()?;
+    if page_ids.is_empty() {
+        return Err(anyhow!(
+            "No pages {}–{} in book {}",
+            range.first_page_id,
+            range.last_page_id,
+            range.book_id
+        ));
+    }
+
+    let mut side = SideTokens {
+        side: CollationSide { book_id: range.book_id, pages: Vec::new(), tokens: 0, truncated: false },
+        normalized: Vec::new(),
+        surfaces: Vec::new(),
+        locations: Vec::new(),
+    };
+    for page_id in page_ids {
+        let tokens = token_cache.get(&PageKey::new(range.book_id, page_id))?;
+        let start = side.normalized.len();
+        let take = tokens.len().min(MAX_COLLATION_TOKENS - start);
+        for token in &tokens[..take] {
+            side.normalized.push(normalize_arabic(&token.surface));
+            side.surfaces.push(token.surface.clone());
+            side.locations.push(TokenLocation { page_id, idx: token.idx });
+        }
+        side.side.pages.push(CollatedPage { page_id, start, len: take });
+        if take < tokens.len() {
+            side.side.truncated = true;
+            break;
+        }
+    }
+    side.side.tokens = side.normalized.len();
+    Ok(side)
+}
+
+/// Align two page ranges token by token
+pub fn collate(corpus: &Connection, token_cache: &TokenCache, request: &CollationRequest) -> Result<CollationResults> {
+    let start = std::time::Instant::now();
+    let a = load_side(corpus, token_cache, &request.a)?;
+    let b = load_side(corpus, token_cache, &request.b)?;
+
+    let matches = match_tokens(&a.normalized, &b.normalized);
+    let edits = gaps(&matches, a.normalized.len(), b.normalized.len())
+        .into_iter()
+        .map(|(a_range, b_range)| CollationEdit {
+            kind: match (a_range.is_empty(), b_range.is_empty()) {
+                (true, _) => EditKind::Insert,
+                (_, true) => EditKind::Delete,
+                _ => EditKind::Substitute,
+            },
+            a_start: a_range.start,
+            a_end: a_range.end,
+            b_start: b_range.start,
+            b_end: b_range.end,
+            a_tokens: a.locations[a_range.clone()].to_vec(),
+            b_tokens: b.locations[b_range.clone()].to_vec(),
+            a_text: a.surfaces[a_range].join(" "),
+            b_text: b.surfaces[b_range].join(" "),
+        })
+        .collect();
+
+    let total = a.normalized.len() + b.normalized.len();
+    Ok(CollationResults {
+        equal_tokens: matches.len(),
+        similarity: if total == 0 { 1.0 } else { 2.0 * matches.len() as f64 / total as f64 },
+        a: a.side,
+        b: b.side,
+        edits,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(normalize_arabic).collect()
+    }
+
+    #[test]
+    fn test_token_gaps() {
+        // Hamza spelling is normalized away; one word dropped, one replaced, one added
+        let a = words("قال أبو بكر رضي الله عنه في كتابه الكبير");
+        let b = words("قال ابو بكر الله عنه في مصنفه الكبير جدا");
+
+        let matches = match_tokens(&a, &b);
+        assert_eq!(matches.len(), 7);
+        assert_eq!(gaps(&matches, a.len(), b.len()), vec![(3..4, 3..3), (7..8, 6..7), (9..9, 8..9)]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Collocation analysis around a node term
+//!
+//! Gathers the lemmas within a window of every node occurrence from TokenCache
+//! page tokens and scores each against its frequency in the lemma index.
+//! Windows are taken per page and never cross into the next page.
+
+use crate::cache::TokenCache;
+use crate::search::{SearchEngine, SearchFilters, SearchMode, SearchTerm, UNKNOWN_LEMMA};
+use crate::tokens::PageKey;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// POS tag prefixes excluded by `exclude_particles` (particles, prepositions, conjunctions)
+const PARTICLE_POS_PREFIXES: &[&str] = &["part", "prep", "conj"];
+
+/// Collocates scored against corpus frequencies, the most frequent first
+const MAX_SCORED_COLLOCATES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollocationSort {
+    Frequency,
+    Mi,
+    TScore,
+    #[default]
+    LogLikelihood,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollocationOptions {
+    /// Tokens on each side of the node
+    pub window: usize,
+    /// Minimum co-occurrence count of a collocate
+    pub min_frequency: usize,
+    /// Skip particles, prepositions and conjunctions
+    pub exclude_particles: bool,
+    /// Further POS tags to skip
+    pub exclude_pos: Vec<String>,
+    pub sort: CollocationSort,
+    /// Maximum number of collocates returned
+    pub limit: usize,
+    /// Node pages scanned, the first in death_ah order: a node on more pages
+    /// is only sampled from the earliest authors (see `truncated`)
+    pub max_pages: usize,
+}
+
+impl Default for CollocationOptions {
+    fn default() -> Self {
+        Self {
+            window: 5,
+            min_frequency: 3,
+            exclude_particles: false,
+            exclude_pos: Vec::new(),
+            sort: CollocationSort::default(),
+            limit: 100,
+            max_pages: 2000,
+        }
+    }
+}
+
+impl CollocationOptions {
+    fn excludes(&self, pos: &str) -> bool {
+        (self.exclude_particles && PARTICLE_POS_PREFIXES.iter().any(|prefix| pos.starts_with(prefix)))
+            || self.exclude_pos.iter().any(|excluded| excluded == pos)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollocationRequest {
+    pub node: SearchTerm,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    #[serde(default)]
+    pub options: CollocationOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collocate {
+    pub lemma: String,
+    /// Occurrences within the node windows
+    pub frequency: usize,
+    /// Occurrences in the whole corpus
+    pub corpus_frequency: u64,
+    /// Occurrences within the windows expected from the corpus frequency
+    pub expected: f64,
+    pub mi: f64,
+    pub t_score: f64,
+    /// Negative when the lemma is seen less often than expected
+    pub log_likelihood: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollocationResults {
+    pub node: SearchTerm,
+    /// Pages containing the node
+    pub total_pages: usize,
+    /// Pages scanned (at most max_pages)
+    pub pages_scanned: usize,
+    /// Node pages were left unscanned, so the collocates describe the earliest
+    /// `pages_scanned` pages in death_ah order, not the whole subcorpus
+    pub truncated: bool,
+    /// Share of the node pages scanned, from 0 to 1
+    pub page_coverage: f64,
+    pub node_occurrences: usize,
+    /// Tokens within the node windows
+    pub window_tokens: u64,
+    /// Tokens in the corpus
+    pub corpus_tokens: u64,
+    pub collocates: Vec<Collocate>,
+    pub elapsed_ms: u64,
+}
+
+/// o·ln(o/e), taken as 0 for an empty cell
+pub(crate) fn ll_term(observed: f64, expected: f64) -> f64 {
+    if observed > 0.0 && expected > 0.0 {
+        observed * (observed / expected).ln()
+    } else {
+        0.0
+    }
+}
+
+/// Score a collocate seen `frequency` times in `window_tokens` tokens, against
+/// `corpus_frequency` occurrences in `corpus_tokens`
+fn score(lemma: String, frequency: usize, corpus_frequency: u64, window_tokens: u64, corpus_tokens: u64) -> Collocate {
+    let observed = frequency as f64;
+    let window = window_tokens as f64;
+    let corpus = (corpus_tokens as f64).max(1.0);
+    // The lemma index and the page tokens can disagree slightly
+    let corpus_frequency = corpus_frequency.max(frequency as u64);
+    let in_corpus = corpus_frequency as f64;
+
+    let expected = window * in_corpus / corpus;
+    let mi = (observed / expected).log2();
+    let t_score = (observed - expected) / observed.sqrt();
+
+    // Dunning's log-likelihood over the window/rest × collocate/other table, signed so
+    // that lemmas kept away from the node rank below the neutral ones
+    let cells = [
+        (observed, window, in_corpus),
+        (window - observed, window, corpus - in_corpus),
+        (in_corpus - observed, corpus - window, in_corpus),
+        (corpus - window - in_corpus + observed, corpus - window, corpus - in_corpus),
+    ];
+    let log_likelihood = 2.0
+        * cells
+            .iter()
+            .map(|&(cell, row, column)| ll_term(cell.max(0.0), row * column / corpus))
+            .sum::<f64>()
+        * if observed < expected { -1.0 } else { 1.0 };
+
+    Collocate { lemma, frequency, corpus_frequency, expected, mi, t_score, log_likelihood }
+}
+
+/// Rank the lemmas co-occurring with a node term
+pub fn find_collocations(engine: &SearchEngine, token_cache: &TokenCache, request: &CollocationRequest) -> Result<CollocationResults> {
+    let start = std::time::Instant::now();
+    let options = &request.options;
+    let occurrences = engine.term_occurrences(&request.node, &request.filters, options.max_pages)?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut window_tokens: u64 = 0;
+    let mut node_occurrences = 0;
+    for page in &occurrences.pages {
+        let tokens = token_cache.get(&PageKey::new(page.id, page.page_id))?;
+
+        // Overlapping windows count each token once; node tokens are not collocates
+        let mut in_window = vec![false; tokens.len()];
+        let mut in_node = vec![false; tokens.len()];
+        for &node_start in &page.starts {
+            let node_start = node_start as usize;
+            if node_start >= tokens.len() {
+                continue;
+            }
+            let node_end = (node_start + occurrences.term_len).min(tokens.len());
+            in_node[node_start..node_end].fill(true);
+            in_window[node_start.saturating_sub(options.window)..(node_end + options.window).min(tokens.len())].fill(true);
+            node_occurrences += 1;
+        }
+
+        for (i, token) in tokens.iter().enumerate() {
+            if !in_window[i] || in_node[i] {
+                continue;
+            }
+            window_tokens += 1;
+            if token.lemma == UNKNOWN_LEMMA || options.excludes(&token.pos) {
+                continue;
+            }
+            *counts.entry(token.lemma.clone()).or_default() += 1;
+        }
+    }
+
+    let mut candidates: Vec<(String, usize)> = counts
+        .into_iter()
+        .filter(|(_, frequency)| *frequency >= options.min_frequency.max(1))
+        .collect();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    candidates.truncate(MAX_SCORED_COLLOCATES);
+
+    let lemmas: Vec<String> = candidates.iter().map(|(lemma, _)| lemma.clone()).collect();
+    let (corpus_tokens, corpus_frequencies) = engine.term_frequencies(SearchMode::Lemma, &lemmas)?;
+
+    let mut collocates: Vec<Collocate> = candidates
+        .into_iter()
+        .zip(corpus_frequencies)
+        .map(|((lemma, frequency), corpus_frequency)| score(lemma, frequency, corpus_frequency, window_tokens, corpus_tokens))
+        .collect();
+    let sort_value = |collocate: &Collocate| match options.sort {
+        CollocationSort::Frequency => collocate.frequency as f64,
+        CollocationSort::Mi => collocate.mi,
+        CollocationSort::TScore => collocate.t_score,
+        CollocationSort::LogLikelihood => collocate.log_likelihood,
+    };
+    collocates.sort_by(|a, b| {
+        sort_value(b)
+            .total_cmp(&sort_value(a))
+            .then_with(|| b.frequency.cmp(&a.frequency))
+            .then_with(|| a.lemma.cmp(&b.lemma))
+    });
+    collocates.truncate(options.limit);
+
+    Ok(CollocationResults {
+        node: request.node.clone(),
+        total_pages: occurrences.total_pages,
+        pages_scanned: occurrences.pages.len(),
+        truncated: occurrences.pages.len() < occurrences.total_pages,
+        page_coverage: if occurrences.total_pages == 0 { 1.0 } else { occurrences.pages.len() as f64 / occurrences.total_pages as f64 },
+        node_occurrences,
+        window_tokens,
+        corpus_tokens,
+        collocates,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collocate_scores() {
+        // Seen exactly as often as its corpus rate predicts
+        let neutral = score("x".to_string(), 10, 1_000, 1_000, 100_000);
+        assert!((neutral.expected - 10.0).abs() < 1e-9);
+        assert!(neutral.mi.abs() < 1e-9);
+        assert!(neutral.log_likelihood.abs() < 1e-6);
+
+        let attracted = score("y".to_string(), 40, 1_000, 1_000, 100_000);
+        assert!((attracted.mi - 2.0).abs() < 1e-9);
+        assert!(attracted.t_score > 0.0);
+        assert!(attracted.log_likelihood > neutral.log_likelihood);
+
+        // Seen less often than expected ranks below neutral
+        let repelled = score("z".to_string(), 2, 1_000, 1_000, 100_000);
+        assert!(repelled.t_score < 0.0);
+        assert!(repelled.log_likelihood < neutral.log_likelihood - 1.0);
+    }
+}
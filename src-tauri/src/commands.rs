@@ -1,18 +1,38 @@
 //! Tauri commands for frontend communication
 
 use anyhow;
+use kashshaf_lib::citations::{self, CitationRequest, CitationResolution};
+use kashshaf_lib::collation::{CollationRequest, CollationResults};
+use kashshaf_lib::collocations::{CollocationRequest, CollocationResults};
+use kashshaf_lib::control::{SearchCancelled, SearchControl, SearchProgress};
+use kashshaf_lib::dates::{DateMention, DateSearchRequest, DateSearchResults, PageDatesRequest};
 use kashshaf_lib::error::KashshafError;
+use kashshaf_lib::export::{ExportRequest, ExportSummary};
+use kashshaf_lib::frequency::{FrequencyList, FrequencyRequest};
+use kashshaf_lib::isnad::{Isnad, IsnadSearchRequest, IsnadSearchResults, PageIsnadsRequest};
+use kashshaf_lib::keyness::{KeynessRequest, KeynessResults};
+use kashshaf_lib::names::{NameForm, NamePatterns, NameSearchForm};
+use kashshaf_lib::network::{self, CoMentionNetwork, NetworkPerson, NetworkRequest};
+use kashshaf_lib::ngrams::{NgramRequest, NgramResults};
+use kashshaf_lib::persons::{self, Person, PersonInput, PersonMentionCounts, PersonMentionsRequest};
+use kashshaf_lib::quran::{PageCitationsRequest, QuranCitation, VerseSearchRequest, VerseSearchResults};
+use kashshaf_lib::result_cache::ResultCacheStats;
+use kashshaf_lib::reuse::{BookReuseRequest, PageReuseRequest, ReuseIndexStatus, ReuseResults};
 use kashshaf_lib::search::{
-    validate_wildcard_query, PageWithMatches, SearchFilters, SearchMode, SearchResult,
-    SearchResults, SearchTerm,
+    validate_wildcard_query, BookPart, ExplainRequest, MatchPositions, MatchQuery, PageDirection, PageWithMatches,
+    QueryExplanation, SearchFilters, SearchMode, SearchOptions, SearchResult, SearchResults, SearchTerm,
 };
+use kashshaf_lib::similar::{SimilarPassages, SimilarPassagesRequest};
 use kashshaf_lib::state::AppState;
+use kashshaf_lib::toponyms::{self, Place, PlaceMentionCounts, PlaceMentionsRequest};
 use kashshaf_lib::tokens::{Token, TokenField};
 use rusqlite::{OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
 use tauri::menu::{ContextMenu, MenuBuilder, MenuItemBuilder};
 use tauri::{AppHandle, Manager, State};
+use tauri_plugin_deep_link::DeepLinkExt;
 
 /// Type alias for managed app state (allows hot-reloading after corpus download)
 pub type ManagedAppState = Arc<RwLock<Option<Arc<AppState>>>>;
@@ -29,6 +49,82 @@ fn require_state(state: &ManagedAppState) -> Result<Arc<AppState>, KashshafError
     })
 }
 
+/// Controls of running searches, keyed by the frontend's search ID
+static RUNNING_SEARCHES: LazyLock<Mutex<HashMap<String, SearchControl>>> = LazyLock::new(Default::default);
+
+/// Payload of "search-progress" events
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchProgressEvent {
+    pub search_id: String,
+    #[serde(flatten)]
+    pub progress: SearchProgress,
+}
+
+/// Keeps a search registered under its ID while it runs, so cancel_search can reach it
+struct RunningSearch {
+    search_id: Option<String>,
+    control: SearchControl,
+}
+
+impl RunningSearch {
+    /// Register a search; a still-running search with the same ID is cancelled.
+    /// Searches without an ID can't be cancelled and don't report progress.
+    fn start(window: &tauri::Window, search_id: Option<String>) -> Self {
+        let Some(search_id) = search_id else {
+            return Self { search_id: None, control: SearchControl::new() };
+        };
+
+        let window = window.clone();
+        let event_id = search_id.clone();
+        let control = SearchControl::new().with_progress(move |progress| {
+            let _ = window.emit(
+                "search-progress",
+                SearchProgressEvent { search_id: event_id.clone(), progress: progress.clone() },
+            );
+        });
+
+        let previous = RUNNING_SEARCHES.lock().unwrap().insert(search_id.clone(), control.clone());
+        if let Some(previous) = previous {
+            previous.cancel();
+        }
+
+        Self { search_id: Some(search_id), control }
+    }
+}
+
+impl Drop for RunningSearch {
+    fn drop(&mut self) {
+        if let Some(ref search_id) = self.search_id {
+            let mut searches = RUNNING_SEARCHES.lock().unwrap();
+            // A newer search may have taken over the ID
+            if searches.get(search_id).is_some_and(|control| control.ptr_eq(&self.control)) {
+                searches.remove(search_id);
+            }
+        }
+    }
+}
+
+/// Map search errors, keeping cancellation distinguishable for the frontend
+fn search_error(e: anyhow::Error) -> KashshafError {
+    if e.is::<SearchCancelled>() {
+        KashshafError::Cancelled
+    } else {
+        KashshafError::Search(e.to_string())
+    }
+}
+
+/// Cancel a running search by the ID it was started with
+#[tauri::command]
+pub fn cancel_search(search_id: String) -> Result<(), KashshafError> {
+    let searches = RUNNING_SEARCHES.lock().unwrap();
+    if let Some(control) = searches.get(&search_id) {
+        control.cancel();
+        Ok(())
+    } else {
+        Err(KashshafError::NotFound(format!("No running search with ID {}", search_id)))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookMetadata {
     pub id: i64,
@@ -70,18 +166,24 @@ fn row_to_book(row: &Row) -> rusqlite::Result<BookMetadata> {
 
 #[tauri::command]
 pub async fn search(
+    window: tauri::Window,
     state: State<'_, ManagedAppState>,
     query: String,
     mode: Option<SearchMode>,
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
+    search_id: Option<String>,
 ) -> Result<SearchResults, KashshafError> {
     let app_state = require_state(&state)?;
     let mode = mode.unwrap_or_default();
     let filters = filters.unwrap_or_default();
     let limit = limit.unwrap_or(50);
     let offset = offset.unwrap_or(0);
+    let mut options = options.unwrap_or_default();
+    let running = RunningSearch::start(&window, search_id);
+    options.control = running.control.clone();
 
     // Clone what we need for the blocking task
     let search_engine = app_state.search_engine.clone();
@@ -89,8 +191,8 @@ pub async fn search(
     // Run CPU-intensive search on blocking thread pool to keep UI responsive
     tokio::task::spawn_blocking(move || {
         search_engine
-            .search(&query, mode, &filters, limit, offset)
-            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+            .search(&query, mode, &filters, limit, offset, &options)
+            .map_err(search_error)
     })
     .await
     .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
@@ -110,6 +212,72 @@ pub fn get_page(
         .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
 }
 
+/// Parts (volumes) of a book with their labels and page ranges
+#[tauri::command]
+pub fn get_book_parts(state: State<'_, ManagedAppState>, id: u64) -> Result<Vec<BookPart>, KashshafError> {
+    let app_state = require_state(&state)?;
+    app_state
+        .search_engine
+        .book_parts(id)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// The next or previous page of a book, across parts; None at either end
+#[tauri::command]
+pub fn get_adjacent_page(
+    state: State<'_, ManagedAppState>,
+    id: u64,
+    part_index: u64,
+    page_id: u64,
+    direction: PageDirection,
+) -> Result<Option<SearchResult>, KashshafError> {
+    let app_state = require_state(&state)?;
+    app_state
+        .search_engine
+        .adjacent_page(id, part_index, page_id, direction)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// The page of a book with a printed page number, optionally within a part
+#[tauri::command]
+pub fn get_page_by_number(
+    state: State<'_, ManagedAppState>,
+    id: u64,
+    part_label: Option<String>,
+    page_number: String,
+) -> Result<Option<SearchResult>, KashshafError> {
+    let app_state = require_state(&state)?;
+    app_state
+        .search_engine
+        .page_by_number(id, part_label.as_deref(), &page_number)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// Resolve a citation (book, volume, printed page) to a page, or the books an ambiguous name matches
+#[tauri::command]
+pub fn resolve_citation(
+    state: State<'_, ManagedAppState>,
+    request: CitationRequest,
+) -> Result<CitationResolution, KashshafError> {
+    let app_state = require_state(&state)?;
+    let conn = app_state
+        .get_db_connection()
+        .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+    citations::resolve_citation(&app_state.search_engine, &conn, &request)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// The citation of the `kashshaf://` link the app was launched with, if any
+#[tauri::command]
+pub fn get_launch_citation(app: AppHandle) -> Result<Option<CitationRequest>, KashshafError> {
+    let urls = app
+        .deep_link()
+        .get_current()
+        .map_err(|e| KashshafError::Other(e.to_string()))?
+        .unwrap_or_default();
+    Ok(urls.iter().find_map(|url| citations::parse_citation_link(url.as_str()).ok()))
+}
+
 fn normalize_arabic_for_search(text: &str) -> String {
     text.chars()
         .filter_map(|c| match c {
@@ -454,6 +622,7 @@ fn token_field_to_search_mode(field: TokenField) -> SearchMode {
 
 #[tauri::command]
 pub async fn proximity_search(
+    window: tauri::Window,
     state: State<'_, ManagedAppState>,
     term1: String,
     field1: TokenField,
@@ -463,6 +632,8 @@ pub async fn proximity_search(
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
+    search_id: Option<String>,
 ) -> Result<SearchResults, KashshafError> {
     let app_state = require_state(&state)?;
     let search_term1 = SearchTerm {
@@ -477,6 +648,9 @@ pub async fn proximity_search(
     let filters = filters.unwrap_or_default();
     let limit = limit.unwrap_or(50);
     let offset = offset.unwrap_or(0);
+    let mut options = options.unwrap_or_default();
+    let running = RunningSearch::start(&window, search_id);
+    options.control = running.control.clone();
 
     let search_engine = app_state.search_engine.clone();
 
@@ -489,8 +663,9 @@ pub async fn proximity_search(
                 &filters,
                 limit,
                 offset,
+                &options,
             )
-            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+            .map_err(search_error)
     })
     .await
     .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
@@ -534,6 +709,13 @@ pub fn get_cache_stats(state: State<'_, ManagedAppState>) -> Result<(usize, usiz
     Ok(app_state.token_cache.stats())
 }
 
+/// Get hit/miss counters of the search result cache
+#[tauri::command]
+pub fn get_result_cache_stats(state: State<'_, ManagedAppState>) -> Result<ResultCacheStats, KashshafError> {
+    let app_state = require_state(&state)?;
+    Ok(app_state.search_engine.result_cache_stats())
+}
+
 #[tauri::command]
 pub fn clear_token_cache(state: State<'_, ManagedAppState>) -> Result<(), KashshafError> {
     let app_state = require_state(&state)?;
@@ -590,61 +772,102 @@ pub fn get_page_with_matches(
 
 #[tauri::command]
 pub async fn combined_search(
+    window: tauri::Window,
     state: State<'_, ManagedAppState>,
     and_terms: Vec<SearchTerm>,
     or_terms: Vec<SearchTerm>,
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
+    search_id: Option<String>,
 ) -> Result<SearchResults, KashshafError> {
     let app_state = require_state(&state)?;
     let filters = filters.unwrap_or_default();
     let limit = limit.unwrap_or(50);
     let offset = offset.unwrap_or(0);
+    let mut options = options.unwrap_or_default();
+    let running = RunningSearch::start(&window, search_id);
+    options.control = running.control.clone();
 
     let search_engine = app_state.search_engine.clone();
 
     tokio::task::spawn_blocking(move || {
         search_engine
-            .combined_search(&and_terms, &or_terms, &filters, limit, offset)
-            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+            .combined_search(&and_terms, &or_terms, &filters, limit, offset, &options)
+            .map_err(search_error)
     })
     .await
     .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NameSearchForm {
-    pub patterns: Vec<String>,
-}
-
 #[tauri::command]
 pub async fn name_search(
+    window: tauri::Window,
     state: State<'_, ManagedAppState>,
     forms: Vec<NameSearchForm>,
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
+    search_id: Option<String>,
 ) -> Result<SearchResults, KashshafError> {
     let app_state = require_state(&state)?;
     let filters = filters.unwrap_or_default();
     let limit = limit.unwrap_or(50);
     let offset = offset.unwrap_or(0);
-
-    // Convert forms to the format expected by the search engine
-    let patterns_by_form: Vec<Vec<String>> = forms.into_iter().map(|f| f.patterns).collect();
-
-    let search_engine = app_state.search_engine.clone();
+    let mut options = options.unwrap_or_default();
+    let running = RunningSearch::start(&window, search_id);
+    options.control = running.control.clone();
 
     tokio::task::spawn_blocking(move || {
-        search_engine
-            .name_search(&patterns_by_form, &filters, limit, offset)
-            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+        let forms = resolve_name_forms(&app_state, forms)?;
+        app_state
+            .search_engine
+            .name_search(&forms, &filters, limit, offset, &options)
+            .map_err(search_error)
     })
     .await
     .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
 }
 
+/// Resolve registry persons and gazetteer places to their patterns: the engine
+/// only sees patterns and structured forms. Every command taking name forms,
+/// directly or in a MatchQuery, goes through here.
+fn resolve_name_forms(app_state: &AppState, forms: Vec<NameSearchForm>) -> Result<Vec<NameSearchForm>, KashshafError> {
+    // Person IDs stand for all of the person's forms in the registry
+    let forms = if forms.iter().any(|form| matches!(form, NameSearchForm::Person { .. })) {
+        let settings = get_persons_connection()?;
+        persons::resolve_person_forms(&settings, forms).map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?
+    } else {
+        forms
+    };
+    // Places stand for their spellings and nisbas in the gazetteer
+    if forms.iter().any(|form| matches!(form, NameSearchForm::Place { .. })) {
+        app_state
+            .gazetteer
+            .get()
+            .and_then(|gazetteer| gazetteer.resolve_place_forms(forms))
+            .map_err(|e: anyhow::Error| KashshafError::NotFound(e.to_string()))
+    } else {
+        Ok(forms)
+    }
+}
+
+/// A MatchQuery with its name forms resolved
+fn resolve_match_query(app_state: &AppState, query: MatchQuery) -> Result<MatchQuery, KashshafError> {
+    match query {
+        MatchQuery::Name { forms } => Ok(MatchQuery::Name { forms: resolve_name_forms(app_state, forms)? }),
+        query => Ok(query),
+    }
+}
+
+/// Search and preview patterns of structured name forms
+#[tauri::command]
+pub fn generate_name_patterns(forms: Vec<NameForm>) -> Result<Vec<NamePatterns>, KashshafError> {
+    Ok(forms.iter().map(NamePatterns::for_form).collect())
+}
+
 #[tauri::command]
 pub fn get_name_match_positions(
     state: State<'_, ManagedAppState>,
@@ -660,6 +883,382 @@ pub fn get_name_match_positions(
         .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
 }
 
+/// Get every match position of a query on a page (search results only carry a preview).
+/// Works for all search types; `limit` of None returns all remaining positions.
+#[tauri::command]
+pub async fn get_all_match_positions(
+    state: State<'_, ManagedAppState>,
+    id: u64,
+    part_index: u64,
+    page_id: u64,
+    query: MatchQuery,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<MatchPositions, KashshafError> {
+    let app_state = require_state(&state)?;
+    let offset = offset.unwrap_or(0);
+
+    tokio::task::spawn_blocking(move || {
+        let query = resolve_match_query(&app_state, query)?;
+        app_state
+            .search_engine
+            .get_all_match_positions(id, part_index, page_id, &query, offset, limit)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Explain how a search is interpreted: normalized terms, the Tantivy query,
+/// term document frequencies, effective filters, the report's own timings and, for a page,
+/// where each term matches on it.
+#[tauri::command]
+pub async fn explain_search(
+    state: State<'_, ManagedAppState>,
+    request: ExplainRequest,
+) -> Result<QueryExplanation, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        let request = ExplainRequest { query: resolve_match_query(&app_state, request.query)?, ..request };
+        app_state
+            .search_engine
+            .explain(&request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Rank the lemmas co-occurring with a node term by frequency, MI, t-score or log-likelihood
+#[tauri::command]
+pub async fn find_collocations(
+    state: State<'_, ManagedAppState>,
+    request: CollocationRequest,
+) -> Result<CollocationResults, KashshafError> {
+    let app_state = require_state(&state)?;
+    let search_engine = app_state.search_engine.clone();
+    let token_cache = app_state.token_cache.clone();
+
+    tokio::task::spawn_blocking(move || {
+        kashshaf_lib::find_collocations(&search_engine, &token_cache, &request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Other places where a span selected in the reader is quoted or paraphrased
+#[tauri::command]
+pub async fn find_similar_passages(
+    state: State<'_, ManagedAppState>,
+    request: SimilarPassagesRequest,
+) -> Result<SimilarPassages, KashshafError> {
+    let app_state = require_state(&state)?;
+    let search_engine = app_state.search_engine.clone();
+    let token_cache = app_state.token_cache.clone();
+
+    tokio::task::spawn_blocking(move || {
+        search_engine
+            .find_similar_passages(&token_cache, &request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Token-level alignment of two page ranges, e.g. two editions of the same work
+#[tauri::command]
+pub async fn collate_pages(
+    state: State<'_, ManagedAppState>,
+    request: CollationRequest,
+) -> Result<CollationResults, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        kashshaf_lib::collate(&corpus, &app_state.token_cache, &request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Chains of transmission on a page, each split into narrators and matn
+#[tauri::command]
+pub fn get_page_isnads(
+    state: State<'_, ManagedAppState>,
+    request: PageIsnadsRequest,
+) -> Result<Vec<Isnad>, KashshafError> {
+    let app_state = require_state(&state)?;
+    kashshaf_lib::page_isnads(&app_state.token_cache, &request)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// Chains of transmission containing the given narrators in order
+#[tauri::command]
+pub async fn search_isnads(
+    state: State<'_, ManagedAppState>,
+    request: IsnadSearchRequest,
+) -> Result<IsnadSearchResults, KashshafError> {
+    let app_state = require_state(&state)?;
+    let search_engine = app_state.search_engine.clone();
+    let token_cache = app_state.token_cache.clone();
+
+    tokio::task::spawn_blocking(move || {
+        kashshaf_lib::search_isnads(&search_engine, &token_cache, &request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Hijri date expressions on a page, with their years
+#[tauri::command]
+pub fn get_page_dates(
+    state: State<'_, ManagedAppState>,
+    request: PageDatesRequest,
+) -> Result<Vec<DateMention>, KashshafError> {
+    let app_state = require_state(&state)?;
+    kashshaf_lib::page_dates(&app_state.token_cache, &request)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// Dated events within a range of years AH, optionally written to a CSV file
+#[tauri::command]
+pub async fn search_dates(
+    state: State<'_, ManagedAppState>,
+    request: DateSearchRequest,
+) -> Result<DateSearchResults, KashshafError> {
+    let app_state = require_state(&state)?;
+    let search_engine = app_state.search_engine.clone();
+    let token_cache = app_state.token_cache.clone();
+
+    tokio::task::spawn_blocking(move || {
+        kashshaf_lib::search_dates(&search_engine, &token_cache, &request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Qur'anic verses quoted on a page, with the quoted token spans
+#[tauri::command]
+pub fn get_page_quran_citations(
+    state: State<'_, ManagedAppState>,
+    request: PageCitationsRequest,
+) -> Result<Vec<QuranCitation>, KashshafError> {
+    let app_state = require_state(&state)?;
+    let quran = app_state
+        .quran
+        .get()
+        .map_err(|e: anyhow::Error| KashshafError::NotFound(e.to_string()))?;
+    kashshaf_lib::page_quran_citations(&quran, &app_state.token_cache, &request)
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+}
+
+/// Pages quoting a verse (sura:aya), with the quoted token spans
+#[tauri::command]
+pub async fn search_verse_citations(
+    state: State<'_, ManagedAppState>,
+    request: VerseSearchRequest,
+) -> Result<VerseSearchResults, KashshafError> {
+    let app_state = require_state(&state)?;
+    let search_engine = app_state.search_engine.clone();
+    let token_cache = app_state.token_cache.clone();
+    let quran_store = app_state.quran.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let quran = quran_store
+            .get()
+            .map_err(|e: anyhow::Error| KashshafError::NotFound(e.to_string()))?;
+        kashshaf_lib::search_verse_citations(&quran, &search_engine, &token_cache, &request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Ranked lemma, root or surface frequency list of a book, author, collection or filter slice
+#[tauri::command]
+pub async fn get_frequency_list(
+    state: State<'_, ManagedAppState>,
+    request: FrequencyRequest,
+) -> Result<FrequencyList, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let settings = get_settings_connection()?;
+        ensure_collections_table(&settings)?;
+
+        let book_ids = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.subcorpus)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        app_state
+            .frequencies
+            .frequency_list(book_ids.as_deref(), request.layer, &request.options)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Keywords distinguishing a target subcorpus from a reference one
+#[tauri::command]
+pub async fn compare_keyness(
+    state: State<'_, ManagedAppState>,
+    request: KeynessRequest,
+) -> Result<KeynessResults, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let settings = get_settings_connection()?;
+        ensure_collections_table(&settings)?;
+
+        let target = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.target)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let reference = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.reference)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        kashshaf_lib::compare_keyness(&app_state.frequencies, target.as_deref(), reference.as_deref(), &request.options)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Recurring surface or lemma n-grams of a subcorpus, optionally written to a TSV file
+#[tauri::command]
+pub async fn extract_ngrams(
+    state: State<'_, ManagedAppState>,
+    request: NgramRequest,
+) -> Result<NgramResults, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let settings = get_settings_connection()?;
+        ensure_collections_table(&settings)?;
+
+        let book_ids = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.subcorpus)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        kashshaf_lib::extract_ngrams(&app_state.db_path, book_ids.as_deref(), &request.options)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Global text reuse index build control (only one build at a time)
+static REUSE_BUILD_CONTROL: Mutex<Option<SearchControl>> = Mutex::new(None);
+
+/// Books and pages covered by the text reuse index
+#[tauri::command]
+pub async fn get_reuse_index_status(state: State<'_, ManagedAppState>) -> Result<ReuseIndexStatus, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        app_state
+            .text_reuse
+            .status()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Build (or resume building) the text reuse index, emitting "reuse-index-progress" after each book
+#[tauri::command]
+pub async fn build_reuse_index(
+    window: tauri::Window,
+    state: State<'_, ManagedAppState>,
+) -> Result<ReuseIndexStatus, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    let control = SearchControl::new();
+    {
+        let mut guard = REUSE_BUILD_CONTROL.lock().unwrap();
+        if guard.is_some() {
+            return Err(KashshafError::Other("The text reuse index is already being built".to_string()));
+        }
+        *guard = Some(control.clone());
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        app_state
+            .text_reuse
+            .build(&control, |progress| {
+                let _ = window.emit("reuse-index-progress", progress);
+            })
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)));
+
+    {
+        let mut guard = REUSE_BUILD_CONTROL.lock().unwrap();
+        *guard = None;
+    }
+
+    result?
+}
+
+/// Stop the running text reuse index build after the current book
+#[tauri::command]
+pub fn cancel_reuse_index_build() -> Result<(), KashshafError> {
+    let guard = REUSE_BUILD_CONTROL.lock().unwrap();
+    if let Some(ref control) = *guard {
+        control.cancel();
+        Ok(())
+    } else {
+        Err(KashshafError::Other("No text reuse index build in progress".to_string()))
+    }
+}
+
+/// Where else does this page's text appear?
+#[tauri::command]
+pub async fn find_page_reuse(
+    state: State<'_, ManagedAppState>,
+    request: PageReuseRequest,
+) -> Result<ReuseResults, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        app_state
+            .text_reuse
+            .page_reuse(&request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Passages of other books sharing text with a book, page by page
+#[tauri::command]
+pub async fn find_book_reuse(
+    state: State<'_, ManagedAppState>,
+    request: BookReuseRequest,
+) -> Result<ReuseResults, KashshafError> {
+    let app_state = require_state(&state)?;
+
+    tokio::task::spawn_blocking(move || {
+        app_state
+            .text_reuse
+            .book_reuse(&request)
+            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
 /// Wildcard search - searches for Arabic text with * wildcards
 /// Only works in Surface mode
 /// Rules:
@@ -668,19 +1267,22 @@ pub fn get_name_match_positions(
 /// - Internal * requires 2+ chars before it
 #[tauri::command]
 pub async fn wildcard_search(
+    window: tauri::Window,
     state: State<'_, ManagedAppState>,
     query: String,
     filters: Option<SearchFilters>,
     limit: Option<usize>,
     offset: Option<usize>,
+    options: Option<SearchOptions>,
+    search_id: Option<String>,
 ) -> Result<SearchResults, KashshafError> {
-    use kashshaf_lib::search::parse_wildcard_query;
-    use kashshaf_lib::tokens::PageKey;
-
     let app_state = require_state(&state)?;
     let filters = filters.unwrap_or_default();
     let limit = limit.unwrap_or(50);
     let offset = offset.unwrap_or(0);
+    let mut options = options.unwrap_or_default();
+    let running = RunningSearch::start(&window, search_id);
+    options.control = running.control.clone();
 
     // Validate the query first
     if let Err(e) = validate_wildcard_query(&query, SearchMode::Surface) {
@@ -688,38 +1290,75 @@ pub async fn wildcard_search(
     }
 
     let search_engine = app_state.search_engine.clone();
-    let token_cache = app_state.token_cache.clone();
-    let query_clone = query.clone();
 
+    // The engine verifies multi-word phrases and highlights only complete matches,
+    // before the snippets are cut around them
     tokio::task::spawn_blocking(move || {
-        let mut results = search_engine
-            .wildcard_search(&query_clone, &filters, limit, offset)
-            .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))?;
-
-        // For multi-word wildcard phrases, recalculate matched_token_indices
-        // using the token cache to ensure only complete phrase matches are highlighted
-        let query_info = parse_wildcard_query(&query_clone);
-        if query_info.terms.len() > 1 {
-            for result in &mut results.results {
-                let page_key = PageKey::new(result.id, result.page_id);
-                if let Ok(positions) = token_cache.find_wildcard_phrase_positions(
-                    &page_key,
-                    &query_info.prefix,
-                    query_info.suffix.as_deref(),
-                    query_info.wildcard_term_index,
-                    &query_info.terms,
-                ) {
-                    result.matched_token_indices = positions;
-                }
-            }
+        search_engine
+            .wildcard_search(&query, &filters, limit, offset, &options)
+            .map_err(search_error)
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Control of the running export
+static EXPORT_CONTROL: Mutex<Option<SearchControl>> = Mutex::new(None);
+
+/// Export every hit of a search to CSV, TSV, JSONL or XLSX - emits "export-progress" events
+#[tauri::command]
+pub async fn export_search_results(
+    window: tauri::Window,
+    state: State<'_, ManagedAppState>,
+    request: ExportRequest,
+) -> Result<ExportSummary, KashshafError> {
+    let app_state = require_state(&state)?;
+    let search_engine = app_state.search_engine.clone();
+
+    let control = SearchControl::new();
+    {
+        let mut guard = EXPORT_CONTROL.lock().unwrap();
+        if guard.is_some() {
+            return Err(KashshafError::Other("An export is already in progress".to_string()));
         }
+        *guard = Some(control.clone());
+    }
 
-        Ok(results)
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let books = kashshaf_lib::load_export_book_info(&conn)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let request = ExportRequest { query: resolve_match_query(&app_state, request.query)?, ..request };
+
+        kashshaf_lib::export_search_results(&search_engine, &books, &request, &control, |progress| {
+            let _ = window.emit("export-progress", progress);
+        })
+        .map_err(|e: anyhow::Error| KashshafError::Search(e.to_string()))
     })
     .await
-    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)));
+
+    {
+        let mut guard = EXPORT_CONTROL.lock().unwrap();
+        *guard = None;
+    }
+
+    result?
 }
 
+/// Cancel the running export
+#[tauri::command]
+pub fn cancel_export() -> Result<(), KashshafError> {
+    let guard = EXPORT_CONTROL.lock().unwrap();
+    if let Some(ref control) = *guard {
+        control.cancel();
+        Ok(())
+    } else {
+        Err(KashshafError::Other("No export in progress".to_string()))
+    }
+}
 
 #[tauri::command]
 pub async fn show_app_menu(app: AppHandle, x: f64, y: f64) -> Result<String, KashshafError> {
@@ -1220,6 +1859,14 @@ pub fn archive_old_corpus(version: String) -> Result<String, KashshafError> {
 pub async fn reload_app_state(state: State<'_, ManagedAppState>) -> Result<bool, KashshafError> {
     let data_dir = get_corpus_data_directory().map_err(|e| KashshafError::Other(e.to_string()))?;
 
+    // The corpus on disk changed under the current engine, which keeps serving
+    // if the new state fails to open; its cached doc addresses are stale either way
+    if let Ok(guard) = state.read() {
+        if let Some(old_state) = guard.as_ref() {
+            old_state.search_engine.clear_result_cache();
+        }
+    }
+
     // Try to create new AppState
     match AppState::new(data_dir) {
         Ok(new_state) => {
@@ -1613,6 +2260,171 @@ pub fn delete_collection(id: i64) -> Result<(), KashshafError> {
     Ok(())
 }
 
+// ============ Persons Commands ============
+
+/// Settings DB connection with the persons table
+fn get_persons_connection() -> Result<rusqlite::Connection, KashshafError> {
+    let conn = get_settings_connection()?;
+    persons::ensure_persons_table(&conn).map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+    Ok(conn)
+}
+
+/// Add a person to the registry
+#[tauri::command]
+pub fn create_person(person: PersonInput) -> Result<Person, KashshafError> {
+    let conn = get_persons_connection()?;
+    persons::create_person(&conn, &person).map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+}
+
+/// All persons in the registry, by name
+#[tauri::command]
+pub fn get_persons() -> Result<Vec<Person>, KashshafError> {
+    let conn = get_persons_connection()?;
+    persons::list_persons(&conn).map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+}
+
+/// Replace a person's name, forms, death date and notes
+#[tauri::command]
+pub fn update_person(id: i64, person: PersonInput) -> Result<Person, KashshafError> {
+    let conn = get_persons_connection()?;
+    persons::update_person(&conn, id, &person).map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+}
+
+/// Remove a person from the registry
+#[tauri::command]
+pub fn delete_person(id: i64) -> Result<(), KashshafError> {
+    let conn = get_persons_connection()?;
+    persons::delete_person(&conn, id).map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))
+}
+
+/// Count mentions of persons across the corpus or a subcorpus.
+/// Progress is reported per person through search-progress under the search ID.
+#[tauri::command]
+pub async fn count_person_mentions(
+    window: tauri::Window,
+    state: State<'_, ManagedAppState>,
+    request: PersonMentionsRequest,
+    search_id: Option<String>,
+) -> Result<PersonMentionCounts, KashshafError> {
+    let app_state = require_state(&state)?;
+    let running = RunningSearch::start(&window, search_id);
+
+    tokio::task::spawn_blocking(move || {
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let settings = get_persons_connection()?;
+        ensure_collections_table(&settings)?;
+
+        let book_ids = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.subcorpus)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let persons = persons::requested_persons(&settings, &request.person_ids)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        persons::count_person_mentions(&app_state.search_engine, &persons, book_ids.as_deref(), &running.control)
+            .map_err(search_error)
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+/// Build the co-mention network of a list of persons, optionally writing it to a file.
+/// Progress events carry the search_id, as for searches.
+#[tauri::command]
+pub async fn build_co_mention_network(
+    window: tauri::Window,
+    state: State<'_, ManagedAppState>,
+    request: NetworkRequest,
+    search_id: Option<String>,
+) -> Result<CoMentionNetwork, KashshafError> {
+    let app_state = require_state(&state)?;
+    let running = RunningSearch::start(&window, search_id);
+
+    tokio::task::spawn_blocking(move || {
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let settings = get_persons_connection()?;
+        ensure_collections_table(&settings)?;
+
+        let book_ids = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.subcorpus)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let persons = request
+            .persons
+            .into_iter()
+            .map(|person| Ok(NetworkPerson { forms: resolve_name_forms(&app_state, person.forms)?, ..person }))
+            .collect::<Result<Vec<_>, KashshafError>>()?;
+
+        let network = network::co_mention_network(
+            &app_state.search_engine,
+            &corpus,
+            &persons,
+            book_ids.as_deref(),
+            &request.options,
+            &running.control,
+        )
+        .map_err(search_error)?;
+        if let Some(path) = &request.path {
+            network::write_network(&network, request.format, path)
+                .map_err(|e: anyhow::Error| KashshafError::Other(e.to_string()))?;
+        }
+        Ok(network)
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
+// ============ Place Commands ============
+
+/// Gazetteer places whose URI or a name contains the query
+#[tauri::command]
+pub fn find_places(state: State<'_, ManagedAppState>, query: String, limit: Option<usize>) -> Result<Vec<Place>, KashshafError> {
+    let app_state = require_state(&state)?;
+    let gazetteer = app_state
+        .gazetteer
+        .get()
+        .map_err(|e: anyhow::Error| KashshafError::NotFound(e.to_string()))?;
+    Ok(gazetteer.search(&query, limit.unwrap_or(20)))
+}
+
+/// Count mentions of gazetteer places (all of them by default), optionally writing them as GeoJSON.
+/// Progress events carry the search_id, as for searches.
+#[tauri::command]
+pub async fn count_place_mentions(
+    window: tauri::Window,
+    state: State<'_, ManagedAppState>,
+    request: PlaceMentionsRequest,
+    search_id: Option<String>,
+) -> Result<PlaceMentionCounts, KashshafError> {
+    let app_state = require_state(&state)?;
+    let running = RunningSearch::start(&window, search_id);
+
+    tokio::task::spawn_blocking(move || {
+        let gazetteer = app_state
+            .gazetteer
+            .get()
+            .map_err(|e: anyhow::Error| KashshafError::NotFound(e.to_string()))?;
+        let places = toponyms::requested_places(&gazetteer, &request.places)
+            .map_err(|e: anyhow::Error| KashshafError::NotFound(e.to_string()))?;
+
+        let corpus = app_state
+            .get_db_connection()
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+        let settings = get_settings_connection()?;
+        ensure_collections_table(&settings)?;
+        let book_ids = kashshaf_lib::resolve_subcorpus(&corpus, &settings, &request.subcorpus)
+            .map_err(|e: anyhow::Error| KashshafError::Database(e.to_string()))?;
+
+        let counts = toponyms::count_place_mentions(&app_state.search_engine, &places, book_ids.as_deref(), &running.control)
+            .map_err(search_error)?;
+        if let Some(path) = &request.path {
+            counts.write_geojson(path).map_err(|e: anyhow::Error| KashshafError::Other(e.to_string()))?;
+        }
+        Ok(counts)
+    })
+    .await
+    .map_err(|e| KashshafError::Search(format!("Task join error: {}", e)))?
+}
+
 /// Fetch announcements from CDN using reqwest (no CORS restrictions)
 /// Returns the manifest or an error
 #[tauri::command]
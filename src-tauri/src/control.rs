@@ -0,0 +1,148 @@
+//! Cancellation and progress reporting for running searches
+//!
+//! A SearchControl travels with SearchOptions. Collectors stop collecting once
+//! it is cancelled and the candidate verification loops bail out with
+//! SearchCancelled; those loops also report progress through it, since
+//! post-filtering is the slow phase of proximity and wildcard searches.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader};
+
+/// Candidates verified between progress reports
+pub const PROGRESS_INTERVAL: usize = 500;
+
+/// Returned (inside anyhow::Error) by a search that was cancelled
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Search cancelled")]
+pub struct SearchCancelled;
+
+/// Progress of a search's candidate verification phase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchProgress {
+    /// Candidates checked so far
+    pub checked: usize,
+    /// Candidates fetched for verification
+    pub candidates: usize,
+    /// Candidates that passed verification so far
+    pub hits: usize,
+}
+
+type ProgressFn = dyn Fn(&SearchProgress) + Send + Sync;
+
+/// Cancellation token and progress callback for one search
+#[derive(Clone, Default)]
+pub struct SearchControl {
+    cancelled: Arc<AtomicBool>,
+    progress: Option<Arc<ProgressFn>>,
+}
+
+impl fmt::Debug for SearchControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchControl")
+            .field("cancelled", &self.is_cancelled())
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl SearchControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `on_progress` during long verification phases
+    pub fn with_progress(mut self, on_progress: impl Fn(&SearchProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Err(SearchCancelled) once the search has been cancelled
+    pub fn check(&self) -> Result<(), SearchCancelled> {
+        if self.is_cancelled() {
+            Err(SearchCancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether both controls belong to the same search
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+
+    pub fn report(&self, progress: &SearchProgress) {
+        if let Some(on_progress) = &self.progress {
+            on_progress(progress);
+        }
+    }
+
+    /// Wrap a collector so it stops collecting once the search is cancelled
+    pub fn collector<C: Collector>(&self, inner: C) -> CancellableCollector<C> {
+        CancellableCollector {
+            inner,
+            cancelled: self.cancelled.clone(),
+        }
+    }
+}
+
+pub struct CancellableCollector<C> {
+    inner: C,
+    cancelled: Arc<AtomicBool>,
+}
+
+pub struct CancellableSegmentCollector<S> {
+    inner: S,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<C: Collector> Collector for CancellableCollector<C> {
+    type Fruit = C::Fruit;
+    type Child = CancellableSegmentCollector<C::Child>;
+
+    fn for_segment(&self, segment_ord: SegmentOrdinal, segment_reader: &SegmentReader) -> tantivy::Result<Self::Child> {
+        Ok(CancellableSegmentCollector {
+            inner: self.inner.for_segment(segment_ord, segment_reader)?,
+            cancelled: self.cancelled.clone(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        self.inner.requires_scoring()
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<<Self::Child as SegmentCollector>::Fruit>) -> tantivy::Result<Self::Fruit> {
+        self.inner.merge_fruits(segment_fruits)
+    }
+}
+
+impl<S: SegmentCollector> SegmentCollector for CancellableSegmentCollector<S> {
+    type Fruit = S::Fruit;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        if !self.cancelled.load(Ordering::Relaxed) {
+            self.inner.collect(doc, score);
+        }
+    }
+
+    fn collect_block(&mut self, docs: &[DocId]) {
+        if !self.cancelled.load(Ordering::Relaxed) {
+            self.inner.collect_block(docs);
+        }
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.inner.harvest()
+    }
+}
@@ -0,0 +1,451 @@
+//! Hijri date expressions
+//!
+//! Chronicles date events in words: "وفي رجب سنة ثلاث وعشرين ومائتين". A date
+//! is سنة or عام followed by a year, spelled out (units, tens, hundreds and
+//! thousands joined by و, smallest first) or in digits. A month just before
+//! it ("في شهر رمضان من سنة ...") and a leading في are part of the expression.
+
+use crate::cache::TokenCache;
+use crate::cursor::SortKey;
+use crate::export::escape_csv;
+use crate::names::with_proclitics;
+use crate::search::{normalize_arabic, SearchEngine, SearchFilters};
+use crate::tokens::{PageKey, Token};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Words introducing a year
+const YEAR_WORDS: &[&str] = &["سنه", "عام"];
+
+/// Single-letter proclitics a date word can take
+const PROCLITICS: &[char] = &['و', 'ف', 'ب', 'ل'];
+
+/// Latest year recognized
+const MAX_YEAR: u32 = 1500;
+
+/// Number words (normalized, final ة as ه) and their values
+const UNITS: &[(&str, u32)] = &[
+    ("واحد", 1), ("احد", 1), ("احدي", 1),
+    ("اثنين", 2), ("اثنتين", 2), ("اثني", 2), ("اثنتي", 2), ("ثنتين", 2),
+    ("ثلاث", 3), ("ثلاثه", 3), ("اربع", 4), ("اربعه", 4), ("خمس", 5), ("خمسه", 5),
+    ("ست", 6), ("سته", 6), ("سبع", 7), ("سبعه", 7), ("ثمان", 8), ("ثماني", 8), ("ثمانيه", 8),
+    ("تسع", 9), ("تسعه", 9),
+];
+const TEN: &[&str] = &["عشر", "عشره"];
+const TENS: &[(&str, u32)] = &[
+    ("عشرين", 20), ("عشرون", 20), ("ثلاثين", 30), ("ثلاثون", 30), ("اربعين", 40), ("اربعون", 40),
+    ("خمسين", 50), ("خمسون", 50), ("ستين", 60), ("ستون", 60), ("سبعين", 70), ("سبعون", 70),
+    ("ثمانين", 80), ("ثمانون", 80), ("تسعين", 90), ("تسعون", 90),
+];
+const HUNDRED: &[&str] = &["مايه", "ميه"];
+const TWO_HUNDRED: &[&str] = &["مايتين", "مايتي", "مايتان", "ميتين", "ميتي"];
+/// Stems of ثلاثمائة ... تسعمائة
+const HUNDREDS_STEMS: &[(&str, u32)] = &[
+    ("ثلاث", 3), ("اربع", 4), ("خمس", 5), ("ست", 6), ("سبع", 7), ("ثمان", 8), ("ثماني", 8), ("تسع", 9),
+];
+const THOUSANDS: &[(&str, u32)] = &[("الف", 1000), ("الفين", 2000), ("الفا", 1000)];
+
+/// Months (normalized, final ة as ه); two-word names are matched before one-word ones
+const MONTHS: &[(&str, u8)] = &[
+    ("محرم", 1), ("المحرم", 1), ("صفر", 2),
+    ("ربيع الاول", 3), ("ربيع الاخر", 4), ("ربيع الثاني", 4), ("ربيع الاخره", 4),
+    ("جمادي الاولي", 5), ("جمادي الاول", 5), ("جمادي الاخره", 6), ("جمادي الاخر", 6), ("جمادي الاخري", 6), ("جمادي الثانيه", 6),
+    ("رجب", 7), ("شعبان", 8), ("رمضان", 9), ("شوال", 10),
+    ("ذي القعده", 11), ("ذو القعده", 11), ("ذي الحجه", 12), ("ذو الحجه", 12),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateMention {
+    pub book_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+    pub year: u32,
+    /// 1 (Muharram) to 12 (Dhu al-Hijja), when named
+    pub month: Option<u8>,
+    /// Token range [start, end) of the expression
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageDatesRequest {
+    pub book_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateSearchRequest {
+    /// Years AH, inclusive
+    pub from_year: u32,
+    pub to_year: u32,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    /// Candidate pages scanned at most, the first by author death
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// Write the dates found to this file as CSV
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+fn default_max_pages() -> usize {
+    5000
+}
+
+fn default_limit() -> usize {
+    1000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateSearchResults {
+    /// Pages with a year word and a number word or digits of the range
+    pub total_pages: usize,
+    /// Pages read, the first by author death; fewer than `total_pages` when
+    /// `max_pages` or `limit` stopped the scan
+    pub pages_scanned: usize,
+    /// Some candidate pages were not scanned, so the dates are a sample
+    pub truncated: bool,
+    /// In result order, then page order
+    pub dates: Vec<DateMention>,
+    pub elapsed_ms: u64,
+}
+
+/// Normalized for the word lists: final ة as ه
+fn normalize(word: &str) -> String {
+    let word = normalize_arabic(word);
+    match word.strip_suffix('ة') {
+        Some(stem) => format!("{}ه", stem),
+        None => word,
+    }
+}
+
+/// The word, and the word without a leading proclitic if it has one
+fn without_proclitic(word: &str) -> (&str, Option<&str>) {
+    let bare = word.strip_prefix(PROCLITICS).filter(|rest| rest.chars().count() > 1);
+    (word, bare)
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], word: &str) -> Option<T> {
+    table.iter().find(|(entry, _)| *entry == word).map(|(_, value)| *value)
+}
+
+/// Value of a digit string, Western or Arabic-Indic
+fn digits_value(word: &str) -> Option<u32> {
+    if word.is_empty() || word.chars().count() > 4 {
+        return None;
+    }
+    word.chars()
+        .map(|c| match c {
+            '0'..='9' => c.to_digit(10),
+            '٠'..='٩' => Some(c as u32 - '٠' as u32),
+            '۰'..='۹' => Some(c as u32 - '۰' as u32),
+            _ => None,
+        })
+        .try_fold(0, |value, digit| Some(value * 10 + digit?))
+}
+
+/// One part of a spelled-out number starting with `word`: its value, its order
+/// (0 units, 1 tens, 2 hundreds, 3 thousands) and the words it takes
+fn number_part(word: &str, next: Option<&str>) -> Option<(u32, u8, usize)> {
+    if let Some(unit) = lookup(UNITS, word) {
+        return match next {
+            // ثلاث عشرة
+            Some(next) if TEN.contains(&next) => Some((unit + 10, 1, 2)),
+            // ثلاث مائة
+            Some(next) if HUNDRED.contains(&next) && unit >= 3 => Some((unit * 100, 2, 2)),
+            _ => Some((unit, 0, 1)),
+        };
+    }
+    if TEN.contains(&word) {
+        return Some((10, 1, 1));
+    }
+    if let Some(tens) = lookup(TENS, word) {
+        return Some((tens, 1, 1));
+    }
+    if HUNDRED.contains(&word) {
+        return Some((100, 2, 1));
+    }
+    if TWO_HUNDRED.contains(&word) {
+        return Some((200, 2, 1));
+    }
+    // ثلاثمائة
+    if let Some(hundreds) = HUNDRED
+        .iter()
+        .find_map(|hundred| word.strip_suffix(hundred).and_then(|stem| lookup(HUNDREDS_STEMS, stem)))
+    {
+        return Some((hundreds * 100, 2, 1));
+    }
+    if let Some(thousands) = lookup(THOUSANDS, word) {
+        return Some((thousands, 3, 1));
+    }
+    None
+}
+
+/// A year starting at words[i] and the number of words it takes. Parts after the
+/// first are joined by و and grow in order (ثلاث وعشرين ومائتين).
+fn parse_year(words: &[String], i: usize) -> Option<(u32, usize)> {
+    if let Some(year) = words.get(i).and_then(|word| digits_value(word)) {
+        return (1..=MAX_YEAR).contains(&year).then_some((year, 1));
+    }
+
+    let word = |j: usize| words.get(j).map(String::as_str);
+    let (mut year, mut order, mut len) = number_part(word(i)?, word(i + 1))?;
+    while let Some(bare) = word(i + len).and_then(|next| next.strip_prefix('و')) {
+        match number_part(bare, word(i + len + 1)) {
+            Some((value, next_order, taken)) if next_order > order => {
+                year += value;
+                order = next_order;
+                len += taken;
+            }
+            _ => break,
+        }
+    }
+    (year <= MAX_YEAR).then_some((year, len))
+}
+
+/// A month name ending at words[end - 1], with its first word index
+fn month_before(words: &[String], end: usize) -> Option<(u8, usize)> {
+    for len in [2, 1] {
+        let Some(start) = end.checked_sub(len) else {
+            continue;
+        };
+        let (first, bare) = without_proclitic(&words[start]);
+        for first in std::iter::once(first).chain(bare) {
+            let name = std::iter::once(first).chain(words[start + 1..end].iter().map(String::as_str)).collect::<Vec<_>>().join(" ");
+            if let Some(month) = lookup(MONTHS, &name) {
+                return Some((month, start));
+            }
+        }
+    }
+    None
+}
+
+fn is_word(word: &str, words: &[&str]) -> bool {
+    let (word, bare) = without_proclitic(word);
+    words.contains(&word) || bare.is_some_and(|bare| words.contains(&bare))
+}
+
+/// Dates on a page's tokens, without their location
+fn extract(tokens: &[Token]) -> Vec<DateMention> {
+    let words: Vec<String> = tokens.iter().map(|token| normalize(&token.surface)).collect();
+
+    let mut dates = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        if !is_word(&words[i], YEAR_WORDS) {
+            i += 1;
+            continue;
+        }
+        let Some((year, len)) = parse_year(&words, i + 1) else {
+            i += 1;
+            continue;
+        };
+
+        // Back over "في [شهر] رجب [من]"
+        let mut start = i;
+        let mut month = None;
+        let before = if start > 0 && is_word(&words[start - 1], &["من"]) { start - 1 } else { start };
+        if let Some((found, month_start)) = month_before(&words, before) {
+            month = Some(found);
+            start = month_start;
+            if start > 0 && is_word(&words[start - 1], &["شهر"]) {
+                start -= 1;
+            }
+        }
+        if start > 0 && is_word(&words[start - 1], &["في"]) {
+            start -= 1;
+        }
+
+        let end = i + 1 + len;
+        let text = tokens[start..end].iter().map(|token| token.surface.as_str()).collect::<Vec<_>>().join(" ");
+        dates.push(DateMention { book_id: 0, part_index: 0, page_id: 0, year, month, start, end, text });
+        i = end;
+    }
+    dates
+}
+
+fn locate(mut dates: Vec<DateMention>, key: &SortKey) -> Vec<DateMention> {
+    for date in &mut dates {
+        date.book_id = key.text_id;
+        date.part_index = key.part_index;
+        date.page_id = key.page_id;
+    }
+    dates
+}
+
+/// Words of the hundreds of a year (none below 100)
+fn hundreds_words(century: u32) -> Vec<String> {
+    match century {
+        0 => Vec::new(),
+        1 => HUNDRED.iter().map(|word| word.to_string()).collect(),
+        2 => TWO_HUNDRED.iter().map(|word| word.to_string()).collect(),
+        3..=9 => HUNDREDS_STEMS
+            .iter()
+            .filter(|(_, value)| *value == century)
+            .flat_map(|(stem, _)| HUNDRED.iter().flat_map(move |hundred| [format!("{}{}", stem, hundred), format!("{} {}", stem, hundred)]))
+            .collect(),
+        _ => THOUSANDS.iter().map(|(word, _)| word.to_string()).collect(),
+    }
+}
+
+/// Unit and tens words of a year below 100
+fn small_year_words(year: u32) -> Vec<String> {
+    let (tens, units) = (year / 10, year % 10);
+    let mut words: Vec<String> = UNITS.iter().filter(|(_, value)| units > 0 && *value == units).map(|(word, _)| word.to_string()).collect();
+    match tens {
+        0 => {}
+        1 => words.extend(TEN.iter().map(|word| word.to_string())),
+        _ => words.extend(TENS.iter().filter(|(_, value)| *value == tens * 10).map(|(word, _)| word.to_string())),
+    }
+    words
+}
+
+/// Both spellings of the final ta marbuta, with any proclitic, as search patterns
+fn word_patterns(words: Vec<String>) -> Vec<String> {
+    words
+        .iter()
+        .flat_map(|word| [word.clone(), word.strip_suffix('ه').map(|stem| format!("{}ة", stem)).unwrap_or_default()])
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| with_proclitics(&word))
+        .collect()
+}
+
+/// Candidate pages for a year range: a year word, and a number word of the
+/// range (the hundreds, or for years below 100 the units and tens) or the year in digits.
+/// None if no year of the range can be written (0 AH), as a year word alone would match
+/// every page with سنة or عام.
+fn candidate_patterns(from_year: u32, to_year: u32) -> Option<Vec<Vec<String>>> {
+    let year_words: Vec<String> = ["سنة", "سنه", "عام"].iter().flat_map(|word| with_proclitics(word)).collect();
+    let mut words: Vec<String> = (from_year / 100..=to_year / 100).flat_map(hundreds_words).collect();
+    words.extend((from_year..=to_year.min(99)).flat_map(small_year_words));
+    words.sort();
+    words.dedup();
+
+    let mut years = word_patterns(words);
+    for year in from_year.max(1)..=to_year {
+        years.push(year.to_string());
+        years.push(year.to_string().chars().map(|c| char::from_u32(c as u32 - '0' as u32 + '٠' as u32).unwrap_or(c)).collect());
+    }
+    (!years.is_empty()).then(|| vec![year_words, years])
+}
+
+/// Dates on one page
+pub fn page_dates(token_cache: &TokenCache, request: &PageDatesRequest) -> Result<Vec<DateMention>> {
+    let tokens = token_cache.get(&PageKey::new(request.book_id, request.page_id))?;
+    let key = SortKey { death_ah: 0, text_id: request.book_id, part_index: request.part_index, page_id: request.page_id };
+    Ok(locate(extract(&tokens), &key))
+}
+
+/// Dates within a year range
+pub fn search_dates(engine: &SearchEngine, token_cache: &TokenCache, request: &DateSearchRequest) -> Result<DateSearchResults> {
+    let start = std::time::Instant::now();
+    if request.from_year > request.to_year || request.to_year > MAX_YEAR {
+        return Err(anyhow!("Year range must be within 0-{} AH, from <= to", MAX_YEAR));
+    }
+
+    let (total_pages, pages) = match candidate_patterns(request.from_year, request.to_year) {
+        Some(patterns) => engine.pattern_pages(&patterns, &request.filters, request.max_pages)?,
+        None => (0, Vec::new()),
+    };
+
+    let mut dates = Vec::new();
+    let mut pages_scanned = 0;
+    for key in &pages {
+        pages_scanned += 1;
+        let tokens = token_cache.get(&PageKey::new(key.text_id, key.page_id))?;
+        let in_range = |date: &DateMention| (request.from_year..=request.to_year).contains(&date.year);
+        dates.extend(locate(extract(&tokens), key).into_iter().filter(in_range));
+        if dates.len() >= request.limit {
+            dates.truncate(request.limit);
+            break;
+        }
+    }
+
+    let results = DateSearchResults {
+        total_pages,
+        pages_scanned,
+        truncated: pages_scanned < total_pages,
+        dates,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    };
+    if let Some(path) = &request.path {
+        write_dates_csv(&results.dates, path)?;
+    }
+    Ok(results)
+}
+
+/// Write dates as CSV, one row per expression
+pub fn write_dates_csv(dates: &[DateMention], path: &Path) -> Result<()> {
+    // BOM so Excel detects UTF-8 Arabic text
+    let mut out = String::from("\u{FEFF}Book ID,Part,Page ID,Year AH,Month,Start,End,Text\n");
+    for date in dates {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            date.book_id,
+            date.part_index,
+            date.page_id,
+            date.year,
+            date.month.map(|month| month.to_string()).unwrap_or_default(),
+            date.start,
+            date.end,
+            escape_csv(&date.text)
+        );
+    }
+    std::fs::write(path, out).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<Token> {
+        text.split_whitespace()
+            .enumerate()
+            .map(|(idx, surface)| Token {
+                idx,
+                surface: surface.to_string(),
+                noclitic_surface: None,
+                lemma: surface.to_string(),
+                root: None,
+                pos: "noun".to_string(),
+                features: Vec::new(),
+                clitics: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn years(text: &str) -> Vec<(u32, Option<u8>, usize, usize)> {
+        extract(&tokens(text)).into_iter().map(|date| (date.year, date.month, date.start, date.end)).collect()
+    }
+
+    #[test]
+    fn test_extract_dates() {
+        assert_eq!(years("وفي رجب سنة ثلاث وعشرين ومائتين مات فلان"), [(223, Some(7), 0, 6)]);
+        assert_eq!(years("ثم دخلت سنة ثلاث عشرة وثلاثمائة"), [(313, None, 2, 6)]);
+        assert_eq!(years("في شهر ربيع الأول من سنة خمس وستين وأربع مائة"), [(465, Some(3), 0, 10)]);
+        assert_eq!(years("توفي سنة عشر ومائة وقيل سنة ٢٥٥"), [(110, None, 1, 4), (255, None, 5, 7)]);
+        assert_eq!(years("في عام الفيل"), []);
+        assert_eq!(years("على سنة رسول الله"), []);
+        // Parts grow in order; a smaller one after a larger one is not part of the year
+        assert_eq!(years("سنة ستين وخمس"), [(60, None, 0, 2)]);
+
+        let patterns = candidate_patterns(200, 250).unwrap();
+        assert!(patterns[1].contains(&"ومايتين".to_string()));
+        assert!(patterns[1].contains(&"٢٢٣".to_string()));
+        // Below 100 the units and tens words narrow the pages too
+        let patterns = candidate_patterns(13, 20).unwrap();
+        assert!(patterns[1].contains(&"وثلاث".to_string()));
+        assert!(patterns[1].contains(&"عشرة".to_string()));
+        assert!(patterns[1].contains(&"عشرين".to_string()));
+        assert!(!patterns[1].contains(&"ثلاثين".to_string()));
+        assert!(!patterns[1].contains(&"ومايه".to_string()));
+        assert!(candidate_patterns(0, 0).is_none());
+    }
+}
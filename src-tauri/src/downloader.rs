@@ -11,6 +11,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tokio::sync::mpsc;
 
 /// R2 public bucket URLs
@@ -230,6 +231,20 @@ pub fn get_data_dir() -> PathBuf {
     PathBuf::from("data")
 }
 
+/// Directory of the resources bundled with the app, set once at startup
+static RESOURCE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Record where the app's bundled resources are (Tauri's resource directory)
+pub fn set_resource_dir(dir: PathBuf) {
+    let _ = RESOURCE_DIR.set(dir);
+}
+
+/// Path of a file bundled with the app (src-tauri/resources), if there is one
+pub fn bundled_resource(name: &str) -> Option<PathBuf> {
+    let path = RESOURCE_DIR.get()?.join("resources").join(name);
+    path.exists().then_some(path)
+}
+
 /// Get the application data directory (for backwards compatibility)
 /// Now returns the portable data directory's parent (or creates structure next to exe)
 pub fn get_app_data_directory() -> Result<PathBuf> {
@@ -287,6 +302,21 @@ pub fn load_local_manifest(data_dir: &Path) -> Option<LocalManifest> {
     serde_json::from_str(&content).ok()
 }
 
+/// Identifies the installed corpus for the caches derived from it: the manifest's
+/// corpus version, if any, with corpus.db's size and modification time, which
+/// also change on manual installs
+pub fn corpus_fingerprint(corpus_db_path: &Path) -> Result<String> {
+    let metadata = fs::metadata(corpus_db_path)
+        .with_context(|| format!("Failed to read {}", corpus_db_path.display()))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |age| age.as_nanos());
+    let version = corpus_db_path.parent().and_then(load_local_manifest).map(|manifest| manifest.corpus_version);
+    Ok(format!("{}:{}:{}", version.unwrap_or_default(), metadata.len(), modified))
+}
+
 /// Save local manifest to disk
 pub fn save_local_manifest(data_dir: &Path, manifest: &LocalManifest) -> Result<()> {
     let manifest_path = data_dir.join("manifest.local.json");
@@ -28,6 +28,9 @@ pub enum KashshafError {
     #[error("Corpus not ready: {0}")]
     CorpusNotReady(String),
 
+    #[error("Search cancelled")]
+    Cancelled,
+
     #[error("{0}")]
     Other(String),
 }
@@ -0,0 +1,373 @@
+//! Export of complete search hit lists
+//!
+//! Pages through a search with cursors until every hit has been written, so
+//! exports are not limited to what the result list has loaded. Rows are
+//! streamed to disk as CSV, TSV, JSONL or XLSX with a keyword-in-context
+//! window around the first match on each page.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::budget::{SearchBudget, TruncationReason};
+use crate::control::{SearchCancelled, SearchControl};
+use crate::search::{MatchQuery, SearchEngine, SearchFilters, SearchOptions, SearchResult};
+use crate::snippets::SnippetOptions;
+use crate::xlsx::{self, Cell, XlsxWriter};
+
+/// Hits fetched per search page while exporting
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Keyword-in-context window around the first match
+const KWIC_WINDOW: SnippetOptions = SnippetOptions {
+    tokens_before: 10,
+    tokens_after: 10,
+    max_snippets: 1,
+};
+
+const HEADERS: [&str; 13] = [
+    "Book ID",
+    "Title",
+    "Author",
+    "Death Year (AH)",
+    "Genre",
+    "Volume",
+    "Page",
+    "Part Index",
+    "Page ID",
+    "Hits",
+    "Left Context",
+    "Match",
+    "Right Context",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Jsonl,
+    Xlsx,
+}
+
+/// What to export and where
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRequest {
+    pub query: MatchQuery,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    pub format: ExportFormat,
+    pub path: PathBuf,
+}
+
+/// Progress reported after each search page is written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportProgress {
+    pub rows_written: usize,
+    /// Hit count reported by the first search page
+    pub total_hits: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub rows_written: usize,
+    /// Export was cancelled; the partial file has been removed
+    pub cancelled: bool,
+    /// Set when a search budget left hits out of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated_reason: Option<TruncationReason>,
+}
+
+/// Book metadata resolved for export rows
+#[derive(Debug, Clone, Default)]
+pub struct ExportBookInfo {
+    pub title: String,
+    pub author: String,
+    pub genre: String,
+}
+
+/// Load titles, authors and genres for every book from corpus.db
+pub fn load_export_book_info(conn: &Connection) -> Result<HashMap<u64, ExportBookInfo>> {
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.title, a.author, g.genre FROM books b
+         LEFT JOIN authors a ON a.id = b.author_id
+         LEFT JOIN genres g ON g.id = b.genre_id",
+    )?;
+
+    let books = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                ExportBookInfo {
+                    title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    author: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                    genre: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+
+    Ok(books)
+}
+
+/// One exported hit (one page)
+#[derive(Debug, Clone, Serialize)]
+struct ExportRow<'a> {
+    book_id: u64,
+    title: &'a str,
+    author: &'a str,
+    death_ah: Option<u64>,
+    genre: &'a str,
+    volume: &'a str,
+    page: &'a str,
+    part_index: u64,
+    page_id: u64,
+    hits: usize,
+    left_context: String,
+    keyword: String,
+    right_context: String,
+}
+
+impl ExportRow<'_> {
+    fn text_fields(&self) -> [String; 13] {
+        [
+            self.book_id.to_string(),
+            self.title.to_string(),
+            self.author.to_string(),
+            self.death_ah.map(|d| d.to_string()).unwrap_or_default(),
+            self.genre.to_string(),
+            self.volume.to_string(),
+            self.page.to_string(),
+            self.part_index.to_string(),
+            self.page_id.to_string(),
+            self.hits.to_string(),
+            self.left_context.clone(),
+            self.keyword.clone(),
+            self.right_context.clone(),
+        ]
+    }
+}
+
+/// Collapse runs of whitespace (including line breaks) into single spaces
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split the result's first snippet into left context, match and right context
+fn kwic(result: &SearchResult) -> (String, String, String) {
+    let Some(snippet) = result.snippets.first() else {
+        return (String::new(), String::new(), String::new());
+    };
+    let chars: Vec<char> = snippet.text.chars().collect();
+    let Some(highlight) = snippet.highlights.first() else {
+        return (collapse_whitespace(&snippet.text), String::new(), String::new());
+    };
+
+    let slice = |start: usize, end: usize| collapse_whitespace(&chars[start..end].iter().collect::<String>());
+    (
+        slice(0, highlight.start),
+        slice(highlight.start, highlight.end),
+        slice(highlight.end, chars.len()),
+    )
+}
+
+fn export_row<'a>(result: &'a SearchResult, books: &'a HashMap<u64, ExportBookInfo>) -> ExportRow<'a> {
+    let book = books.get(&result.id);
+    let (left_context, keyword, right_context) = kwic(result);
+    ExportRow {
+        book_id: result.id,
+        title: book.map(|b| b.title.as_str()).unwrap_or(""),
+        author: book.map(|b| b.author.as_str()).unwrap_or(""),
+        death_ah: result.death_ah,
+        genre: book.map(|b| b.genre.as_str()).unwrap_or(""),
+        volume: &result.part_label,
+        page: &result.page_number,
+        part_index: result.part_index,
+        page_id: result.page_id,
+        hits: result.match_count,
+        left_context,
+        keyword,
+        right_context,
+    }
+}
+
+/// Quote a CSV field when needed (same rules as escapeCSV in exportData.ts)
+pub(crate) fn escape_csv(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks become spaces
+fn escape_tsv(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+enum RowWriter {
+    Csv(BufWriter<File>),
+    Tsv(BufWriter<File>),
+    Jsonl(BufWriter<File>),
+    Xlsx(XlsxWriter),
+}
+
+impl RowWriter {
+    fn create(format: ExportFormat, path: &Path) -> Result<Self> {
+        let open = || -> Result<BufWriter<File>> {
+            let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+            Ok(BufWriter::new(file))
+        };
+
+        let mut writer = match format {
+            ExportFormat::Csv => {
+                let mut out = open()?;
+                // BOM so Excel detects UTF-8 Arabic text
+                out.write_all("\u{FEFF}".as_bytes())?;
+                RowWriter::Csv(out)
+            }
+            ExportFormat::Tsv => RowWriter::Tsv(open()?),
+            ExportFormat::Jsonl => RowWriter::Jsonl(open()?),
+            ExportFormat::Xlsx => RowWriter::Xlsx(XlsxWriter::create(path)?),
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        match self {
+            RowWriter::Csv(out) => writeln!(out, "{}", HEADERS.map(escape_csv).join(","))?,
+            RowWriter::Tsv(out) => writeln!(out, "{}", HEADERS.join("\t"))?,
+            RowWriter::Jsonl(_) => {}
+            RowWriter::Xlsx(xlsx) => xlsx.write_row(&HEADERS.map(Cell::Text))?,
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &ExportRow) -> Result<()> {
+        match self {
+            RowWriter::Csv(out) => {
+                let fields = row.text_fields();
+                writeln!(out, "{}", fields.iter().map(|f| escape_csv(f)).collect::<Vec<_>>().join(","))?;
+            }
+            RowWriter::Tsv(out) => {
+                let fields = row.text_fields();
+                writeln!(out, "{}", fields.iter().map(|f| escape_tsv(f)).collect::<Vec<_>>().join("\t"))?;
+            }
+            RowWriter::Jsonl(out) => {
+                serde_json::to_writer(&mut *out, row)?;
+                out.write_all(b"\n")?;
+            }
+            RowWriter::Xlsx(xlsx) => {
+                let number = |n: Option<u64>| n.map(Cell::Number).unwrap_or(Cell::Empty);
+                xlsx.write_row(&[
+                    Cell::Number(row.book_id),
+                    Cell::Text(row.title),
+                    Cell::Text(row.author),
+                    number(row.death_ah),
+                    Cell::Text(row.genre),
+                    Cell::Text(row.volume),
+                    Cell::Text(row.page),
+                    Cell::Number(row.part_index),
+                    Cell::Number(row.page_id),
+                    Cell::Number(row.hits as u64),
+                    Cell::Text(&row.left_context),
+                    Cell::Text(&row.keyword),
+                    Cell::Text(&row.right_context),
+                ])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            RowWriter::Csv(mut out) | RowWriter::Tsv(mut out) | RowWriter::Jsonl(mut out) => out.flush()?,
+            RowWriter::Xlsx(xlsx) => xlsx.finish("Results")?,
+        }
+        Ok(())
+    }
+}
+
+/// Run a search to completion and write every hit to `request.path`.
+/// Stops when `control` is cancelled; a cancelled export removes its partial file.
+pub fn export_search_results(
+    engine: &SearchEngine,
+    books: &HashMap<u64, ExportBookInfo>,
+    request: &ExportRequest,
+    control: &SearchControl,
+    mut on_progress: impl FnMut(&ExportProgress),
+) -> Result<ExportSummary> {
+    let mut writer = RowWriter::create(request.format, &request.path)?;
+    let mut options = SearchOptions {
+        snippets: Some(KWIC_WINDOW),
+        include_body: false,
+        cursor: None,
+        // Exports run until done; a per-page deadline would drop hits
+        budget: SearchBudget { max_millis: None, ..SearchBudget::default() },
+        pattern_hits: false,
+        control: control.clone(),
+    };
+    let mut rows_written = 0;
+    let mut total_hits = None;
+    let mut truncated_reason = None;
+
+    let result = loop {
+        if control.is_cancelled() {
+            break Ok(true);
+        }
+
+        let page = match engine.run_query(&request.query, &request.filters, EXPORT_PAGE_SIZE, 0, &options) {
+            Ok(page) => page,
+            Err(e) if e.is::<SearchCancelled>() => break Ok(true),
+            Err(e) => break Err(e),
+        };
+        let total_hits = *total_hits.get_or_insert(page.total_hits);
+
+        if let Err(e) = page
+            .results
+            .iter()
+            .try_for_each(|result| writer.write_row(&export_row(result, books)))
+        {
+            break Err(e);
+        }
+        rows_written += page.results.len();
+        // Candidate caps only shorten a page; the cursor picks up where it stopped
+        if let Some(reason) = page.truncated_reason.filter(|&r| r != TruncationReason::Candidates) {
+            truncated_reason.get_or_insert(reason);
+        }
+        on_progress(&ExportProgress { rows_written, total_hits });
+
+        match page.next_cursor {
+            Some(cursor) => options.cursor = Some(cursor),
+            None => break Ok(false),
+        }
+    };
+
+    match result {
+        Ok(false) => {
+            writer.finish()?;
+            Ok(ExportSummary { rows_written, cancelled: false, truncated_reason })
+        }
+        Ok(true) => {
+            drop(writer);
+            remove_partial_export(&request.path);
+            Ok(ExportSummary { rows_written, cancelled: true, truncated_reason })
+        }
+        Err(e) => {
+            drop(writer);
+            remove_partial_export(&request.path);
+            Err(e)
+        }
+    }
+}
+
+/// Remove the output file and any XLSX temp sheet left by an unfinished export
+fn remove_partial_export(path: &Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(xlsx::temp_sheet_path(path));
+}
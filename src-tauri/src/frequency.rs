@@ -0,0 +1,482 @@
+//! Frequency lists for subcorpora
+//!
+//! Counts come from the page_tokens and token_definitions tables of corpus.db.
+//! Token ID counts are kept per book, so overlapping subcorpora (an author, then
+//! one collection of theirs) only read the books they haven't seen yet. Whole-corpus
+//! lists are stored in frequencies.db next to corpus.db, which records the corpus
+//! they were counted from and is emptied once the corpus changes.
+
+use crate::downloader::corpus_fingerprint;
+use crate::search::SearchFilters;
+use crate::tokens::TokenField;
+use anyhow::{anyhow, Context, Result};
+use lru::LruCache;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Books whose token ID counts are kept in memory
+const BOOK_CACHE_CAPACITY: usize = 256;
+
+/// Subcorpus layer counts kept in memory
+const COUNTS_CACHE_CAPACITY: usize = 8;
+
+/// Token definitions looked up per query (SQLite limit is 999 variables)
+const DEFINITION_BATCH: usize = 500;
+
+/// Books to count: the intersection of every criterion given, or the whole corpus if none is
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Subcorpus {
+    pub filters: SearchFilters,
+    /// A collection from the settings database
+    pub collection_id: Option<i64>,
+}
+
+impl Subcorpus {
+    fn is_whole_corpus(&self) -> bool {
+        let filters = &self.filters;
+        self.collection_id.is_none()
+            && filters.book_ids.is_none()
+            && filters.author_id.is_none()
+            && filters.genre_id.is_none()
+            && filters.death_ah_min.is_none()
+            && filters.death_ah_max.is_none()
+            && filters.century_ah.is_none()
+    }
+}
+
+/// IDs of the books in a subcorpus, or None for the whole corpus.
+/// `settings` is only read for a collection.
+pub fn resolve_subcorpus(corpus: &Connection, settings: &Connection, subcorpus: &Subcorpus) -> Result<Option<Vec<u64>>> {
+    if subcorpus.is_whole_corpus() {
+        return Ok(None);
+    }
+    let filters = &subcorpus.filters;
+
+    let mut sql = String::from("SELECT id FROM books WHERE 1=1");
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    let mut add = |column: &str, op: &str, value: Option<u64>| {
+        if let Some(value) = value {
+            params.push(Box::new(value as i64));
+            sql.push_str(&format!(" AND {} {} ?{}", column, op, params.len()));
+        }
+    };
+    add("author_id", "=", filters.author_id);
+    add("genre_id", "=", filters.genre_id);
+    add("death_ah", ">=", filters.death_ah_min);
+    add("death_ah", "<=", filters.death_ah_max);
+    add("century_ah", "=", filters.century_ah);
+    sql.push_str(" ORDER BY id");
+
+    let mut book_ids: Vec<u64> = corpus
+        .prepare(&sql)?
+        .query_map(rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())), |row| row.get::<_, i64>(0))?
+        .collect::<rusqlite::Result<Vec<i64>>>()?
+        .into_iter()
+        .map(|id| id as u64)
+        .collect();
+
+    if let Some(ref wanted) = filters.book_ids {
+        let wanted: HashSet<u64> = wanted.iter().copied().collect();
+        book_ids.retain(|id| wanted.contains(id));
+    }
+
+    if let Some(collection_id) = subcorpus.collection_id {
+        let book_ids_json: String = settings
+            .query_row("SELECT book_ids FROM collections WHERE id = ?1", [collection_id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| anyhow!("Collection {} not found", collection_id))?;
+        let in_collection: HashSet<u64> = serde_json::from_str::<Vec<u64>>(&book_ids_json)?.into_iter().collect();
+        book_ids.retain(|id| in_collection.contains(id));
+    }
+
+    Ok(Some(book_ids))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrequencyOptions {
+    /// Entries below this count are left out
+    pub min_count: u64,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl Default for FrequencyOptions {
+    fn default() -> Self {
+        Self { min_count: 1, limit: 1000, offset: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrequencyRequest {
+    #[serde(default)]
+    pub subcorpus: Subcorpus,
+    pub layer: TokenField,
+    #[serde(default)]
+    pub options: FrequencyOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrequencyEntry {
+    pub value: String,
+    pub count: u64,
+    /// Occurrences per million tokens of the subcorpus
+    pub per_million: f64,
+    /// Number of books it occurs in
+    pub document_frequency: u64,
+    /// Occurs exactly once in the subcorpus
+    pub hapax: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrequencyList {
+    pub layer: TokenField,
+    pub books: usize,
+    pub total_tokens: u64,
+    /// Distinct values in the subcorpus
+    pub types: usize,
+    pub hapax_count: usize,
+    /// Entries ranked by count, after min_count and offset/limit
+    pub entries: Vec<FrequencyEntry>,
+    pub elapsed_ms: u64,
+}
+
+/// Count and document frequency of one value
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValueCount {
+    pub count: u64,
+    pub books: u64,
+}
+
+/// Layer counts of a subcorpus
+#[derive(Debug, Default)]
+pub struct LayerCounts {
+    pub books: usize,
+    pub total_tokens: u64,
+    pub values: HashMap<String, ValueCount>,
+}
+
+/// A layer and the sorted book IDs of a subcorpus (None = whole corpus)
+type CountsKey = (TokenField, Option<Vec<u64>>);
+
+/// Token ID counts of one book
+struct BookCounts {
+    tokens: u64,
+    counts: HashMap<u32, u64>,
+}
+
+fn layer_name(layer: TokenField) -> &'static str {
+    match layer {
+        TokenField::Surface => "surface",
+        TokenField::Lemma => "lemma",
+        TokenField::Root => "root",
+    }
+}
+
+/// Frequency counts over corpus.db with per-book and whole-corpus caching
+pub struct FrequencyStore {
+    corpus_db_path: PathBuf,
+    cache_db_path: PathBuf,
+    books: Mutex<LruCache<u64, Arc<BookCounts>>>,
+    counts: Mutex<LruCache<CountsKey, Arc<LayerCounts>>>,
+}
+
+impl FrequencyStore {
+    pub fn new(corpus_db_path: PathBuf, cache_db_path: PathBuf) -> Self {
+        Self {
+            corpus_db_path,
+            cache_db_path,
+            books: Mutex::new(LruCache::new(NonZeroUsize::new(BOOK_CACHE_CAPACITY).unwrap())),
+            counts: Mutex::new(LruCache::new(NonZeroUsize::new(COUNTS_CACHE_CAPACITY).unwrap())),
+        }
+    }
+
+    /// Ranked frequency list of a subcorpus (`book_ids` None = whole corpus)
+    pub fn frequency_list(&self, book_ids: Option<&[u64]>, layer: TokenField, options: &FrequencyOptions) -> Result<FrequencyList> {
+        let start = std::time::Instant::now();
+        let counts = self.layer_counts(book_ids, layer)?;
+
+        let mut ranked: Vec<(&String, &ValueCount)> = counts.values.iter().collect();
+        ranked.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+        let hapax_count = ranked.iter().filter(|(_, value)| value.count == 1).count();
+
+        let per_million = 1_000_000.0 / (counts.total_tokens.max(1) as f64);
+        let entries = ranked
+            .iter()
+            .filter(|(_, value)| value.count >= options.min_count)
+            .skip(options.offset)
+            .take(options.limit)
+            .map(|(text, value)| FrequencyEntry {
+                value: (*text).clone(),
+                count: value.count,
+                per_million: value.count as f64 * per_million,
+                document_frequency: value.books,
+                hapax: value.count == 1,
+            })
+            .collect();
+
+        Ok(FrequencyList {
+            layer,
+            books: counts.books,
+            total_tokens: counts.total_tokens,
+            types: ranked.len(),
+            hapax_count,
+            entries,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Counts of every value of a layer in a subcorpus (`book_ids` None = whole corpus)
+    pub fn layer_counts(&self, book_ids: Option<&[u64]>, layer: TokenField) -> Result<Arc<LayerCounts>> {
+        let mut sorted_ids = book_ids.map(|ids| ids.to_vec());
+        if let Some(ids) = sorted_ids.as_mut() {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+        let key: CountsKey = (layer, sorted_ids);
+        if let Some(counts) = self.counts.lock().unwrap().get(&key) {
+            return Ok(counts.clone());
+        }
+
+        let counts = match &key.1 {
+            Some(ids) => self.count_books(ids, layer)?,
+            None => self.corpus_counts(layer)?,
+        };
+        let counts = Arc::new(counts);
+        self.counts.lock().unwrap().put(key, counts.clone());
+        Ok(counts)
+    }
+
+    fn open_corpus(&self) -> Result<Connection> {
+        Connection::open(&self.corpus_db_path)
+            .with_context(|| format!("Failed to open corpus.db at {:?}", self.corpus_db_path))
+    }
+
+    /// Open the cache, emptied if it was counted from another corpus
+    fn open_cache(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.cache_db_path)?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS cache_meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS corpus_totals (
+                layer TEXT PRIMARY KEY,
+                books INTEGER NOT NULL,
+                tokens INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS corpus_frequencies (
+                layer TEXT NOT NULL,
+                value TEXT NOT NULL,
+                count INTEGER NOT NULL,
+                books INTEGER NOT NULL,
+                PRIMARY KEY (layer, value)
+            ) WITHOUT ROWID;
+            "#,
+        )?;
+
+        let counted_from = corpus_fingerprint(&self.corpus_db_path)?;
+        let stored: Option<String> = conn
+            .query_row("SELECT value FROM cache_meta WHERE key = 'counted_from'", [], |row| row.get(0))
+            .optional()?;
+        if stored.as_deref() != Some(counted_from.as_str()) {
+            conn.execute_batch("BEGIN; DELETE FROM corpus_frequencies; DELETE FROM corpus_totals;")?;
+            conn.execute("INSERT OR REPLACE INTO cache_meta (key, value) VALUES ('counted_from', ?1)", [&counted_from])?;
+            conn.execute_batch("COMMIT;")?;
+        }
+        Ok(conn)
+    }
+
+    /// Whole-corpus counts, from frequencies.db if they were computed before
+    fn corpus_counts(&self, layer: TokenField) -> Result<LayerCounts> {
+        let mut cache = self.open_cache()?;
+        let name = layer_name(layer);
+
+        let totals: Option<(i64, i64)> = cache
+            .query_row("SELECT books, tokens FROM corpus_totals WHERE layer = ?1", [name], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+        if let Some((books, tokens)) = totals {
+            let mut stmt = cache.prepare("SELECT value, count, books FROM corpus_frequencies WHERE layer = ?1")?;
+            let values = stmt
+                .query_map([name], |row| {
+                    let count: i64 = row.get(1)?;
+                    let books: i64 = row.get(2)?;
+                    Ok((row.get::<_, String>(0)?, ValueCount { count: count as u64, books: books as u64 }))
+                })?
+                .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+            return Ok(LayerCounts { books: books as usize, total_tokens: tokens as u64, values });
+        }
+
+        let book_ids: Vec<u64> = self
+            .open_corpus()?
+            .prepare("SELECT id FROM books ORDER BY id")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id as u64))
+            .collect::<rusqlite::Result<_>>()?;
+        let counts = self.count_books(&book_ids, layer)?;
+
+        let tx = cache.transaction()?;
+        {
+            let mut insert = tx.prepare("INSERT OR REPLACE INTO corpus_frequencies (layer, value, count, books) VALUES (?1, ?2, ?3, ?4)")?;
+            for (value, count) in &counts.values {
+                insert.execute(rusqlite::params![name, value, count.count as i64, count.books as i64])?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO corpus_totals (layer, books, tokens) VALUES (?1, ?2, ?3)",
+            rusqlite::params![name, counts.books as i64, counts.total_tokens as i64],
+        )?;
+        tx.commit()?;
+
+        Ok(counts)
+    }
+
+    /// Count a layer over the given books
+    fn count_books(&self, book_ids: &[u64], layer: TokenField) -> Result<LayerCounts> {
+        let corpus = self.open_corpus()?;
+        let mut id_values: HashMap<u32, Option<String>> = HashMap::new();
+        let mut counts = LayerCounts { books: book_ids.len(), ..Default::default() };
+
+        for &book_id in book_ids {
+            let book = self.book_counts(&corpus, book_id)?;
+            counts.total_tokens += book.tokens;
+
+            let unknown: Vec<u32> = book.counts.keys().filter(|id| !id_values.contains_key(id)).copied().collect();
+            load_values(&corpus, layer, &unknown, &mut id_values)?;
+
+            let mut book_values: HashMap<&str, u64> = HashMap::new();
+            for (token_id, count) in &book.counts {
+                if let Some(Some(value)) = id_values.get(token_id) {
+                    *book_values.entry(value.as_str()).or_default() += count;
+                }
+            }
+            for (value, count) in book_values {
+                let entry = counts.values.entry(value.to_string()).or_default();
+                entry.count += count;
+                entry.books += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Token ID counts of one book, from memory or page_tokens
+    fn book_counts(&self, corpus: &Connection, book_id: u64) -> Result<Arc<BookCounts>> {
+        if let Some(book) = self.books.lock().unwrap().get(&book_id) {
+            return Ok(book.clone());
+        }
+
+        let mut book = BookCounts { tokens: 0, counts: HashMap::new() };
+        let mut stmt = corpus.prepare("SELECT token_ids FROM page_tokens WHERE book_id = ?1")?;
+        let mut rows = stmt.query([book_id as i64])?;
+        while let Some(row) = rows.next()? {
+            let blob: Vec<u8> = row.get(0)?;
+            for chunk in blob.chunks_exact(4) {
+                let token_id = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                *book.counts.entry(token_id).or_default() += 1;
+                book.tokens += 1;
+            }
+        }
+
+        let book = Arc::new(book);
+        self.books.lock().unwrap().put(book_id, book.clone());
+        Ok(book)
+    }
+}
+
+/// Look up the layer value of each token definition (None if it has none)
+pub(crate) fn load_values(corpus: &Connection, layer: TokenField, token_ids: &[u32], id_values: &mut HashMap<u32, Option<String>>) -> Result<()> {
+    let column = match layer {
+        TokenField::Surface => "td.surface",
+        TokenField::Lemma => "l.lemma",
+        TokenField::Root => "r.root",
+    };
+
+    for chunk in token_ids.chunks(DEFINITION_BATCH) {
+        let placeholders: String = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        let sql = format!(
+            "SELECT td.id, {}
+             FROM token_definitions td
+             LEFT JOIN lemmas l ON l.id = td.lemma_id
+             LEFT JOIN roots r ON r.id = td.root_id
+             WHERE td.id IN ({})",
+            column, placeholders
+        );
+        let mut stmt = corpus.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(chunk.iter().map(|id| *id as i64)), |row| {
+            Ok((row.get::<_, i64>(0)? as u32, row.get::<_, Option<String>>(1)?))
+        })?;
+        for row in rows {
+            let (id, value) = row?;
+            id_values.insert(id, value);
+        }
+        // Definitions missing from the table count towards the total only
+        for id in chunk {
+            id_values.entry(*id).or_insert(None);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_corpus::TestCorpus;
+
+    fn write_corpus() -> TestCorpus {
+        let corpus = TestCorpus::new("frequency");
+        corpus
+            .conn
+            .execute_batch(
+                r#"
+                INSERT INTO books VALUES (1, 10, 1, 300, 4), (2, 20, 1, 500, 6);
+                INSERT INTO lemmas VALUES (1, 'كتاب'), (2, 'قال');
+                INSERT INTO roots VALUES (1, 'كتب'), (2, 'قول');
+                INSERT INTO token_definitions VALUES (1, 'كتاب', 1, 1), (2, 'الكتاب', 1, 1), (3, 'قال', 2, 2);
+                "#,
+            )
+            .unwrap();
+        corpus.add_page(1, 1, &[1, 3, 2]);
+        corpus.add_page(1, 2, &[3, 3]);
+        corpus.add_page(2, 1, &[2, 9]);
+        corpus
+    }
+
+    #[test]
+    fn test_frequency_lists() {
+        let corpus = write_corpus();
+        let dir = &corpus.dir;
+        let store = FrequencyStore::new(corpus.path(), dir.join("frequencies.db"));
+
+        let list = store.frequency_list(None, TokenField::Lemma, &FrequencyOptions::default()).unwrap();
+        assert_eq!((list.books, list.total_tokens, list.types), (2, 7, 2));
+        let qala = &list.entries[0];
+        assert_eq!((qala.value.as_str(), qala.count, qala.document_frequency, qala.hapax), ("قال", 3, 1, false));
+        assert_eq!((list.entries[1].count, list.entries[1].document_frequency), (3, 2));
+
+        let surface = store.frequency_list(Some(&[2]), TokenField::Surface, &FrequencyOptions::default()).unwrap();
+        assert_eq!((surface.total_tokens, surface.hapax_count), (2, 1));
+        assert_eq!(surface.entries[0].value, "الكتاب");
+
+        // A second store reads the whole-corpus list back from frequencies.db
+        store.open_cache().unwrap().execute("UPDATE corpus_totals SET tokens = 70", []).unwrap();
+        let reopened = FrequencyStore::new(corpus.path(), dir.join("frequencies.db"));
+        let cached = reopened.frequency_list(None, TokenField::Lemma, &FrequencyOptions::default()).unwrap();
+        assert_eq!((cached.types, cached.total_tokens), (2, 70));
+
+        // ...until the corpus changes
+        corpus.add_page(2, 2, &[3, 3, 3]);
+        let updated = FrequencyStore::new(corpus.path(), dir.join("frequencies.db"));
+        let list = updated.frequency_list(None, TokenField::Lemma, &FrequencyOptions::default()).unwrap();
+        assert_eq!((list.total_tokens, list.entries[0].count), (10, 6));
+    }
+}
@@ -0,0 +1,349 @@
+//! Isnad (chain of transmission) extraction
+//!
+//! A chain opens with a transmission verb (حدثنا، أخبرنا، سمعت ...) and goes on
+//! through further verbs or عن, each followed by a narrator's name. A name is a
+//! run of proper nouns, kinship/kunya particles (ابن، بن، أبو، عبد ...) and
+//! nisbas; honorifics (رضي الله عنه) and a linking قال are skipped, and a chain
+//! ends at the Prophet or at the first word that is neither. What follows the
+//! chain, up to the next one, is its matn.
+
+use crate::cache::TokenCache;
+use crate::cursor::SortKey;
+use crate::names::{fold_kunya_case, kunya_case_variants, with_proclitics};
+use crate::search::{normalize_arabic, SearchEngine, SearchFilters};
+use crate::tokens::{PageKey, Token};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Verbs that open a chain (normalized, without clitics)
+const OPENING_VERBS: &[&str] = &[
+    "حدثنا", "حدثني", "حدثناه", "حدثه", "حدثهم", "ثنا", "اخبرنا", "اخبرني", "اخبرناه", "انبانا", "انباني", "نبانا",
+    "سمعت", "سمعنا",
+];
+
+/// Links that only continue a chain
+const LINKING_WORDS: &[&str] = &["عن", "سمع"];
+
+/// Words between a narrator and the next link ("حدثنا مالك قال حدثنا")
+const CONNECTORS: &[&str] = &["قال", "قالا", "قالوا", "قالت", "يقول", "تقول", "انه", "انها", "ان"];
+
+/// Name particles: kinship, kunya and theophoric elements
+const NAME_PARTICLES: &[&str] = &["ابن", "بن", "بنت", "ابو", "ابي", "ابا", "ام", "عبد", "مولي"];
+
+/// Honorifics after a narrator (length in words)
+const HONORIFICS: &[(&str, usize)] = &[("رضي", 3), ("رحمه", 2), ("صلي", 4), ("عليه", 2)];
+
+/// Longest narrator name
+const MAX_NAME_TOKENS: usize = 10;
+
+/// Longest matn returned
+const MAX_MATN_TOKENS: usize = 300;
+
+/// POS tag of proper nouns
+const PROPER_NOUN_POS: &str = "noun_prop";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Narrator {
+    /// Name as written
+    pub name: String,
+    /// Token range [start, end) of the name on the page
+    pub start: usize,
+    pub end: usize,
+    /// Transmission term linking this narrator to the previous one (as written)
+    pub transmission: String,
+    /// The Prophet, ending the chain
+    pub is_prophet: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Isnad {
+    pub book_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+    /// Token range [start, end) of the chain
+    pub start: usize,
+    pub end: usize,
+    pub narrators: Vec<Narrator>,
+    /// Token range [matn_start, matn_end) of the text transmitted
+    pub matn_start: usize,
+    pub matn_end: usize,
+    pub matn: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageIsnadsRequest {
+    pub book_id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsnadSearchRequest {
+    /// Narrator names that must appear in this order (not necessarily adjacent);
+    /// a kunya matches in any case (ابو/ابا/ابي)
+    pub narrators: Vec<String>,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: usize,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_max_pages() -> usize {
+    2000
+}
+
+fn default_limit() -> usize {
+    200
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsnadSearchResults {
+    /// Pages containing every narrator name
+    pub total_pages: usize,
+    pub pages_scanned: usize,
+    pub isnads: Vec<Isnad>,
+    pub elapsed_ms: u64,
+}
+
+/// A token with the normalized forms the extractor compares
+struct Word<'a> {
+    token: &'a Token,
+    /// Without clitics ("وحدثنا" -> "حدثنا")
+    bare: String,
+    /// Whole surface
+    full: String,
+}
+
+impl Word<'_> {
+    fn is(&self, words: &[&str]) -> bool {
+        words.contains(&self.bare.as_str()) || words.contains(&self.full.as_str())
+    }
+
+    fn is_name_part(&self) -> bool {
+        self.token.pos == PROPER_NOUN_POS
+            || self.is(NAME_PARTICLES)
+            // Nisba: الزهري، المدني
+            || (self.full.starts_with("ال") && self.full.ends_with('ي') && self.full.chars().count() > 3)
+    }
+}
+
+fn words(tokens: &[Token]) -> Vec<Word<'_>> {
+    tokens
+        .iter()
+        .map(|token| Word {
+            token,
+            bare: normalize_arabic(token.noclitic_surface.as_deref().unwrap_or(&token.surface)),
+            full: normalize_arabic(&token.surface),
+        })
+        .collect()
+}
+
+fn is_link(word: &Word) -> bool {
+    word.is(OPENING_VERBS) || word.is(LINKING_WORDS)
+}
+
+fn joined(words: &[Word]) -> String {
+    words.iter().map(|word| word.token.surface.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+/// Chains on a page's tokens, without their location
+fn extract(tokens: &[Token]) -> Vec<Isnad> {
+    let words = words(tokens);
+    let mut chains: Vec<Isnad> = Vec::new();
+    let mut i = 0;
+
+    while i < words.len() {
+        if !words[i].is(OPENING_VERBS) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut narrators: Vec<Narrator> = Vec::new();
+        let mut pos = i;
+        let mut end = i;
+        while pos < words.len() && is_link(&words[pos]) {
+            let name_start = pos + 1;
+            let mut name_end = name_start;
+            let is_prophet = words.get(name_start).is_some_and(|word| word.bare == "النبي" || word.bare == "رسول");
+            if is_prophet {
+                name_end += if words.get(name_start).is_some_and(|word| word.bare == "رسول") { 2 } else { 1 };
+                name_end = name_end.min(words.len());
+            } else {
+                while name_end < words.len() && name_end - name_start < MAX_NAME_TOKENS && words[name_end].is_name_part() {
+                    name_end += 1;
+                }
+            }
+            if name_end == name_start {
+                break;
+            }
+
+            narrators.push(Narrator {
+                name: joined(&words[name_start..name_end]),
+                start: name_start,
+                end: name_end,
+                transmission: words[pos].token.surface.clone(),
+                is_prophet,
+            });
+            end = name_end;
+
+            let mut next = name_end;
+            if let Some(&(_, len)) = words.get(next).and_then(|word| HONORIFICS.iter().find(|(first, _)| word.bare == *first)) {
+                next = (next + len).min(words.len());
+                end = next;
+            }
+            if is_prophet {
+                break;
+            }
+            if words.get(next).is_some_and(|word| word.is(CONNECTORS)) && words.get(next + 1).is_some_and(is_link) {
+                next += 1;
+            }
+            pos = next;
+        }
+
+        // A single narrator is more likely a report ("حدثنا فلان بكذا") than a chain
+        if narrators.len() < 2 && !narrators.iter().any(|narrator| narrator.is_prophet) {
+            i = start + 1;
+            continue;
+        }
+
+        let matn_start = end;
+        let matn_end = (matn_start..words.len())
+            .find(|&k| words[k].is(OPENING_VERBS))
+            .unwrap_or(words.len())
+            .min(matn_start + MAX_MATN_TOKENS);
+        chains.push(Isnad {
+            book_id: 0,
+            part_index: 0,
+            page_id: 0,
+            start,
+            end,
+            narrators,
+            matn_start,
+            matn_end,
+            matn: joined(&words[matn_start..matn_end]),
+        });
+        i = end.max(start + 1);
+    }
+
+    chains
+}
+
+/// Whether every query name occurs, in order, among a chain's narrators
+fn chain_contains(isnad: &Isnad, names: &[Vec<String>]) -> bool {
+    let mut remaining = names.iter().peekable();
+    for narrator in &isnad.narrators {
+        let Some(name) = remaining.peek() else {
+            break;
+        };
+        let narrator_words = name_words(&narrator.name);
+        if contains_in_order(&narrator_words, name) {
+            remaining.next();
+        }
+    }
+    remaining.peek().is_none()
+}
+
+/// Normalized words of a name, kunya particles in one case
+fn name_words(name: &str) -> Vec<String> {
+    name.split_whitespace().map(|word| fold_kunya_case(&normalize_arabic(word)).to_string()).collect()
+}
+
+fn contains_in_order(haystack: &[String], needle: &[String]) -> bool {
+    let mut rest = haystack.iter();
+    needle.iter().all(|word| rest.any(|candidate| candidate == word))
+}
+
+fn locate(mut isnads: Vec<Isnad>, key: &SortKey) -> Vec<Isnad> {
+    for isnad in &mut isnads {
+        isnad.book_id = key.text_id;
+        isnad.part_index = key.part_index;
+        isnad.page_id = key.page_id;
+    }
+    isnads
+}
+
+/// Chains of transmission on one page
+pub fn page_isnads(token_cache: &TokenCache, request: &PageIsnadsRequest) -> Result<Vec<Isnad>> {
+    let tokens = token_cache.get(&PageKey::new(request.book_id, request.page_id))?;
+    let key = SortKey { death_ah: 0, text_id: request.book_id, part_index: request.part_index, page_id: request.page_id };
+    Ok(locate(extract(&tokens), &key))
+}
+
+/// Chains containing the given narrators in order
+pub fn search_isnads(engine: &SearchEngine, token_cache: &TokenCache, request: &IsnadSearchRequest) -> Result<IsnadSearchResults> {
+    let start = std::time::Instant::now();
+    let narrators: Vec<&String> = request.narrators.iter().filter(|name| !name.trim().is_empty()).collect();
+    if narrators.is_empty() {
+        return Err(anyhow!("At least one narrator name is required"));
+    }
+    let names: Vec<Vec<String>> = narrators.iter().map(|name| name_words(name)).collect();
+
+    // Pages with every narrator in some case of its kunya and with any proclitic, as in name search
+    let patterns_by_form: Vec<Vec<String>> = narrators
+        .iter()
+        .map(|name| kunya_case_variants(name).iter().flat_map(|variant| with_proclitics(variant)).collect())
+        .collect();
+    let (total_pages, pages) = engine.pattern_pages(&patterns_by_form, &request.filters, request.max_pages)?;
+
+    let mut isnads = Vec::new();
+    for key in &pages {
+        let tokens = token_cache.get(&PageKey::new(key.text_id, key.page_id))?;
+        isnads.extend(locate(extract(&tokens), key).into_iter().filter(|isnad| chain_contains(isnad, &names)));
+        if isnads.len() >= request.limit {
+            isnads.truncate(request.limit);
+            break;
+        }
+    }
+
+    Ok(IsnadSearchResults { total_pages, pages_scanned: pages.len(), isnads, elapsed_ms: start.elapsed().as_millis() as u64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str, proper_nouns: &[&str]) -> Vec<Token> {
+        text.split_whitespace()
+            .enumerate()
+            .map(|(idx, surface)| Token {
+                idx,
+                surface: surface.to_string(),
+                noclitic_surface: surface.strip_prefix('و').filter(|rest| rest.starts_with("حدث")).map(str::to_string),
+                lemma: surface.to_string(),
+                root: None,
+                pos: if proper_nouns.contains(&surface) { PROPER_NOUN_POS.to_string() } else { "noun".to_string() },
+                features: Vec::new(),
+                clitics: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_chain_and_matn() {
+        let page = tokens(
+            "وحدثنا قتيبة بن سعيد قال حدثنا مالك عن نافع عن ابن عمر رضي الله عنهما أن رسول الله صلى الله عليه وسلم نهى عن بيع الغرر",
+            &["قتيبة", "سعيد", "مالك", "نافع", "عمر"],
+        );
+        let chains = extract(&page);
+        assert_eq!(chains.len(), 1);
+
+        let chain = &chains[0];
+        let names: Vec<&str> = chain.narrators.iter().map(|narrator| narrator.name.as_str()).collect();
+        assert_eq!(names, ["قتيبة بن سعيد", "مالك", "نافع", "ابن عمر"]);
+        assert_eq!(chain.narrators[1].transmission, "حدثنا");
+        assert!(chain.matn.starts_with("أن رسول الله"));
+
+        let query = |names: &[&str]| names.iter().map(|name| name_words(name)).collect::<Vec<_>>();
+        assert!(chain_contains(chain, &query(&["مالك", "ابن عمر"])));
+        assert!(!chain_contains(chain, &query(&["نافع", "مالك"])));
+
+        // A kunya is found in whichever case the chain puts it
+        let page = tokens("حدثنا مالك عن سعيد عن ابي هريرة قال", &["مالك", "سعيد", "هريرة"]);
+        let chain = &extract(&page)[0];
+        assert!(chain_contains(chain, &query(&["مالك", "أبو هريرة"])));
+        assert_eq!(kunya_case_variants("أبو هريرة"), ["ابو هريرة", "ابا هريرة", "ابي هريرة"]);
+    }
+}
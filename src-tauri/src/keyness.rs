@@ -0,0 +1,293 @@
+//! Keyness comparison between two subcorpora
+//!
+//! Compares the layer counts of a target subcorpus with a reference one, both
+//! taken from FrequencyStore. Each value is scored with Rayson's log-likelihood,
+//! Hardie's log-ratio and Gabrielatos & Marchi's %DIFF. Without a reference
+//! subcorpus the target is compared with the rest of the corpus, so that its own
+//! counts do not pull the reference towards it.
+
+use crate::collocations::ll_term;
+use crate::frequency::{FrequencyStore, LayerCounts, Subcorpus, ValueCount};
+use crate::tokens::TokenField;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Added to zero counts when taking the log-ratio
+const LOG_RATIO_ZERO_CORRECTION: f64 = 0.5;
+
+/// Stands in for a zero reference rate in %DIFF
+const PERCENT_DIFF_ZERO_RATE: f64 = 1e-18;
+
+/// Log-likelihood critical values (1 d.f.), strictest last
+const CRITICAL_VALUES: [(Significance, f64); 4] = [
+    (Significance::P05, 3.84),
+    (Significance::P01, 6.63),
+    (Significance::P001, 10.83),
+    (Significance::P0001, 15.13),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Significance {
+    /// p < 0.05
+    P05,
+    /// p < 0.01
+    #[default]
+    P01,
+    /// p < 0.001
+    P001,
+    /// p < 0.0001
+    P0001,
+}
+
+impl Significance {
+    pub fn critical_value(self) -> f64 {
+        CRITICAL_VALUES.iter().find(|(level, _)| *level == self).map(|(_, value)| *value).unwrap_or(0.0)
+    }
+
+    /// Strictest level a log-likelihood score reaches
+    fn of(log_likelihood: f64) -> Option<Self> {
+        CRITICAL_VALUES.iter().rev().find(|(_, value)| log_likelihood >= *value).map(|(level, _)| *level)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeynessSort {
+    #[default]
+    LogLikelihood,
+    LogRatio,
+    PercentDiff,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeynessOptions {
+    pub layer: TokenField,
+    /// Minimum count in the target (positive keywords) or reference (negative ones)
+    pub min_frequency: u64,
+    /// Keywords must reach this level
+    pub significance: Significance,
+    /// Also return values distinctively rare in the target
+    pub include_negative: bool,
+    pub sort: KeynessSort,
+    /// Maximum keywords returned per direction
+    pub limit: usize,
+}
+
+impl Default for KeynessOptions {
+    fn default() -> Self {
+        Self {
+            layer: TokenField::Lemma,
+            min_frequency: 5,
+            significance: Significance::default(),
+            include_negative: true,
+            sort: KeynessSort::default(),
+            limit: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeynessRequest {
+    pub target: Subcorpus,
+    /// Defaults to the rest of the corpus: every book not in the target
+    #[serde(default)]
+    pub reference: Subcorpus,
+    #[serde(default)]
+    pub options: KeynessOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyword {
+    pub value: String,
+    pub target_count: u64,
+    pub target_per_million: f64,
+    pub reference_count: u64,
+    pub reference_per_million: f64,
+    pub log_likelihood: f64,
+    /// log2 of the ratio of relative frequencies, target over reference
+    pub log_ratio: f64,
+    /// Difference of relative frequencies as a percentage of the reference one
+    pub percent_diff: f64,
+    /// Strictest significance level reached
+    pub significance: Option<Significance>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeynessResults {
+    pub layer: TokenField,
+    pub target_books: usize,
+    pub target_tokens: u64,
+    pub reference_books: usize,
+    pub reference_tokens: u64,
+    pub critical_value: f64,
+    /// More frequent in the target than in the reference
+    pub positive: Vec<Keyword>,
+    /// Less frequent in the target than in the reference
+    pub negative: Vec<Keyword>,
+    pub elapsed_ms: u64,
+}
+
+/// Score a value seen `target` times in `target_tokens` and `reference` times in `reference_tokens`
+fn score(value: String, target: u64, reference: u64, target_tokens: u64, reference_tokens: u64) -> Keyword {
+    let (a, b) = (target as f64, reference as f64);
+    let (c, d) = ((target_tokens as f64).max(1.0), (reference_tokens as f64).max(1.0));
+
+    let expected_target = c * (a + b) / (c + d);
+    let expected_reference = d * (a + b) / (c + d);
+    let log_likelihood = 2.0 * (ll_term(a, expected_target) + ll_term(b, expected_reference));
+
+    let log_ratio = ((a.max(LOG_RATIO_ZERO_CORRECTION) / c) / (b.max(LOG_RATIO_ZERO_CORRECTION) / d)).log2();
+
+    let target_rate = a * 1_000_000.0 / c;
+    let reference_rate = b * 1_000_000.0 / d;
+    let percent_diff = (target_rate - reference_rate) * 100.0 / reference_rate.max(PERCENT_DIFF_ZERO_RATE);
+
+    Keyword {
+        value,
+        target_count: target,
+        target_per_million: target_rate,
+        reference_count: reference,
+        reference_per_million: reference_rate,
+        log_likelihood,
+        log_ratio,
+        percent_diff,
+        significance: Significance::of(log_likelihood),
+    }
+}
+
+fn count_of(counts: &LayerCounts, value: &str) -> u64 {
+    counts.values.get(value).map(|count| count.count).unwrap_or(0)
+}
+
+/// Counts of the corpus less a subcorpus of it
+fn rest_of_corpus(corpus: &LayerCounts, part: &LayerCounts) -> LayerCounts {
+    let values = corpus
+        .values
+        .iter()
+        .filter_map(|(value, count)| {
+            let in_part = part.values.get(value);
+            let rest = ValueCount {
+                count: count.count.saturating_sub(in_part.map_or(0, |c| c.count)),
+                books: count.books.saturating_sub(in_part.map_or(0, |c| c.books)),
+            };
+            (rest.count > 0).then(|| (value.clone(), rest))
+        })
+        .collect();
+    LayerCounts {
+        books: corpus.books.saturating_sub(part.books),
+        total_tokens: corpus.total_tokens.saturating_sub(part.total_tokens),
+        values,
+    }
+}
+
+/// Keywords of a target subcorpus (`None` = whole corpus) against a reference
+/// one, by default (`None`) the rest of the corpus
+pub fn compare_keyness(
+    store: &FrequencyStore,
+    target_ids: Option<&[u64]>,
+    reference_ids: Option<&[u64]>,
+    options: &KeynessOptions,
+) -> Result<KeynessResults> {
+    let start = std::time::Instant::now();
+    let target = store.layer_counts(target_ids, options.layer)?;
+    let reference = match (reference_ids, target_ids) {
+        (Some(_), _) => store.layer_counts(reference_ids, options.layer)?,
+        (None, Some(_)) => Arc::new(rest_of_corpus(&*store.layer_counts(None, options.layer)?, &target)),
+        (None, None) => return Err(anyhow!("Choose a target or a reference subcorpus: the whole corpus has no rest to compare with")),
+    };
+    let critical_value = options.significance.critical_value();
+    let min_frequency = options.min_frequency.max(1);
+
+    let mut positive = Vec::new();
+    let mut negative = Vec::new();
+    let target_values = target.values.iter().map(|(value, count)| (value, count.count, count_of(&reference, value)));
+    let reference_only = reference
+        .values
+        .iter()
+        .filter(|(value, _)| !target.values.contains_key(*value))
+        .map(|(value, count)| (value, 0, count.count));
+
+    for (value, in_target, in_reference) in target_values.chain(reference_only) {
+        let target_rate = in_target as f64 / (target.total_tokens.max(1) as f64);
+        let reference_rate = in_reference as f64 / (reference.total_tokens.max(1) as f64);
+        let is_positive = target_rate > reference_rate;
+        if is_positive && in_target < min_frequency {
+            continue;
+        }
+        if !is_positive && (!options.include_negative || in_reference < min_frequency) {
+            continue;
+        }
+
+        let keyword = score(value.clone(), in_target, in_reference, target.total_tokens, reference.total_tokens);
+        if keyword.log_likelihood < critical_value {
+            continue;
+        }
+        if is_positive {
+            positive.push(keyword);
+        } else {
+            negative.push(keyword);
+        }
+    }
+
+    // Negative keywords rank by how far below the reference they fall
+    let sort_value = |keyword: &Keyword| match options.sort {
+        KeynessSort::LogLikelihood => keyword.log_likelihood,
+        KeynessSort::LogRatio => keyword.log_ratio.abs(),
+        KeynessSort::PercentDiff => keyword.percent_diff.abs(),
+    };
+    for keywords in [&mut positive, &mut negative] {
+        keywords.sort_by(|a, b| sort_value(b).total_cmp(&sort_value(a)).then_with(|| a.value.cmp(&b.value)));
+        keywords.truncate(options.limit);
+    }
+
+    Ok(KeynessResults {
+        layer: options.layer,
+        target_books: target.books,
+        target_tokens: target.total_tokens,
+        reference_books: reference.books,
+        reference_tokens: reference.total_tokens,
+        critical_value,
+        positive,
+        negative,
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyness_scores() {
+        // Same rate in both subcorpora
+        let neutral = score("x".to_string(), 10, 100, 10_000, 100_000);
+        assert!(neutral.log_likelihood.abs() < 1e-9);
+        assert!(neutral.log_ratio.abs() < 1e-9);
+        assert_eq!(neutral.significance, None);
+
+        // Four times as frequent in the target
+        let key = score("y".to_string(), 40, 100, 10_000, 100_000);
+        assert!((key.log_ratio - 2.0).abs() < 1e-9);
+        assert!((key.percent_diff - 300.0).abs() < 1e-9);
+        assert_eq!(key.significance, Some(Significance::P0001));
+
+        // Absent from the reference
+        let unique = score("z".to_string(), 8, 0, 10_000, 100_000);
+        assert!(unique.log_ratio.is_finite() && unique.percent_diff.is_finite());
+
+        // The default reference leaves the target out
+        let counts = |books: usize, values: &[(&str, u64, u64)]| LayerCounts {
+            books,
+            total_tokens: values.iter().map(|(_, count, _)| count).sum(),
+            values: values.iter().map(|&(value, count, books)| (value.to_string(), ValueCount { count, books })).collect(),
+        };
+        let corpus = counts(3, &[("قال", 30, 3), ("كتاب", 4, 1)]);
+        let rest = rest_of_corpus(&corpus, &counts(1, &[("قال", 10, 1), ("كتاب", 4, 1)]));
+        assert_eq!((rest.books, rest.total_tokens), (2, 20));
+        assert_eq!(count_of(&rest, "قال"), 20);
+        assert!(!rest.values.contains_key("كتاب"));
+    }
+}
@@ -4,20 +4,64 @@
 
 // Token types must be defined first as they're used by search
 pub mod tokens;
+pub mod cursor;
+pub mod budget;
+pub mod control;
+pub mod result_cache;
 pub mod search;
+pub mod citations;
+pub mod snippets;
+pub mod similar;
+pub mod collocations;
+pub mod collation;
+pub mod frequency;
+pub mod isnad;
+pub mod dates;
+pub mod quran;
+pub mod names;
+pub mod persons;
+pub mod network;
+pub mod toponyms;
+pub mod keyness;
+pub mod ngrams;
+pub mod reuse;
+pub mod xlsx;
+pub mod export;
 pub mod cache;
 pub mod error;
 pub mod state;
 pub mod downloader;
+#[cfg(test)]
+mod test_corpus;
 
 pub use error::KashshafError;
 pub use state::AppState;
-pub use search::{SearchEngine, SearchMode, SearchFilters, SearchResult, SearchResults, PageWithMatches, SearchTerm, parse_wildcard_query, WildcardQueryInfo};
+pub use search::{BookPart, PageDirection, SearchEngine, SearchMode, SearchFilters, SearchResult, SearchResults, PatternHits, PatternMatch, PageWithMatches, SearchTerm, MatchQuery, MatchPositions, SearchOptions, ExplainRequest, QueryExplanation, parse_wildcard_query, WildcardQueryInfo};
+pub use budget::{SearchBudget, TruncationReason};
+pub use control::{SearchCancelled, SearchControl, SearchProgress};
+pub use result_cache::ResultCacheStats;
+pub use citations::{CitationMatch, CitationRequest, CitationResolution, CitedBook, ResolvedCitation, parse_citation_link, resolve_citation};
+pub use snippets::{Snippet, SnippetOptions, HighlightRange};
+pub use similar::{SimilarPassage, SimilarPassages, SimilarPassagesOptions, SimilarPassagesRequest};
+pub use collation::{CollatedPage, CollationEdit, CollationRequest, CollationResults, CollationSide, EditKind, PageRange, TokenLocation, collate};
+pub use collocations::{Collocate, CollocationOptions, CollocationRequest, CollocationResults, CollocationSort, find_collocations};
+pub use isnad::{Isnad, IsnadSearchRequest, IsnadSearchResults, Narrator, PageIsnadsRequest, page_isnads, search_isnads};
+pub use dates::{DateMention, DateSearchRequest, DateSearchResults, PageDatesRequest, page_dates, search_dates};
+pub use quran::{PageCitationsRequest, Quran, QuranCitation, QuranStore, VerseSearchRequest, VerseSearchResults, page_quran_citations, search_verse_citations};
+pub use names::{NameForm, NamePatterns, NameSearchForm, generate_display_patterns, generate_patterns, generate_search_patterns, is_form_valid};
+pub use network::{CoMentionNetwork, NetworkEdge, NetworkFormat, NetworkGrouping, NetworkNode, NetworkOptions, NetworkPerson, NetworkRequest, co_mention_network, write_network};
+pub use persons::{NameMentions, Person, PersonInput, PersonMentionCounts, PersonMentions, PersonMentionsRequest};
+pub use toponyms::{Gazetteer, GazetteerStore, Place, PlaceMentionCounts, PlaceMentions, PlaceMentionsRequest, derive_nisba};
+pub use frequency::{FrequencyEntry, FrequencyList, FrequencyOptions, FrequencyRequest, FrequencyStore, Subcorpus, resolve_subcorpus};
+pub use keyness::{Keyword, KeynessOptions, KeynessRequest, KeynessResults, KeynessSort, Significance, compare_keyness};
+pub use ngrams::{Ngram, NgramOptions, NgramRequest, NgramResults, extract_ngrams};
+pub use reuse::{BookReuseRequest, PageReuseRequest, ReuseBuildProgress, ReuseIndex, ReuseIndexStatus, ReuseMatch, ReuseOptions, ReusePassage, ReuseResults};
+pub use export::{ExportFormat, ExportRequest, ExportProgress, ExportSummary, ExportBookInfo, export_search_results, load_export_book_info};
 pub use cache::TokenCache;
 pub use tokens::{Token, TokenField, PageKey};
 pub use downloader::{
     CorpusStatus, DownloadProgress, DownloadState, LocalManifest, RemoteManifest,
     check_corpus_status, download_corpus, fetch_remote_manifest, load_local_manifest,
     get_data_dir, get_app_data_directory, get_corpus_data_directory, get_settings_db_path,
-    archive_old_corpus, verify_file_hash,
+    archive_old_corpus, verify_file_hash, set_resource_dir, bundled_resource, corpus_fingerprint,
 };
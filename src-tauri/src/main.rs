@@ -5,9 +5,10 @@
 
 mod commands;
 
-use kashshaf_lib::{AppState, get_data_dir};
+use kashshaf_lib::{AppState, citations, get_data_dir};
 use std::sync::{Arc, RwLock};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
 
 /// Wrapper for AppState that allows hot-reloading after corpus download
 pub type ManagedAppState = Arc<RwLock<Option<Arc<AppState>>>>;
@@ -34,15 +35,31 @@ fn main() {
     ));
 
     tauri::Builder::default()
+        // Registered first: on Windows and Linux a citation link opened while the app
+        // runs starts a second instance, which hands the link to this one (emitted
+        // through on_open_url below) and exits
+        .plugin(tauri_plugin_single_instance::init(|app, _argv, _cwd| {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+        }))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_deep_link::init())
         .manage(app_state)
         .invoke_handler(tauri::generate_handler![
             commands::search,
             commands::combined_search,
             commands::name_search,
+            commands::generate_name_patterns,
             commands::get_page,
+            commands::get_book_parts,
+            commands::get_adjacent_page,
+            commands::get_page_by_number,
+            commands::resolve_citation,
+            commands::get_launch_citation,
             commands::get_all_books,
             commands::list_books,
             commands::list_books_filtered,
@@ -56,12 +73,35 @@ fn main() {
             commands::get_page_tokens,
             commands::get_token_at,
             commands::get_cache_stats,
+            commands::get_result_cache_stats,
             commands::clear_token_cache,
             commands::get_match_positions,
             commands::get_match_positions_combined,
             commands::get_page_with_matches,
             commands::get_name_match_positions,
+            commands::get_all_match_positions,
+            commands::explain_search,
+            commands::find_collocations,
+            commands::find_similar_passages,
+            commands::collate_pages,
+            commands::get_page_isnads,
+            commands::search_isnads,
+            commands::get_page_dates,
+            commands::search_dates,
+            commands::get_page_quran_citations,
+            commands::search_verse_citations,
+            commands::get_frequency_list,
+            commands::compare_keyness,
+            commands::extract_ngrams,
+            commands::get_reuse_index_status,
+            commands::build_reuse_index,
+            commands::cancel_reuse_index_build,
+            commands::find_page_reuse,
+            commands::find_book_reuse,
             commands::wildcard_search,
+            commands::export_search_results,
+            commands::cancel_export,
+            commands::cancel_search,
             commands::show_app_menu,
             // Search history commands
             commands::add_to_history,
@@ -99,7 +139,43 @@ fn main() {
             commands::update_collection_description,
             commands::rename_collection,
             commands::delete_collection,
+            commands::create_person,
+            commands::get_persons,
+            commands::update_person,
+            commands::delete_person,
+            commands::count_person_mentions,
+            commands::build_co_mention_network,
+            commands::find_places,
+            commands::count_place_mentions,
         ])
+        .setup(|app| {
+            // The gazetteer falls back to the copy bundled with the app (and the Qur'an
+            // text to src-tauri/resources/quran.txt, which is only there if added before building)
+            kashshaf_lib::set_resource_dir(app.path().resource_dir()?);
+
+            // Linux and Windows dev builds register the kashshaf:// scheme at runtime. On Linux
+            // this runs xdg-mime, which may be missing: links then don't open the app, but it starts.
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                eprintln!("Failed to register citation links: {}", e);
+            }
+
+            // Forward citation links opened while running to the frontend
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                for url in event.urls() {
+                    match citations::parse_citation_link(url.as_str()) {
+                        Ok(request) => {
+                            if let Err(e) = handle.emit("open-citation", request) {
+                                eprintln!("Failed to emit open-citation event: {}", e);
+                            }
+                        }
+                        Err(e) => eprintln!("Ignoring deep link: {}", e),
+                    }
+                }
+            });
+            Ok(())
+        })
         .on_menu_event(|app, event| {
             match event.id().as_ref() {
                 "quit" => {
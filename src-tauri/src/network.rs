@@ -0,0 +1,351 @@
+//! Co-mention network of persons
+//!
+//! Each person is a list of alternative name forms, found with the patterns of
+//! name search. Two persons are linked when they are mentioned on the same
+//! page, or, with a token window, when a mention of one starts within that many
+//! tokens of a mention of the other. Edges can be split per book or century and
+//! written as an edge list (CSV) or a graph (GEXF, GraphML) for Gephi and the like.
+
+use crate::control::{SearchControl, SearchProgress};
+use crate::cursor::SortKey;
+use crate::export::escape_csv;
+use crate::frequency::Subcorpus;
+use crate::names::NameSearchForm;
+use crate::search::{SearchEngine, SearchFilters};
+use crate::xlsx::push_xml_escaped;
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkPerson {
+    /// Node label; the person's first pattern if empty
+    #[serde(default)]
+    pub label: String,
+    /// Alternative forms of the person's name; a mention of any one counts
+    pub forms: Vec<NameSearchForm>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkGrouping {
+    /// One edge per pair over the whole subcorpus
+    #[default]
+    None,
+    Book,
+    Century,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkFormat {
+    #[default]
+    Csv,
+    Gexf,
+    Graphml,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkOptions {
+    /// Link mentions starting at most this many tokens apart; None links any two on a page
+    pub window: Option<usize>,
+    pub group_by: NetworkGrouping,
+    /// Edges with a lower weight are dropped
+    pub min_weight: usize,
+    /// Pages read per person; persons mentioned more often are flagged truncated
+    pub max_pages: usize,
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        Self { window: None, group_by: NetworkGrouping::None, min_weight: 1, max_pages: 20_000 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkRequest {
+    pub persons: Vec<NetworkPerson>,
+    #[serde(default)]
+    pub subcorpus: Subcorpus,
+    #[serde(default)]
+    pub options: NetworkOptions,
+    /// Write the network to this file as well
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub format: NetworkFormat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkNode {
+    /// Index of the person in the request
+    pub id: usize,
+    pub label: String,
+    pub pages: usize,
+    pub mentions: usize,
+    /// More than max_pages pages mention the person; only those were linked
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkEdge {
+    pub source: usize,
+    pub target: usize,
+    /// Book ID or century AH, when grouped
+    pub group: Option<u64>,
+    /// Pages mentioning both, or pairs of mentions within the window
+    pub weight: usize,
+    /// Pages mentioning both (within the window, if any)
+    pub pages: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoMentionNetwork {
+    pub nodes: Vec<NetworkNode>,
+    /// Heaviest first
+    pub edges: Vec<NetworkEdge>,
+    pub group_by: NetworkGrouping,
+    pub elapsed_ms: u64,
+}
+
+/// Pairs of mentions, one from each list, starting at most `window` tokens apart.
+/// Both lists are sorted; a shared start is one name, not two persons.
+fn close_pairs(a: &[u32], b: &[u32], window: usize) -> usize {
+    let window = window as u32;
+    a.iter()
+        .map(|&x| {
+            let lo = b.partition_point(|&y| y < x.saturating_sub(window));
+            let hi = b.partition_point(|&y| y <= x.saturating_add(window));
+            b[lo..hi].iter().filter(|&&y| y != x).count()
+        })
+        .sum()
+}
+
+/// Century of each book from the corpus database
+fn book_centuries(corpus: &Connection) -> Result<HashMap<u64, u64>> {
+    let centuries = corpus
+        .prepare("SELECT id, century_ah FROM books WHERE century_ah IS NOT NULL")?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(centuries)
+}
+
+/// Link persons mentioned together within the given books (`None`: the whole corpus).
+/// Progress is reported per person: checked persons out of all, persons found.
+pub fn co_mention_network(
+    engine: &SearchEngine,
+    corpus: &Connection,
+    persons: &[NetworkPerson],
+    book_ids: Option<&[u64]>,
+    options: &NetworkOptions,
+    control: &SearchControl,
+) -> Result<CoMentionNetwork> {
+    let start = std::time::Instant::now();
+    let filters = SearchFilters { book_ids: book_ids.map(<[u64]>::to_vec), ..Default::default() };
+    // An empty subcorpus would otherwise mean no book filter at all
+    let no_books = book_ids.is_some_and(|ids| ids.is_empty());
+    let centuries = if options.group_by == NetworkGrouping::Century { book_centuries(corpus)? } else { HashMap::new() };
+
+    // Page -> mentions of each person on it, in person order
+    let mut pages: HashMap<SortKey, Vec<(usize, Vec<u32>)>> = HashMap::new();
+    let mut nodes = Vec::with_capacity(persons.len());
+    for (id, person) in persons.iter().enumerate() {
+        control.check()?;
+        let mut patterns: Vec<String> = Vec::new();
+        for pattern in person.forms.iter().map(NameSearchForm::search_patterns).collect::<Result<Vec<_>>>()?.concat() {
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+        let label = if person.label.trim().is_empty() { patterns.first().cloned().unwrap_or_default() } else { person.label.clone() };
+
+        let mut node = NetworkNode { id, label, pages: 0, mentions: 0, truncated: false };
+        if !no_books && !patterns.is_empty() {
+            let occurrences = engine.name_occurrences(&patterns, &filters, options.max_pages, control)?;
+            node.pages = occurrences.total_pages;
+            node.truncated = occurrences.total_pages > occurrences.pages.len();
+            for (key, starts) in occurrences.pages {
+                node.mentions += starts.len();
+                pages.entry(key).or_default().push((id, starts));
+            }
+        }
+        nodes.push(node);
+        let found = nodes.iter().filter(|node| node.pages > 0).count();
+        control.report(&SearchProgress { checked: id + 1, candidates: persons.len(), hits: found });
+    }
+
+    let mut weights: HashMap<(usize, usize, Option<u64>), (usize, usize)> = HashMap::new();
+    for (page, mentions) in &pages {
+        let group = match options.group_by {
+            NetworkGrouping::None => None,
+            NetworkGrouping::Book => Some(page.text_id),
+            NetworkGrouping::Century => centuries.get(&page.text_id).copied(),
+        };
+        for (i, (source, source_starts)) in mentions.iter().enumerate() {
+            for (target, target_starts) in &mentions[i + 1..] {
+                let weight = match options.window {
+                    Some(window) => close_pairs(source_starts, target_starts, window),
+                    None => 1,
+                };
+                if weight > 0 {
+                    let edge = weights.entry((*source, *target, group)).or_default();
+                    edge.0 += weight;
+                    edge.1 += 1;
+                }
+            }
+        }
+    }
+
+    let mut edges: Vec<NetworkEdge> = weights
+        .into_iter()
+        .filter(|(_, (weight, _))| *weight >= options.min_weight.max(1))
+        .map(|((source, target, group), (weight, pages))| NetworkEdge { source, target, group, weight, pages })
+        .collect();
+    edges.sort_by(|a, b| {
+        b.weight.cmp(&a.weight).then((a.source, a.target, a.group).cmp(&(b.source, b.target, b.group)))
+    });
+
+    Ok(CoMentionNetwork { nodes, edges, group_by: options.group_by, elapsed_ms: start.elapsed().as_millis() as u64 })
+}
+
+/// Name of the edge group attribute
+fn group_title(group_by: NetworkGrouping) -> Option<&'static str> {
+    match group_by {
+        NetworkGrouping::None => None,
+        NetworkGrouping::Book => Some("book_id"),
+        NetworkGrouping::Century => Some("century_ah"),
+    }
+}
+
+fn to_csv(network: &CoMentionNetwork) -> String {
+    let group = group_title(network.group_by);
+    // BOM so Excel detects UTF-8 Arabic text
+    let mut out = String::from("\u{FEFF}Source,Target,Weight,Pages");
+    if let Some(group) = group {
+        let _ = write!(out, ",{}", group);
+    }
+    out.push('\n');
+    for edge in &network.edges {
+        let label = |id: usize| escape_csv(&network.nodes[id].label);
+        let _ = write!(out, "{},{},{},{}", label(edge.source), label(edge.target), edge.weight, edge.pages);
+        if group.is_some() {
+            let _ = write!(out, ",{}", edge.group.map(|g| g.to_string()).unwrap_or_default());
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn to_gexf(network: &CoMentionNetwork) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n\
+         <graph defaultedgetype=\"undirected\">\n\
+         <attributes class=\"node\"><attribute id=\"pages\" title=\"pages\" type=\"integer\"/><attribute id=\"mentions\" title=\"mentions\" type=\"integer\"/></attributes>\n\
+         <attributes class=\"edge\"><attribute id=\"pages\" title=\"pages\" type=\"integer\"/>",
+    );
+    if let Some(group) = group_title(network.group_by) {
+        let _ = write!(out, "<attribute id=\"group\" title=\"{}\" type=\"long\"/>", group);
+    }
+    out.push_str("</attributes>\n<nodes>\n");
+    for node in &network.nodes {
+        let _ = write!(out, "<node id=\"{}\" label=\"", node.id);
+        push_xml_escaped(&mut out, &node.label);
+        let _ = writeln!(
+            out,
+            "\"><attvalues><attvalue for=\"pages\" value=\"{}\"/><attvalue for=\"mentions\" value=\"{}\"/></attvalues></node>",
+            node.pages, node.mentions
+        );
+    }
+    out.push_str("</nodes>\n<edges>\n");
+    for (id, edge) in network.edges.iter().enumerate() {
+        let _ = write!(
+            out,
+            "<edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"><attvalues><attvalue for=\"pages\" value=\"{}\"/>",
+            id, edge.source, edge.target, edge.weight, edge.pages
+        );
+        if let Some(group) = edge.group {
+            let _ = write!(out, "<attvalue for=\"group\" value=\"{}\"/>", group);
+        }
+        out.push_str("</attvalues></edge>\n");
+    }
+    out.push_str("</edges>\n</graph>\n</gexf>\n");
+    out
+}
+
+fn to_graphml(network: &CoMentionNetwork) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n\
+         <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n\
+         <key id=\"node_pages\" for=\"node\" attr.name=\"pages\" attr.type=\"int\"/>\n\
+         <key id=\"mentions\" for=\"node\" attr.name=\"mentions\" attr.type=\"int\"/>\n\
+         <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n\
+         <key id=\"edge_pages\" for=\"edge\" attr.name=\"pages\" attr.type=\"int\"/>\n",
+    );
+    if let Some(group) = group_title(network.group_by) {
+        let _ = writeln!(out, "<key id=\"group\" for=\"edge\" attr.name=\"{}\" attr.type=\"long\"/>", group);
+    }
+    out.push_str("<graph id=\"co-mentions\" edgedefault=\"undirected\">\n");
+    for node in &network.nodes {
+        let _ = write!(out, "<node id=\"n{}\"><data key=\"label\">", node.id);
+        push_xml_escaped(&mut out, &node.label);
+        let _ = writeln!(
+            out,
+            "</data><data key=\"node_pages\">{}</data><data key=\"mentions\">{}</data></node>",
+            node.pages, node.mentions
+        );
+    }
+    for edge in &network.edges {
+        let _ = write!(
+            out,
+            "<edge source=\"n{}\" target=\"n{}\"><data key=\"weight\">{}</data><data key=\"edge_pages\">{}</data>",
+            edge.source, edge.target, edge.weight, edge.pages
+        );
+        if let Some(group) = edge.group {
+            let _ = write!(out, "<data key=\"group\">{}</data>", group);
+        }
+        out.push_str("</edge>\n");
+    }
+    out.push_str("</graph>\n</graphml>\n");
+    out
+}
+
+/// Write a network as a CSV edge list, GEXF or GraphML
+pub fn write_network(network: &CoMentionNetwork, format: NetworkFormat, path: &Path) -> Result<()> {
+    let contents = match format {
+        NetworkFormat::Csv => to_csv(network),
+        NetworkFormat::Gexf => to_gexf(network),
+        NetworkFormat::Graphml => to_graphml(network),
+    };
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_pairs_and_exports() {
+        // 10 is near 12 and 14; 50 is near nothing; the shared start 30 is one name
+        assert_eq!(close_pairs(&[10, 30, 50], &[12, 14, 30, 90], 5), 2);
+        assert_eq!(close_pairs(&[3], &[0, 1, 6], 3), 3);
+
+        let network = CoMentionNetwork {
+            nodes: vec![
+                NetworkNode { id: 0, label: "الزهري".to_string(), pages: 3, mentions: 4, truncated: false },
+                NetworkNode { id: 1, label: "مالك, بن أنس".to_string(), pages: 2, mentions: 2, truncated: false },
+            ],
+            edges: vec![NetworkEdge { source: 0, target: 1, group: Some(2), weight: 2, pages: 2 }],
+            group_by: NetworkGrouping::Century,
+            elapsed_ms: 0,
+        };
+        assert_eq!(to_csv(&network), "\u{FEFF}Source,Target,Weight,Pages,century_ah\nالزهري,\"مالك, بن أنس\",2,2,2\n");
+        assert!(to_gexf(&network).contains("<edge id=\"0\" source=\"0\" target=\"1\" weight=\"2\">"));
+        assert!(to_graphml(&network).contains("<data key=\"group\">2</data>"));
+    }
+}
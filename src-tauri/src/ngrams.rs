@@ -0,0 +1,428 @@
+//! N-gram extraction for recurring formulae
+//!
+//! Reads page_tokens book by book and counts every 2–6 gram of the surface or
+//! lemma layer, together with the number of books it occurs in. N-grams never
+//! cross a page or a token without a value. When the counts outgrow memory they
+//! are spilled to sorted run files and merged at the end, so slices of any size
+//! can be written out to a TSV file.
+
+use crate::frequency::{load_values, Subcorpus};
+use crate::search::{SearchMode, SearchTerm};
+use crate::tokens::TokenField;
+use anyhow::{anyhow, Context, Result};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Shortest and longest n-grams extracted
+const MIN_N: usize = 2;
+const MAX_N: usize = 6;
+
+/// Distinct n-grams held in memory before spilling a run to disk
+const SPILL_THRESHOLD: usize = 2_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NgramOptions {
+    /// Surface or Lemma
+    pub layer: TokenField,
+    pub min_n: usize,
+    pub max_n: usize,
+    /// Minimum occurrences in the slice
+    pub min_frequency: u64,
+    /// Minimum number of books it occurs in
+    pub min_books: u32,
+    /// Top n-grams returned, by frequency
+    pub limit: usize,
+    /// Every n-gram above the thresholds is also written here as TSV
+    pub path: Option<PathBuf>,
+}
+
+impl Default for NgramOptions {
+    fn default() -> Self {
+        Self {
+            layer: TokenField::Surface,
+            min_n: 3,
+            max_n: 5,
+            min_frequency: 5,
+            min_books: 2,
+            limit: 500,
+            path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NgramRequest {
+    #[serde(default)]
+    pub subcorpus: Subcorpus,
+    #[serde(default)]
+    pub options: NgramOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ngram {
+    pub text: String,
+    pub n: usize,
+    pub count: u64,
+    pub books: u32,
+    /// Phrase search term for this n-gram
+    pub term: SearchTerm,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NgramResults {
+    pub layer: TokenField,
+    pub books: usize,
+    pub total_tokens: u64,
+    /// N-grams meeting min_frequency and min_books
+    pub kept: u64,
+    /// Most frequent first
+    pub ngrams: Vec<Ngram>,
+    /// Runs spilled to disk while counting
+    pub spilled_runs: usize,
+    pub path: Option<PathBuf>,
+    pub elapsed_ms: u64,
+}
+
+/// Interned layer values making up an n-gram
+type NgramKey = Vec<u32>;
+
+/// Occurrences and number of books
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct NgramCount {
+    count: u64,
+    books: u32,
+    /// Positions of the first and last books it occurs in, so a book split
+    /// across two runs is counted once when they are merged
+    first_book: u32,
+    last_book: u32,
+}
+
+/// A sorted run of n-gram counts on disk
+struct RunReader {
+    reader: BufReader<File>,
+}
+
+impl RunReader {
+    fn next(&mut self) -> Result<Option<(NgramKey, NgramCount)>> {
+        let mut n = [0u8; 1];
+        match self.reader.read_exact(&mut n) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut key = Vec::with_capacity(n[0] as usize);
+        let mut word = [0u8; 4];
+        for _ in 0..n[0] {
+            self.reader.read_exact(&mut word)?;
+            key.push(u32::from_le_bytes(word));
+        }
+        let mut count = [0u8; 8];
+        self.reader.read_exact(&mut count)?;
+        let mut books = [0u32; 3];
+        for value in &mut books {
+            self.reader.read_exact(&mut word)?;
+            *value = u32::from_le_bytes(word);
+        }
+        let [books, first_book, last_book] = books;
+        Ok(Some((key, NgramCount { count: u64::from_le_bytes(count), books, first_book, last_book })))
+    }
+}
+
+/// Counts of the pages read so far, spilling to sorted runs. The runs are
+/// removed with the counter.
+struct NgramCounter {
+    counts: HashMap<NgramKey, NgramCount>,
+    spill_dir: PathBuf,
+    runs: Vec<PathBuf>,
+    spill_threshold: usize,
+}
+
+impl NgramCounter {
+    /// Count an occurrence in the book at position `book` (books are added in order)
+    fn add(&mut self, key: NgramKey, book: u32) {
+        match self.counts.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let entry = entry.get_mut();
+                entry.count += 1;
+                if entry.last_book != book {
+                    entry.books += 1;
+                    entry.last_book = book;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(NgramCount { count: 1, books: 1, first_book: book, last_book: book });
+            }
+        }
+    }
+
+    /// Spill once the counts outgrow memory, even within a book
+    fn spill_if_full(&mut self) -> Result<()> {
+        if self.counts.len() >= self.spill_threshold {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        if self.runs.is_empty() {
+            std::fs::create_dir_all(&self.spill_dir)?;
+        }
+        let path = self.spill_dir.join(format!("run-{}.bin", self.runs.len()));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        let mut entries: Vec<(NgramKey, NgramCount)> = self.counts.drain().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        for (key, count) in entries {
+            writer.write_all(&[key.len() as u8])?;
+            for id in key {
+                writer.write_all(&id.to_le_bytes())?;
+            }
+            writer.write_all(&count.count.to_le_bytes())?;
+            for value in [count.books, count.first_book, count.last_book] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        writer.flush()?;
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Pass every n-gram with its total count to `visit`
+    fn finish(mut self, mut visit: impl FnMut(NgramKey, NgramCount) -> Result<()>) -> Result<usize> {
+        if self.runs.is_empty() {
+            for (key, count) in self.counts.drain() {
+                visit(key, count)?;
+            }
+            return Ok(0);
+        }
+        if !self.counts.is_empty() {
+            self.spill()?;
+        }
+
+        let mut readers = self
+            .runs
+            .iter()
+            .map(|path| Ok(RunReader { reader: BufReader::new(File::open(path)?) }))
+            .collect::<Result<Vec<_>>>()?;
+        let mut heads = BinaryHeap::new();
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some((key, count)) = reader.next()? {
+                heads.push(Reverse((key, run, count)));
+            }
+        }
+
+        // Equal keys come out in run order, that is in book order
+        let mut current: Option<(NgramKey, NgramCount)> = None;
+        while let Some(Reverse((key, run, count))) = heads.pop() {
+            if let Some((next_key, next_count)) = readers[run].next()? {
+                heads.push(Reverse((next_key, run, next_count)));
+            }
+            match current.as_mut() {
+                Some((current_key, total)) if *current_key == key => {
+                    total.count += count.count;
+                    total.books += count.books - u32::from(total.last_book == count.first_book);
+                    total.last_book = count.last_book;
+                }
+                _ => {
+                    if let Some((done_key, total)) = current.take() {
+                        visit(done_key, total)?;
+                    }
+                    current = Some((key, count));
+                }
+            }
+        }
+        if let Some((key, total)) = current {
+            visit(key, total)?;
+        }
+
+        Ok(self.runs.len())
+    }
+}
+
+impl Drop for NgramCounter {
+    fn drop(&mut self) {
+        if self.spill_dir.exists() {
+            std::fs::remove_dir_all(&self.spill_dir).ok();
+        }
+    }
+}
+
+fn book_ids_of(corpus: &Connection, book_ids: Option<&[u64]>) -> Result<Vec<u64>> {
+    match book_ids {
+        Some(ids) => Ok(ids.to_vec()),
+        None => Ok(corpus
+            .prepare("SELECT id FROM books ORDER BY id")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id as u64))
+            .collect::<rusqlite::Result<_>>()?),
+    }
+}
+
+/// Recurring n-grams of a subcorpus (`book_ids` None = whole corpus)
+pub fn extract_ngrams(corpus_db_path: &Path, book_ids: Option<&[u64]>, options: &NgramOptions) -> Result<NgramResults> {
+    extract_with_spill(corpus_db_path, book_ids, options, SPILL_THRESHOLD)
+}
+
+fn extract_with_spill(corpus_db_path: &Path, book_ids: Option<&[u64]>, options: &NgramOptions, spill_threshold: usize) -> Result<NgramResults> {
+    let start = std::time::Instant::now();
+    let mode = match options.layer {
+        TokenField::Surface => SearchMode::Surface,
+        TokenField::Lemma => SearchMode::Lemma,
+        TokenField::Root => return Err(anyhow!("N-grams are extracted from the surface or lemma layer")),
+    };
+    let min_n = options.min_n.clamp(MIN_N, MAX_N);
+    let max_n = options.max_n.clamp(min_n, MAX_N);
+
+    let corpus = Connection::open(corpus_db_path)
+        .with_context(|| format!("Failed to open corpus.db at {:?}", corpus_db_path))?;
+    let book_ids = book_ids_of(&corpus, book_ids)?;
+
+    // Token definition ID -> interned layer value
+    let mut id_values: HashMap<u32, Option<String>> = HashMap::new();
+    let mut interned: HashMap<u32, Option<u32>> = HashMap::new();
+    let mut values: Vec<String> = Vec::new();
+    let mut value_ids: HashMap<String, u32> = HashMap::new();
+
+    let spill_dir = std::env::temp_dir().join(format!(
+        "kashshaf-ngrams-{}-{}",
+        std::process::id(),
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0)
+    ));
+    let mut counter = NgramCounter { counts: HashMap::new(), spill_dir, runs: Vec::new(), spill_threshold: spill_threshold.max(1) };
+    let mut total_tokens: u64 = 0;
+
+    let mut stmt = corpus.prepare("SELECT token_ids FROM page_tokens WHERE book_id = ?1 ORDER BY page_id")?;
+    for (position, &book_id) in book_ids.iter().enumerate() {
+        let pages: Vec<Vec<u32>> = stmt
+            .query_map([book_id as i64], |row| row.get::<_, Vec<u8>>(0))?
+            .map(|blob| {
+                blob.map(|blob| blob.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect())
+            })
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut unknown: Vec<u32> = pages.iter().flatten().filter(|id| !interned.contains_key(id)).copied().collect();
+        unknown.sort_unstable();
+        unknown.dedup();
+        load_values(&corpus, options.layer, &unknown, &mut id_values)?;
+        for id in unknown {
+            let value = id_values.remove(&id).flatten().map(|value| {
+                *value_ids.entry(value.clone()).or_insert_with(|| {
+                    values.push(value);
+                    (values.len() - 1) as u32
+                })
+            });
+            interned.insert(id, value);
+        }
+
+        for page in &pages {
+            total_tokens += page.len() as u64;
+            let sequence: Vec<Option<u32>> = page.iter().map(|id| interned[id]).collect();
+            for n in min_n..=max_n {
+                for window in sequence.windows(n) {
+                    if let Some(key) = window.iter().copied().collect::<Option<NgramKey>>() {
+                        counter.add(key, position as u32);
+                    }
+                }
+            }
+            counter.spill_if_full()?;
+        }
+    }
+
+    let text_of = |key: &[u32]| key.iter().map(|id| values[*id as usize].as_str()).collect::<Vec<_>>().join(" ");
+    let mut writer = match &options.path {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {:?}", path))?);
+            writeln!(writer, "ngram\tn\tcount\tbooks")?;
+            Some(writer)
+        }
+        None => None,
+    };
+    let mut top: BinaryHeap<Reverse<(u64, u32, NgramKey)>> = BinaryHeap::new();
+    let mut kept: u64 = 0;
+
+    let spilled_runs = counter.finish(|key, count| {
+        if count.count < options.min_frequency || count.books < options.min_books {
+            return Ok(());
+        }
+        kept += 1;
+        if let Some(writer) = writer.as_mut() {
+            writeln!(writer, "{}\t{}\t{}\t{}", text_of(&key), key.len(), count.count, count.books)?;
+        }
+        if options.limit > 0 {
+            top.push(Reverse((count.count, count.books, key)));
+            if top.len() > options.limit {
+                top.pop();
+            }
+        }
+        Ok(())
+    })?;
+    if let Some(mut writer) = writer {
+        writer.flush()?;
+    }
+
+    let mut ranked: Vec<(u64, u32, NgramKey)> = top.into_iter().map(|Reverse(entry)| entry).collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.cmp(&a.1)).then_with(|| a.2.cmp(&b.2)));
+    let ngrams = ranked
+        .into_iter()
+        .map(|(count, books, key)| {
+            let text = text_of(&key);
+            Ngram { n: key.len(), count, books, term: SearchTerm { query: text.clone(), mode }, text }
+        })
+        .collect();
+
+    Ok(NgramResults {
+        layer: options.layer,
+        books: book_ids.len(),
+        total_tokens,
+        kept,
+        ngrams,
+        spilled_runs,
+        path: options.path.clone(),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_corpus::TestCorpus;
+
+    #[test]
+    fn test_spilled_ngrams_match() {
+        let corpus = TestCorpus::new("ngrams-test");
+        corpus.add_books(1..=4);
+        corpus
+            .conn
+            .execute_batch("INSERT INTO token_definitions VALUES (1, 'صلى', NULL, NULL), (2, 'الله', NULL, NULL), (3, 'عليه', NULL, NULL), (4, 'وسلم', NULL, NULL), (5, 'قال', NULL, NULL);")
+            .unwrap();
+        corpus.add_page(1, 1, &[5, 1, 2, 3, 4, 5, 1, 2, 3, 4]);
+        corpus.add_page(2, 1, &[1, 2, 3, 4, 5]);
+        // Unknown definition 9 breaks the formula
+        corpus.add_page(3, 1, &[1, 2, 9, 3, 4]);
+        // A book spilled in two runs counts once
+        corpus.add_page(4, 1, &[1, 2, 3, 4]);
+        corpus.add_page(4, 2, &[1, 2, 3, 4]);
+
+        let path = corpus.path();
+        let options = NgramOptions { min_n: 4, max_n: 4, min_frequency: 2, min_books: 2, ..Default::default() };
+        let in_memory = extract_with_spill(&path, None, &options, usize::MAX).unwrap();
+        let spilled = extract_with_spill(&path, None, &options, 1).unwrap();
+
+        assert_eq!(in_memory.spilled_runs, 0);
+        assert_eq!(spilled.spilled_runs, 4);
+        for results in [&in_memory, &spilled] {
+            assert_eq!(results.kept, 2);
+            let formula = &results.ngrams[0];
+            assert_eq!((formula.text.as_str(), formula.count, formula.books), ("صلى الله عليه وسلم", 5, 3));
+        }
+        // The runs are removed with the counter
+        let spill_prefix = format!("kashshaf-ngrams-{}-", std::process::id());
+        assert!(!std::env::temp_dir().read_dir().unwrap().any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with(&spill_prefix)));
+    }
+}
@@ -0,0 +1,279 @@
+//! Person authority file
+//!
+//! Persons are kept in the settings database with every name form they are
+//! known by, so a prosopography is entered once and reused: name search takes
+//! a person ID in place of a form, and the mentions of a list of persons can be
+//! counted across the corpus or a collection in one go.
+
+use crate::control::{SearchControl, SearchProgress};
+use crate::names::{generate_search_patterns, NameForm, NameSearchForm};
+use crate::search::{SearchEngine, SearchFilters};
+use anyhow::{anyhow, Result};
+use rusqlite::{Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Person {
+    pub id: i64,
+    /// Name shown in lists and exports
+    pub name: String,
+    /// Ways the person is named; a mention of any one of them counts
+    pub forms: Vec<NameForm>,
+    pub death_ah: Option<u64>,
+    pub notes: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Fields of a person as created or edited
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonInput {
+    pub name: String,
+    pub forms: Vec<NameForm>,
+    pub death_ah: Option<u64>,
+    pub notes: Option<String>,
+}
+
+impl Person {
+    /// Search patterns of all the person's forms
+    pub fn search_patterns(&self) -> Vec<String> {
+        forms_patterns(&self.forms)
+    }
+}
+
+/// Search patterns of some name forms, once each
+fn forms_patterns(forms: &[NameForm]) -> Vec<String> {
+    let mut patterns: Vec<String> = Vec::new();
+    for pattern in forms.iter().flat_map(generate_search_patterns) {
+        if !patterns.contains(&pattern) {
+            patterns.push(pattern);
+        }
+    }
+    patterns
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersonMentionsRequest {
+    /// Persons to count; all persons in the registry if empty
+    #[serde(default)]
+    pub person_ids: Vec<i64>,
+    #[serde(default)]
+    pub subcorpus: crate::frequency::Subcorpus,
+}
+
+/// Where a name occurs under some filters
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NameMentions {
+    pub pages: usize,
+    pub books: usize,
+    /// Occurrences of any pattern; overlapping patterns count once
+    pub mentions: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonMentions {
+    pub person_id: i64,
+    pub name: String,
+    #[serde(flatten)]
+    pub counts: NameMentions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonMentionCounts {
+    /// Most mentioned first
+    pub persons: Vec<PersonMentions>,
+    pub elapsed_ms: u64,
+}
+
+/// Create the persons table if needed
+pub fn ensure_persons_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS persons (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            forms TEXT NOT NULL,
+            death_ah INTEGER,
+            notes TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+const PERSON_COLUMNS: &str = "id, name, forms, death_ah, notes, created_at, updated_at";
+
+fn person_from_row(row: &Row) -> rusqlite::Result<Person> {
+    let forms_json: String = row.get(2)?;
+    Ok(Person {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        forms: serde_json::from_str(&forms_json)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?,
+        death_ah: row.get::<_, Option<i64>>(3)?.map(|death| death as u64),
+        notes: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+fn validate(input: &PersonInput) -> Result<String> {
+    if input.name.trim().is_empty() {
+        return Err(anyhow!("A person needs a name"));
+    }
+    // A person no search could find would silently drop out of name searches
+    if forms_patterns(&input.forms).is_empty() {
+        return Err(anyhow!("{} has no name form to search for", input.name.trim()));
+    }
+    Ok(serde_json::to_string(&input.forms)?)
+}
+
+pub fn create_person(conn: &Connection, input: &PersonInput) -> Result<Person> {
+    let forms_json = validate(input)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO persons (name, forms, death_ah, notes, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        rusqlite::params![input.name.trim(), forms_json, input.death_ah.map(|death| death as i64), input.notes, now],
+    )?;
+    get_person(conn, conn.last_insert_rowid())
+}
+
+pub fn get_person(conn: &Connection, id: i64) -> Result<Person> {
+    conn.query_row(&format!("SELECT {} FROM persons WHERE id = ?1", PERSON_COLUMNS), [id], person_from_row)
+        .optional()?
+        .ok_or_else(|| anyhow!("Person with id {} not found", id))
+}
+
+/// All persons, by name
+pub fn list_persons(conn: &Connection) -> Result<Vec<Person>> {
+    let persons = conn
+        .prepare(&format!("SELECT {} FROM persons ORDER BY name, id", PERSON_COLUMNS))?
+        .query_map([], person_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(persons)
+}
+
+pub fn update_person(conn: &Connection, id: i64, input: &PersonInput) -> Result<Person> {
+    let forms_json = validate(input)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    let rows_affected = conn.execute(
+        "UPDATE persons SET name = ?1, forms = ?2, death_ah = ?3, notes = ?4, updated_at = ?5 WHERE id = ?6",
+        rusqlite::params![input.name.trim(), forms_json, input.death_ah.map(|death| death as i64), input.notes, now, id],
+    )?;
+    if rows_affected == 0 {
+        return Err(anyhow!("Person with id {} not found", id));
+    }
+    get_person(conn, id)
+}
+
+pub fn delete_person(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM persons WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+/// Replace person references in name search forms by the person's patterns
+pub fn resolve_person_forms(conn: &Connection, forms: Vec<NameSearchForm>) -> Result<Vec<NameSearchForm>> {
+    forms
+        .into_iter()
+        .map(|form| match form {
+            NameSearchForm::Person { person_id } => {
+                let person = get_person(conn, person_id)?;
+                let patterns = person.search_patterns();
+                if patterns.is_empty() {
+                    return Err(anyhow!("{} has no name form to search for", person.name));
+                }
+                Ok(NameSearchForm::Patterns(patterns))
+            }
+            form => Ok(form),
+        })
+        .collect()
+}
+
+/// The persons of a request: the listed ones in order, or the whole registry
+pub fn requested_persons(conn: &Connection, person_ids: &[i64]) -> Result<Vec<Person>> {
+    if person_ids.is_empty() {
+        return list_persons(conn);
+    }
+    person_ids.iter().map(|&id| get_person(conn, id)).collect()
+}
+
+/// Mentions of each person within the given books (`None`: the whole corpus).
+/// Progress is reported per person: checked persons out of all, persons found.
+pub fn count_person_mentions(
+    engine: &SearchEngine,
+    persons: &[Person],
+    book_ids: Option<&[u64]>,
+    control: &SearchControl,
+) -> Result<PersonMentionCounts> {
+    let start = std::time::Instant::now();
+    let filters = SearchFilters { book_ids: book_ids.map(<[u64]>::to_vec), ..Default::default() };
+
+    let mut counts = Vec::with_capacity(persons.len());
+    let mut found = 0;
+    for (checked, person) in persons.iter().enumerate() {
+        control.check()?;
+        // An empty subcorpus would otherwise mean no book filter at all
+        let mentions = if book_ids.is_some_and(|ids| ids.is_empty()) {
+            NameMentions::default()
+        } else {
+            engine.count_name_mentions(&person.search_patterns(), &filters, control)?
+        };
+        if mentions.pages > 0 {
+            found += 1;
+        }
+        counts.push(PersonMentions { person_id: person.id, name: person.name.clone(), counts: mentions });
+        control.report(&SearchProgress { checked: checked + 1, candidates: persons.len(), hits: found });
+    }
+    counts.sort_by_key(|person| std::cmp::Reverse(person.counts.mentions));
+
+    Ok(PersonMentionCounts { persons: counts, elapsed_ms: start.elapsed().as_millis() as u64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_and_form_resolution() {
+        let conn = Connection::open_in_memory().unwrap();
+        ensure_persons_table(&conn).unwrap();
+
+        let form = |nasab: &str| NameForm { nasab: nasab.to_string(), allow_two_nasab: true, ..Default::default() };
+        let mut input = PersonInput {
+            name: "Ibn Shihab al-Zuhri".to_string(),
+            forms: vec![form("محمد بن مسلم"), NameForm { shuhra: "ابن شهاب".to_string(), ..Default::default() }],
+            death_ah: Some(124),
+            notes: None,
+        };
+        let person = create_person(&conn, &input).unwrap();
+        assert_eq!(person.forms.len(), 2);
+        assert_eq!(person.death_ah, Some(124));
+
+        // Patterns of every form, once each
+        let patterns = person.search_patterns();
+        assert!(patterns.contains(&"محمد بن مسلم".to_string()));
+        assert!(patterns.contains(&"والمعروف بابن شهاب".to_string()));
+
+        input.notes = Some("d. 124".to_string());
+        assert_eq!(update_person(&conn, person.id, &input).unwrap().notes.as_deref(), Some("d. 124"));
+        assert!(create_person(&conn, &PersonInput::default()).is_err());
+        let kunya_only = NameForm { kunyas: vec!["ابو بكر".to_string()], ..Default::default() };
+        assert!(create_person(&conn, &PersonInput { name: "Abu Bakr".to_string(), forms: vec![kunya_only.clone()], ..Default::default() }).is_err());
+
+        let forms: Vec<NameSearchForm> = serde_json::from_str(&format!(r#"[{{"person_id": {}}}, ["الزهري"]]"#, person.id)).unwrap();
+        let resolved = resolve_person_forms(&conn, forms).unwrap();
+        assert_eq!(resolved[0].search_patterns().unwrap(), patterns);
+        assert_eq!(resolved[1].search_patterns().unwrap(), ["الزهري"]);
+
+        delete_person(&conn, person.id).unwrap();
+        assert!(list_persons(&conn).unwrap().is_empty());
+        assert!(resolve_person_forms(&conn, vec![NameSearchForm::Person { person_id: person.id }]).is_err());
+
+        // Stored before persons needed a searchable form
+        conn.execute("INSERT INTO persons (name, forms, created_at, updated_at) VALUES ('Abu Bakr', ?1, '', '')", [serde_json::to_string(&[kunya_only]).unwrap()]).unwrap();
+        let forms = vec![NameSearchForm::Person { person_id: conn.last_insert_rowid() }];
+        assert!(resolve_person_forms(&conn, forms).is_err());
+    }
+}
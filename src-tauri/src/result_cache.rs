@@ -0,0 +1,134 @@
+//! LRU cache of recent search hit lists
+//!
+//! Paging through results and switching back to an earlier tab re-run the same
+//! query. The cache keeps the leading docs of each query's hit list in sort
+//! order, together with its total hit count, so those pages are served without
+//! running Tantivy again.
+
+use crate::cursor::SortKey;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tantivy::DocAddress;
+
+/// Leading hits of one query, in SortKey order
+struct CachedHits {
+    total_hits: usize,
+    docs: Vec<(SortKey, DocAddress)>,
+}
+
+/// One page served from the cache
+pub struct CachedPage {
+    pub total_hits: usize,
+    pub docs: Vec<(SortKey, DocAddress)>,
+    /// Whether another page follows
+    pub has_more: bool,
+}
+
+impl CachedHits {
+    /// Up to `limit` docs starting `offset` docs after `after`.
+    /// None if the cached prefix is too short to tell whether more follow.
+    fn page(&self, after: Option<SortKey>, offset: usize, limit: usize) -> Option<CachedPage> {
+        let first = match after {
+            Some(after) => self.docs.partition_point(|(sort_key, _)| *sort_key <= after),
+            None => 0,
+        };
+        let start = (first + offset).min(self.docs.len());
+        let end = (first + offset + limit).min(self.docs.len());
+
+        // One extra doc tells us whether another page exists, unless every hit is cached
+        let complete = self.docs.len() >= self.total_hits;
+        if !complete && self.docs.len() <= first + offset + limit {
+            return None;
+        }
+
+        Some(CachedPage {
+            total_hits: self.total_hits,
+            docs: self.docs[start..end].to_vec(),
+            has_more: self.docs.len() > first + offset + limit,
+        })
+    }
+}
+
+/// Hit/miss counters of the result cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultCacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub struct ResultCache {
+    cache: Mutex<LruCache<String, CachedHits>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        let cache = LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(64).unwrap()));
+        Self { cache: Mutex::new(cache), hits: AtomicU64::new(0), misses: AtomicU64::new(0) }
+    }
+
+    /// A page of a cached query, counting a hit or miss
+    pub fn page(&self, key: &str, after: Option<SortKey>, offset: usize, limit: usize) -> Option<CachedPage> {
+        let page = self.cache.lock().unwrap().get(key).and_then(|hits| hits.page(after, offset, limit));
+        let counter = if page.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        page
+    }
+
+    /// Cache the leading `docs` of a query's hit list
+    pub fn insert(&self, key: String, total_hits: usize, docs: Vec<(SortKey, DocAddress)>) {
+        self.cache.lock().unwrap().put(key, CachedHits { total_hits, docs });
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> ResultCacheStats {
+        let cache = self.cache.lock().unwrap();
+        ResultCacheStats {
+            entries: cache.len(),
+            capacity: cache.cap().get(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(page_id: u64) -> SortKey {
+        SortKey { death_ah: 300, text_id: 1, part_index: 0, page_id }
+    }
+
+    #[test]
+    fn test_cached_pages() {
+        let cache = ResultCache::new(4);
+        let docs: Vec<_> = (0..10).map(|i| (key(i), DocAddress::new(0, i as u32))).collect();
+        cache.insert("q".to_string(), 25, docs);
+
+        let page = cache.page("q", None, 4, 5).unwrap();
+        assert_eq!(page.docs.len(), 5);
+        assert!(page.has_more);
+
+        let page = cache.page("q", Some(key(3)), 0, 5).unwrap();
+        assert_eq!(page.docs[0].0, key(4));
+
+        // Only 10 of the 25 hits are cached, so whether more follow page 5..10 is unknown
+        assert!(cache.page("q", None, 5, 5).is_none());
+        assert!(cache.page("other", None, 0, 5).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 2, 2));
+    }
+}
@@ -0,0 +1,619 @@
+//! Text reuse detection across books
+//!
+//! Pages are shingled into overlapping surface n-grams, and windows of
+//! consecutive shingles are summarised by MinHash signatures, so a quotation
+//! short next to its page is still found. The signatures are cut into LSH bands
+//! whose hashes are kept in reuse_index.db next to tantivy_index: windows are
+//! indexed end to end and looked up at a finer stride, and pages sharing a band
+//! hash are candidates. Candidates are re-read from page_tokens, scored by how
+//! much of a window of either page the other contains, and aligned token by
+//! token (Smith-Waterman, as for similar passages).
+//!
+//! The index is built book by book and each book is committed on its own, so an
+//! interrupted build resumes where it stopped. It records the corpus it was built
+//! from and starts afresh once the corpus changes.
+
+use crate::control::SearchControl;
+use crate::downloader::corpus_fingerprint;
+use crate::frequency::load_values;
+use crate::similar::{align, AlignedToken};
+use crate::tokens::TokenField;
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Surface tokens per shingle
+const SHINGLE_SIZE: usize = 5;
+
+/// Shingles per window; pages with fewer make a single window
+const WINDOW_SHINGLES: usize = 20;
+
+/// Stride of the windows looked up for a page (indexed windows don't overlap), so a
+/// passage as long as a window lines up with an indexed one to within a few shingles
+const QUERY_STRIDE: usize = 5;
+
+/// MinHash signature length of a window, cut into BANDS bands of ROWS rows. Window
+/// pairs become candidates with even odds at a Jaccard of (1/BANDS)^(1/ROWS) = 0.25;
+/// a quotation filling half of an indexed window reaches 1/3 against the best
+/// query window, which makes it a candidate with a probability of 0.85.
+const BANDS: usize = 16;
+const ROWS: usize = 2;
+
+/// Bumped when the index layout changes, so older indexes are rebuilt
+const INDEX_SCHEME: &str = "windows-1";
+
+/// Pages with fewer shingles are not indexed
+const MIN_SHINGLES: usize = 8;
+
+/// Candidates re-read and scored per page
+const MAX_CANDIDATES: usize = 500;
+
+/// Pages whose token definitions are looked up together
+const PAGE_BATCH: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReuseOptions {
+    /// Minimum share of a window of either page found on the other (see ReuseMatch::similarity)
+    pub min_similarity: f64,
+    /// Leave out pages of the same book
+    pub exclude_same_book: bool,
+    /// Only report pages of these books
+    pub book_ids: Option<Vec<u64>>,
+    pub limit: usize,
+}
+
+impl Default for ReuseOptions {
+    fn default() -> Self {
+        Self { min_similarity: 0.5, exclude_same_book: true, book_ids: None, limit: 50 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageReuseRequest {
+    pub book_id: u64,
+    pub page_id: u64,
+    #[serde(default)]
+    pub options: ReuseOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookReuseRequest {
+    pub book_id: u64,
+    #[serde(default)]
+    pub options: ReuseOptions,
+}
+
+/// A token range [start, end) of one page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReusePassage {
+    pub book_id: u64,
+    pub page_id: u64,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Two pages sharing text, with the aligned passages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReuseMatch {
+    pub source: ReusePassage,
+    pub target: ReusePassage,
+    /// Containment: the largest share of a window of WINDOW_SHINGLES shingles, on
+    /// either page, whose shingles occur on the other page
+    pub similarity: f64,
+    pub shared_shingles: usize,
+    /// LSH band hashes the windows of the two pages have in common
+    pub band_hits: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReuseResults {
+    pub book_id: u64,
+    /// None for a whole-book comparison
+    pub page_id: Option<u64>,
+    pub pages_checked: usize,
+    pub candidates: usize,
+    pub matches: Vec<ReuseMatch>,
+    /// The index doesn't cover every book yet, so matches in the others are missing
+    pub partial_index: bool,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReuseIndexStatus {
+    pub books_indexed: usize,
+    pub total_books: usize,
+    pub pages_indexed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReuseBuildProgress {
+    pub books_done: usize,
+    pub total_books: usize,
+    pub pages_indexed: u64,
+}
+
+/// A shingle hash and the token it starts at
+type Shingle = (u64, usize);
+
+/// Pseudo-random permutation of 64-bit values (splitmix64 finaliser)
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// FNV-1a; stable across builds, unlike std's hasher
+fn hash_str(text: &str) -> u64 {
+    text.bytes().fold(0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3))
+}
+
+/// Shingles of a page's token hashes. Tokens without a surface break shingles.
+fn shingles(tokens: &[Option<u64>]) -> Vec<Shingle> {
+    tokens
+        .windows(SHINGLE_SIZE)
+        .enumerate()
+        .filter_map(|(start, window)| {
+            window.iter().try_fold(0u64, |hash, token| token.map(|token| mix(hash ^ token))).map(|hash| (hash, start))
+        })
+        .collect()
+}
+
+/// Windows of WINDOW_SHINGLES consecutive shingles starting every `stride`
+/// shingles; the last one always ends with the page
+fn windows(shingles: &[Shingle], stride: usize) -> Vec<&[Shingle]> {
+    if shingles.len() <= WINDOW_SHINGLES {
+        return vec![shingles];
+    }
+    let last = shingles.len() - WINDOW_SHINGLES;
+    let mut starts: Vec<usize> = (0..=last).step_by(stride).collect();
+    if starts.last() != Some(&last) {
+        starts.push(last);
+    }
+    starts.into_iter().map(|start| &shingles[start..start + WINDOW_SHINGLES]).collect()
+}
+
+fn signature(shingles: &[Shingle]) -> [u64; BANDS * ROWS] {
+    let mut signature = [u64::MAX; BANDS * ROWS];
+    for &(hash, _) in shingles {
+        for (i, slot) in signature.iter_mut().enumerate() {
+            *slot = (*slot).min(mix(hash ^ mix(i as u64 + 1)));
+        }
+    }
+    signature
+}
+
+/// One hash per band of a window; stored as i64 for SQLite
+fn band_hashes(window: &[Shingle]) -> [i64; BANDS] {
+    let signature = signature(window);
+    let mut bands = [0i64; BANDS];
+    for (band, rows) in signature.chunks(ROWS).enumerate() {
+        bands[band] = rows.iter().fold(band as u64, |hash, row| mix(hash ^ row)) as i64;
+    }
+    bands
+}
+
+/// Distinct band hashes of a page's windows
+fn page_bands(shingles: &[Shingle], stride: usize) -> HashSet<(usize, i64)> {
+    windows(shingles, stride)
+        .into_iter()
+        .flat_map(|window| band_hashes(window).into_iter().enumerate())
+        .collect()
+}
+
+fn shingle_set(shingles: &[Shingle]) -> HashSet<u64> {
+    shingles.iter().map(|(hash, _)| *hash).collect()
+}
+
+/// Largest share of a window of `shingles` that occurs in `other`
+fn window_containment(shingles: &[Shingle], other: &HashSet<u64>) -> f64 {
+    let found: Vec<usize> = shingles.iter().map(|(hash, _)| other.contains(hash) as usize).collect();
+    let window = WINDOW_SHINGLES.min(found.len());
+    if window == 0 {
+        return 0.0;
+    }
+    let mut count: usize = found[..window].iter().sum();
+    let mut best = count;
+    for end in window..found.len() {
+        count = count + found[end] - found[end - window];
+        best = best.max(count);
+    }
+    best as f64 / window as f64
+}
+
+/// Token range [start, end) covered by the shingles shared with the other page
+fn shared_span(shingles: &[Shingle], shared: &HashSet<u64>) -> Option<(usize, usize)> {
+    let starts = shingles.iter().filter(|(hash, _)| shared.contains(hash)).map(|(_, start)| *start);
+    starts.fold(None, |span, start| match span {
+        None => Some((start, start + SHINGLE_SIZE)),
+        Some((min, max)) => Some((min.min(start), max.max(start + SHINGLE_SIZE))),
+    })
+}
+
+/// Aligned passages of two pages: the best local alignment of their tokens within
+/// the spans of their shared shingles
+fn align_pages(source: &Page, target: &Page, shared: &HashSet<u64>) -> Option<((usize, usize), (usize, usize))> {
+    let (source_start, source_end) = shared_span(&source.shingles, shared)?;
+    let (target_start, target_end) = shared_span(&target.shingles, shared)?;
+    let tokens = |page: &Page, start: usize, end: usize| page.tokens[start..end].iter().map(|&token| AlignedToken(token)).collect::<Vec<_>>();
+    let alignment = align(&tokens(source, source_start, source_end), &tokens(target, target_start, target_end))?;
+    Some((
+        (source_start + alignment.source_range.start, source_start + alignment.source_range.end),
+        (target_start + alignment.target_range.start, target_start + alignment.target_range.end),
+    ))
+}
+
+/// A page's surface token hashes and its shingles
+struct Page {
+    tokens: Vec<Option<u64>>,
+    shingles: Vec<Shingle>,
+}
+
+impl Page {
+    fn new(tokens: Vec<Option<u64>>) -> Self {
+        let shingles = shingles(&tokens);
+        Self { tokens, shingles }
+    }
+}
+
+/// Reads pages from corpus.db as surface token hashes
+struct PageReader {
+    corpus: Connection,
+    token_hashes: HashMap<u32, Option<u64>>,
+}
+
+impl PageReader {
+    fn new(corpus: Connection) -> Self {
+        Self { corpus, token_hashes: HashMap::new() }
+    }
+
+    fn hashes(&mut self, token_ids: &[u32]) -> Result<Vec<Option<u64>>> {
+        let mut unknown: Vec<u32> = token_ids.iter().filter(|id| !self.token_hashes.contains_key(id)).copied().collect();
+        unknown.sort_unstable();
+        unknown.dedup();
+        let mut values = HashMap::new();
+        load_values(&self.corpus, TokenField::Surface, &unknown, &mut values)?;
+        for (id, value) in values {
+            self.token_hashes.insert(id, value.as_deref().map(hash_str));
+        }
+        Ok(token_ids.iter().map(|id| self.token_hashes.get(id).copied().flatten()).collect())
+    }
+
+    fn page(&mut self, book_id: u64, page_id: u64) -> Result<Page> {
+        let blob: Vec<u8> = self
+            .corpus
+            .query_row(
+                "SELECT token_ids FROM page_tokens WHERE book_id = ?1 AND page_id = ?2",
+                [book_id as i64, page_id as i64],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!("Page {} of book {} not found", page_id, book_id))?;
+        let token_ids = decode(&blob);
+        Ok(Page::new(self.hashes(&token_ids)?))
+    }
+
+    /// Every page of a book, in page order
+    fn book_pages(&mut self, book_id: u64) -> Result<Vec<(u64, Page)>> {
+        let pages: Vec<(u64, Vec<u32>)> = self
+            .corpus
+            .prepare("SELECT page_id, token_ids FROM page_tokens WHERE book_id = ?1 ORDER BY page_id")?
+            .query_map([book_id as i64], |row| Ok((row.get::<_, i64>(0)? as u64, decode(&row.get::<_, Vec<u8>>(1)?))))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut shingled = Vec::with_capacity(pages.len());
+        for batch in pages.chunks(PAGE_BATCH) {
+            let ids: Vec<u32> = batch.iter().flat_map(|(_, ids)| ids.iter().copied()).collect();
+            self.hashes(&ids)?;
+            for (page_id, token_ids) in batch {
+                shingled.push((*page_id, Page::new(self.hashes(token_ids)?)));
+            }
+        }
+        Ok(shingled)
+    }
+}
+
+fn decode(blob: &[u8]) -> Vec<u32> {
+    blob.chunks_exact(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+}
+
+/// MinHash/LSH index of page shingles
+pub struct ReuseIndex {
+    corpus_db_path: PathBuf,
+    index_path: PathBuf,
+}
+
+impl ReuseIndex {
+    pub fn new(corpus_db_path: PathBuf, index_path: PathBuf) -> Self {
+        Self { corpus_db_path, index_path }
+    }
+
+    fn open_corpus(&self) -> Result<Connection> {
+        Connection::open(&self.corpus_db_path)
+            .with_context(|| format!("Failed to open corpus.db at {:?}", self.corpus_db_path))
+    }
+
+    /// Open the index, emptied if it was built from another corpus or layout
+    fn open_index(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.index_path)?;
+        conn.execute_batch(
+            r#"
+            CREATE TABLE IF NOT EXISTS reuse_meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS indexed_books (
+                book_id INTEGER PRIMARY KEY,
+                pages INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS page_bands (
+                band INTEGER NOT NULL,
+                hash INTEGER NOT NULL,
+                book_id INTEGER NOT NULL,
+                page_id INTEGER NOT NULL,
+                PRIMARY KEY (band, hash, book_id, page_id)
+            ) WITHOUT ROWID;
+            "#,
+        )?;
+
+        let built_from = format!("{}/{}", INDEX_SCHEME, corpus_fingerprint(&self.corpus_db_path)?);
+        let stored: Option<String> = conn
+            .query_row("SELECT value FROM reuse_meta WHERE key = 'built_from'", [], |row| row.get(0))
+            .optional()?;
+        if stored.as_deref() != Some(built_from.as_str()) {
+            conn.execute_batch("BEGIN; DELETE FROM page_bands; DELETE FROM indexed_books;")?;
+            conn.execute("INSERT OR REPLACE INTO reuse_meta (key, value) VALUES ('built_from', ?1)", [&built_from])?;
+            conn.execute_batch("COMMIT;")?;
+        }
+        Ok(conn)
+    }
+
+    pub fn status(&self) -> Result<ReuseIndexStatus> {
+        let total_books: i64 = self.open_corpus()?.query_row("SELECT COUNT(*) FROM books", [], |row| row.get(0))?;
+        let (books_indexed, pages_indexed): (i64, i64) = self
+            .open_index()?
+            .query_row("SELECT COUNT(*), COALESCE(SUM(pages), 0) FROM indexed_books", [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(ReuseIndexStatus {
+            books_indexed: books_indexed as usize,
+            total_books: total_books as usize,
+            pages_indexed: pages_indexed as u64,
+        })
+    }
+
+    /// Index every book not indexed yet. Stops between books once `control` is cancelled.
+    pub fn build(&self, control: &SearchControl, on_progress: impl Fn(&ReuseBuildProgress)) -> Result<ReuseIndexStatus> {
+        let mut index = self.open_index()?;
+        let indexed: HashSet<u64> = index
+            .prepare("SELECT book_id FROM indexed_books")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id as u64))
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut reader = PageReader::new(self.open_corpus()?);
+        let book_ids: Vec<u64> = reader
+            .corpus
+            .prepare("SELECT id FROM books ORDER BY id")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id as u64))
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut progress = ReuseBuildProgress { books_done: indexed.len(), total_books: book_ids.len(), pages_indexed: 0 };
+        for book_id in book_ids.into_iter().filter(|id| !indexed.contains(id)) {
+            if control.is_cancelled() {
+                break;
+            }
+
+            let pages = reader.book_pages(book_id)?;
+            let tx = index.transaction()?;
+            let mut pages_indexed = 0;
+            {
+                let mut insert = tx.prepare("INSERT OR IGNORE INTO page_bands (band, hash, book_id, page_id) VALUES (?1, ?2, ?3, ?4)")?;
+                for (page_id, page) in pages.iter().filter(|(_, page)| page.shingles.len() >= MIN_SHINGLES) {
+                    for (band, hash) in page_bands(&page.shingles, WINDOW_SHINGLES) {
+                        insert.execute(rusqlite::params![band as i64, hash, book_id as i64, *page_id as i64])?;
+                    }
+                    pages_indexed += 1;
+                }
+            }
+            tx.execute("INSERT INTO indexed_books (book_id, pages) VALUES (?1, ?2)", [book_id as i64, pages_indexed])?;
+            tx.commit()?;
+
+            progress.books_done += 1;
+            progress.pages_indexed += pages_indexed as u64;
+            on_progress(&progress);
+        }
+
+        self.status()
+    }
+
+    /// Drop the index so the next build starts afresh
+    pub fn clear(&self) -> Result<()> {
+        self.open_index()?.execute_batch("DELETE FROM page_bands; DELETE FROM indexed_books;")?;
+        Ok(())
+    }
+
+    fn is_partial(&self) -> Result<bool> {
+        let status = self.status()?;
+        Ok(status.books_indexed < status.total_books)
+    }
+
+    /// Where else the text of a page appears
+    pub fn page_reuse(&self, request: &PageReuseRequest) -> Result<ReuseResults> {
+        let start = std::time::Instant::now();
+        let index = self.open_index()?;
+        let mut reader = PageReader::new(self.open_corpus()?);
+        let source = reader.page(request.book_id, request.page_id)?;
+
+        let mut results = ReuseResults {
+            book_id: request.book_id,
+            page_id: Some(request.page_id),
+            pages_checked: 1,
+            candidates: 0,
+            matches: Vec::new(),
+            partial_index: self.is_partial()?,
+            elapsed_ms: 0,
+        };
+        self.match_page(&index, &mut reader, request.book_id, request.page_id, &source, &request.options, &mut results)?;
+        finish(&mut results, &request.options, start);
+        Ok(results)
+    }
+
+    /// Pages of other books sharing text with any page of a book
+    pub fn book_reuse(&self, request: &BookReuseRequest) -> Result<ReuseResults> {
+        let start = std::time::Instant::now();
+        let index = self.open_index()?;
+        let mut reader = PageReader::new(self.open_corpus()?);
+        let pages = reader.book_pages(request.book_id)?;
+
+        let mut results = ReuseResults {
+            book_id: request.book_id,
+            page_id: None,
+            pages_checked: 0,
+            candidates: 0,
+            matches: Vec::new(),
+            partial_index: self.is_partial()?,
+            elapsed_ms: 0,
+        };
+        for (page_id, source) in pages.iter().filter(|(_, page)| page.shingles.len() >= MIN_SHINGLES) {
+            results.pages_checked += 1;
+            self.match_page(&index, &mut reader, request.book_id, *page_id, source, &request.options, &mut results)?;
+        }
+        finish(&mut results, &request.options, start);
+        Ok(results)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn match_page(
+        &self,
+        index: &Connection,
+        reader: &mut PageReader,
+        book_id: u64,
+        page_id: u64,
+        source: &Page,
+        options: &ReuseOptions,
+        results: &mut ReuseResults,
+    ) -> Result<()> {
+        if source.shingles.is_empty() {
+            return Ok(());
+        }
+        let wanted: Option<HashSet<u64>> = options.book_ids.as_ref().map(|ids| ids.iter().copied().collect());
+
+        let mut band_hits: HashMap<(u64, u64), usize> = HashMap::new();
+        let mut stmt = index.prepare_cached("SELECT book_id, page_id FROM page_bands WHERE band = ?1 AND hash = ?2")?;
+        for (band, hash) in page_bands(&source.shingles, QUERY_STRIDE) {
+            let rows = stmt.query_map(rusqlite::params![band as i64, hash], |row| {
+                Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+            })?;
+            for row in rows {
+                let (other_book, other_page) = row?;
+                if (other_book == book_id && (other_page == page_id || options.exclude_same_book))
+                    || wanted.as_ref().is_some_and(|wanted| !wanted.contains(&other_book))
+                {
+                    continue;
+                }
+                *band_hits.entry((other_book, other_page)).or_default() += 1;
+            }
+        }
+
+        let mut candidates: Vec<((u64, u64), usize)> = band_hits.into_iter().collect();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        candidates.truncate(MAX_CANDIDATES);
+        results.candidates += candidates.len();
+
+        let source_set = shingle_set(&source.shingles);
+        for ((other_book, other_page), band_hits) in candidates {
+            let target = reader.page(other_book, other_page)?;
+            let target_set = shingle_set(&target.shingles);
+            let similarity = window_containment(&source.shingles, &target_set).max(window_containment(&target.shingles, &source_set));
+            if similarity < options.min_similarity {
+                continue;
+            }
+            let shared: HashSet<u64> = source_set.intersection(&target_set).copied().collect();
+            let Some((source_span, target_span)) = align_pages(source, &target, &shared) else {
+                continue;
+            };
+            results.matches.push(ReuseMatch {
+                source: ReusePassage { book_id, page_id, start: source_span.0, end: source_span.1 },
+                target: ReusePassage { book_id: other_book, page_id: other_page, start: target_span.0, end: target_span.1 },
+                similarity,
+                shared_shingles: shared.len(),
+                band_hits,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn finish(results: &mut ReuseResults, options: &ReuseOptions, start: std::time::Instant) {
+    results.matches.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| (a.target.book_id, a.target.page_id).cmp(&(b.target.book_id, b.target.page_id)))
+    });
+    results.matches.truncate(options.limit);
+    results.elapsed_ms = start.elapsed().as_millis() as u64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_corpus::{token_blob, TestCorpus};
+
+    #[test]
+    fn test_page_reuse() {
+        let corpus = TestCorpus::new("reuse");
+        corpus.add_books(1..=5);
+        for id in 1..=400 {
+            corpus.conn.execute("INSERT INTO token_definitions VALUES (?1, ?2, NULL, NULL)", rusqlite::params![id, format!("w{}", id)]).unwrap();
+        }
+        let passage: Vec<u32> = (1..=30).collect();
+        let mut quoted = vec![41, 42, 43];
+        quoted.extend(&passage);
+        corpus.add_page(1, 1, &passage);
+        corpus.add_page(2, 7, &quoted);
+        corpus.add_page(3, 1, &(31..=60).collect::<Vec<_>>());
+        // A short quotation within two long pages that otherwise differ
+        let quotation: Vec<u32> = (61..=85).collect();
+        let long_page = |before: std::ops::Range<u32>, after: std::ops::Range<u32>| -> Vec<u32> {
+            before.chain(quotation.iter().copied()).chain(after).collect()
+        };
+        corpus.add_page(4, 1, &long_page(100..180, 180..250));
+        corpus.add_page(5, 3, &long_page(250..280, 280..400));
+
+        let index = ReuseIndex::new(corpus.path(), corpus.dir.join("reuse_index.db"));
+        let control = SearchControl::new();
+        control.cancel();
+        assert_eq!(index.build(&control, |_| {}).unwrap().books_indexed, 0);
+        let request = PageReuseRequest { book_id: 1, page_id: 1, options: ReuseOptions::default() };
+        assert!(index.page_reuse(&request).unwrap().partial_index);
+
+        let status = index.build(&SearchControl::new(), |_| {}).unwrap();
+        assert_eq!((status.books_indexed, status.pages_indexed), (5, 5));
+
+        let results = index.page_reuse(&request).unwrap();
+        assert!(!results.partial_index);
+        assert_eq!(results.matches.len(), 1);
+        let reuse = &results.matches[0];
+        assert_eq!((reuse.target.book_id, reuse.target.page_id), (2, 7));
+        assert_eq!((reuse.source.start, reuse.source.end), (0, 30));
+        assert_eq!((reuse.target.start, reuse.target.end), (3, 33));
+
+        let results = index.page_reuse(&PageReuseRequest { book_id: 4, page_id: 1, options: ReuseOptions::default() }).unwrap();
+        assert_eq!(results.matches.len(), 1);
+        let reuse = &results.matches[0];
+        assert_eq!((reuse.target.book_id, reuse.target.page_id), (5, 3));
+        assert_eq!(reuse.similarity, 1.0);
+        assert_eq!((reuse.source.start, reuse.source.end), (80, 105));
+        assert_eq!((reuse.target.start, reuse.target.end), (30, 55));
+
+        // A changed corpus empties the index
+        corpus.add_books([6]);
+        corpus.conn.execute("INSERT INTO page_tokens VALUES (6, 1, ?1)", [token_blob(&(1..=400).cycle().take(50_000).collect::<Vec<_>>())]).unwrap();
+        assert_eq!(index.status().unwrap().books_indexed, 0);
+    }
+}
@@ -1,17 +1,28 @@
 //! Search functionality using Tantivy
 
+use crate::budget::{Deadline, SearchBudget, TruncationReason};
+use crate::cache::TokenCache;
+use crate::control::{SearchControl, SearchProgress, PROGRESS_INTERVAL};
+use crate::cursor::{SortKey, SortKeyTopDocs};
+use crate::names::NameSearchForm;
+use crate::persons::NameMentions;
+use crate::result_cache::{ResultCache, ResultCacheStats};
+use crate::similar::{align, similarity, AlignedToken, SimilarPassage, SimilarPassages, SimilarPassagesRequest};
+use crate::snippets::{build_snippets, Snippet, SnippetOptions};
+use crate::tokens::{PageKey, Token};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
 use std::path::Path;
-use tantivy::collector::{Count, TopDocs};
-use tantivy::postings::Postings;
-use tantivy::query::{BooleanQuery, Occur, PhraseQuery, Query, QueryParser, TermQuery, RegexQuery};
+use tantivy::collector::{Collector, Count, TopDocs};
+use tantivy::postings::{Postings, SegmentPostings, TermInfo};
+use tantivy::query::{BooleanQuery, Occur, PhraseQuery, Query, QueryParser, RangeQuery, TermQuery, TermSetQuery, RegexQuery};
 use tantivy::schema::*;
-use tantivy::{DocAddress, DocSet, Index, ReloadPolicy, SegmentReader, Term};
+use tantivy::{DocAddress, DocSet, Index, Order, ReloadPolicy, Searcher, SegmentReader, Term};
 
 /// Normalize Arabic text for search: removes diacritics, normalizes hamza/alif variants
-fn normalize_arabic(text: &str) -> String {
+pub(crate) fn normalize_arabic(text: &str) -> String {
     text.chars()
         .filter_map(|c| {
             match c {
@@ -226,6 +237,65 @@ pub struct SearchResult {
     pub body: String,
     pub score: f32,
     pub matched_token_indices: Vec<u32>,
+    /// Number of query occurrences on the page (matched_token_indices is only a preview)
+    #[serde(default)]
+    pub match_count: usize,
+    /// Text windows around the matches (snippet mode only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snippets: Vec<Snippet>,
+    /// Name patterns found on the page (name search only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matched_patterns: Vec<PatternMatch>,
+}
+
+/// A name pattern found on a result page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternMatch {
+    /// Index of the name form the pattern belongs to
+    pub form: usize,
+    pub pattern: String,
+    /// Token position where each occurrence starts
+    pub starts: Vec<u32>,
+    /// Words in the pattern
+    pub len: usize,
+}
+
+/// Pages where a name occurs
+#[derive(Debug, Clone)]
+pub struct NameOccurrences {
+    pub total_pages: usize,
+    /// Page and the token positions where the name starts on it, in result order
+    pub pages: Vec<(SortKey, Vec<u32>)>,
+}
+
+/// Result pages a name pattern matches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternHits {
+    pub form: usize,
+    pub pattern: String,
+    pub pages: usize,
+}
+
+/// Per-request options for how search results are returned
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Snippet mode: return text windows around the matches instead of the page body
+    pub snippets: Option<SnippetOptions>,
+    /// Keep the full body in snippet mode
+    pub include_body: bool,
+    /// Resume after the last hit of a previous page (`next_cursor` of its results).
+    /// Proximity and wildcard phrase searches stop verifying candidates once a resumed
+    /// page is full, so their total_hits then only counts hits up to that page.
+    pub cursor: Option<String>,
+    /// Limits on time and work; results cut short by them are flagged `truncated`
+    pub budget: SearchBudget,
+    /// Count the result pages of each name pattern (name search, first page only).
+    /// One count per pattern, so it runs within the budget's deadline.
+    pub pattern_hits: bool,
+    /// Cancellation and progress reporting, set by the caller
+    #[serde(skip)]
+    pub control: SearchControl,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -235,6 +305,18 @@ pub struct SearchResults {
     pub total_hits: usize,
     pub results: Vec<SearchResult>,
     pub elapsed_ms: u64,
+    /// Cursor for the next page, if there are more results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// A budget ran out and these results are partial
+    #[serde(default)]
+    pub truncated: bool,
+    /// Which budget ran out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated_reason: Option<TruncationReason>,
+    /// Pages matched by each name pattern, most first (name search with `pattern_hits` set, first page only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pattern_hits: Vec<PatternHits>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -246,9 +328,374 @@ pub struct PageWithMatches {
     pub matched_token_indices: Vec<u32>,
 }
 
+/// A part (volume) of a book and the pages it spans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookPart {
+    pub part_index: u64,
+    pub part_label: String,
+    pub first_page_id: u64,
+    pub last_page_id: u64,
+    pub page_count: usize,
+    /// Printed page numbers of the first and last page
+    pub first_page_number: String,
+    pub last_page_number: String,
+}
+
+/// Direction of sequential reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageDirection {
+    Next,
+    Previous,
+}
+
+/// Printed page numbers match as numbers when both are numeric ("045" is page 45)
+fn same_page_number(a: &str, b: &str) -> bool {
+    let (a, b) = (a.trim(), b.trim());
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Part labels match ignoring case, or as numbers ("03" is part 3)
+pub(crate) fn same_part_label(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim()) || same_page_number(a, b)
+}
+
+/// Spellings of a page number that same_page_number accepts, as index terms:
+/// a number also without and with leading zeros, up to four digits
+fn page_number_spellings(page_number: &str) -> Vec<String> {
+    let page_number = page_number.trim();
+    let mut spellings = vec![page_number.to_string()];
+    if let Ok(number) = page_number.parse::<u64>() {
+        spellings.extend((1..=4).map(|width| format!("{:0width$}", number, width = width)));
+    }
+    spellings.sort();
+    spellings.dedup();
+    spellings
+}
+
+/// Read the positions of a postings list in one document.
+/// Returns None if the term does not occur in the document.
+fn doc_positions(mut postings: SegmentPostings, doc_id: u32) -> Option<Vec<u32>> {
+    // Check current position before seeking - a fresh postings iterator
+    // points to its first document, which may already be past our target.
+    // Calling seek() when current_doc > target triggers a Tantivy assertion.
+    let current_doc = postings.doc();
+    if current_doc == tantivy::TERMINATED || current_doc > doc_id {
+        return None;
+    }
+    if current_doc != doc_id && postings.seek(doc_id) != doc_id {
+        return None;
+    }
+
+    let mut pos_buffer: Vec<u32> = Vec::new();
+    postings.positions(&mut pos_buffer);
+    Some(pos_buffer)
+}
+
+/// Find start positions where term_positions[0][i] + k is in term_positions[k] for every k
+fn consecutive_starts(term_positions: &[Vec<u32>]) -> Vec<u32> {
+    let Some(first_positions) = term_positions.first() else {
+        return Vec::new();
+    };
+
+    let mut starts: Vec<u32> = first_positions
+        .iter()
+        .copied()
+        .filter(|&start_pos| {
+            term_positions
+                .iter()
+                .enumerate()
+                .skip(1)
+                .all(|(offset, positions)| positions.binary_search(&(start_pos + offset as u32)).is_ok())
+        })
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+    starts
+}
+
+/// Expand phrase start positions into every token position they cover
+fn phrase_positions_from_starts(starts: &[u32], phrase_len: usize) -> Vec<u32> {
+    let mut positions: Vec<u32> = starts
+        .iter()
+        .flat_map(|&start| (0..phrase_len as u32).map(move |offset| start + offset))
+        .collect();
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
+
+/// Token positions covered by name pattern occurrences, plus the number of distinct
+/// places a pattern starts (overlapping patterns count once)
+fn occurrence_positions(occurrences: &[PatternMatch]) -> (Vec<u32>, usize) {
+    let mut positions: BTreeSet<u32> = BTreeSet::new();
+    let mut starts: HashSet<u32> = HashSet::new();
+    for occurrence in occurrences {
+        positions.extend(phrase_positions_from_starts(&occurrence.starts, occurrence.len));
+        starts.extend(&occurrence.starts);
+    }
+    (positions.into_iter().collect(), starts.len())
+}
+
+/// Description of any search type, used to re-run it or recompute its match positions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MatchQuery {
+    Simple { query: String, mode: SearchMode },
+    Combined {
+        and_terms: Vec<SearchTerm>,
+        #[serde(default)]
+        or_terms: Vec<SearchTerm>,
+    },
+    Proximity { term1: SearchTerm, term2: SearchTerm, distance: usize },
+    Name { forms: Vec<NameSearchForm> },
+    Wildcard { query: String },
+}
+
+/// A page of match positions for one document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchPositions {
+    pub match_count: usize,
+    pub total_positions: usize,
+    pub offset: usize,
+    pub positions: Vec<u32>,
+}
+
+/// Occurrences of a search term on one page
+#[derive(Debug, Clone)]
+pub struct PageOccurrences {
+    pub id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+    /// Token index of the first word of each occurrence
+    pub starts: Vec<u32>,
+}
+
+/// Pages containing a search term, from SearchEngine::term_occurrences
+#[derive(Debug, Clone)]
+pub struct TermOccurrences {
+    /// Number of pages containing the term
+    pub total_pages: usize,
+    /// Words per occurrence (more than one for phrases)
+    pub term_len: usize,
+    /// The first pages in death_ah order
+    pub pages: Vec<PageOccurrences>,
+}
+
+/// Page whose term matches an explain report should list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplainPage {
+    pub id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplainRequest {
+    pub query: MatchQuery,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    #[serde(default)]
+    pub page: Option<ExplainPage>,
+}
+
+/// An index term and the number of pages containing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermFrequency {
+    pub term: String,
+    pub doc_freq: u64,
+}
+
+/// How one search term was interpreted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermExplanation {
+    /// Place in the query: "query", "and", "or", "term1", "term2", "form N" or "word N"
+    pub role: String,
+    pub query: String,
+    pub mode: SearchMode,
+    /// Index field searched
+    pub field: String,
+    /// The query after normalize_arabic / normalize_root_query
+    pub normalized: String,
+    /// Index terms it is looked up as
+    pub terms: Vec<TermFrequency>,
+    /// Number of index terms a wildcard expanded to; `terms` lists the first EXPLAIN_MAX_EXPANSIONS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expanded_terms: Option<usize>,
+}
+
+/// Filters as the index query applies them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExplainedFilters {
+    pub book_ids: Option<Vec<u64>>,
+    /// Filters set in the request that the index query does not apply
+    /// (callers resolve these to book_ids)
+    pub ignored: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub micros: u64,
+}
+
+/// Positions of one search term's index terms on a page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermPositions {
+    pub role: String,
+    pub term: String,
+    pub positions: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageExplanation {
+    pub id: u64,
+    pub part_index: u64,
+    pub page_id: u64,
+    /// Whether the page is in the index
+    pub found: bool,
+    /// Whether the page matches the Tantivy query (before proximity/adjacency checks)
+    pub matches_query: bool,
+    pub terms: Vec<TermPositions>,
+    /// Match positions as search results report them
+    pub matches: MatchPositions,
+}
+
+/// Debug report on how a search is interpreted and run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryExplanation {
+    pub terms: Vec<TermExplanation>,
+    /// Debug form of the Tantivy query, filters included
+    pub query_tree: String,
+    pub filters: ExplainedFilters,
+    /// Pages matching the Tantivy query; proximity and multi-word wildcard
+    /// searches verify these further
+    pub candidate_hits: usize,
+    /// Phases of this report: it runs the query its own way (counting the
+    /// candidates instead of collecting and verifying a page of them), so these
+    /// time the explain, not the search, which reports its own `elapsed_ms`
+    pub explain_timings: Vec<PhaseTiming>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageExplanation>,
+}
+
+/// Result cache key of a search: its kind and serialized inputs.
+/// None if they don't serialize, in which case the search isn't cached.
+fn result_cache_key(kind: &str, inputs: &impl Serialize) -> Option<String> {
+    serde_json::to_string(inputs).ok().map(|json| format!("{}:{}", kind, json))
+}
+
+/// The committed index state a searcher sees: its segments and their deletes.
+/// Cached doc addresses are only valid for the same one.
+fn index_generation(searcher: &Searcher) -> String {
+    searcher
+        .generation()
+        .segments()
+        .iter()
+        .map(|(segment_id, delete_opstamp)| format!("{}.{}", segment_id.uuid_string(), delete_opstamp.unwrap_or(0)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Next-page cursor for searches that verify an overfetched candidate list
+fn filtered_next_cursor(
+    more_hits: bool,
+    candidates_exhausted: bool,
+    last_result: Option<SortKey>,
+    last_candidate: Option<SortKey>,
+) -> Option<String> {
+    let key = if more_hits {
+        last_result
+    } else if !candidates_exhausted {
+        // Every fetched candidate was checked, but more may follow the last one
+        last_candidate
+    } else {
+        None
+    };
+    key.map(SortKey::to_cursor)
+}
+
+/// Run a collector that stops collecting once the search is cancelled or its deadline passes
+fn search_with_control<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    collector: C,
+    control: &SearchControl,
+    deadline: &Deadline,
+) -> Result<C::Fruit> {
+    let fruit = searcher.search(query, &control.collector(deadline.collector(collector)));
+    control.check()?;
+    Ok(fruit?)
+}
+
+/// Truncation reason and next cursor of a search that verifies an overfetched candidate list.
+/// `last_checked` is the last candidate that was fully verified.
+fn verified_page_end(
+    more_hits: bool,
+    candidates_exhausted: bool,
+    candidates_capped: bool,
+    collection_cut: bool,
+    verification_cut: bool,
+    last_result: Option<SortKey>,
+    last_checked: Option<SortKey>,
+) -> (Option<TruncationReason>, Option<String>) {
+    if collection_cut {
+        // Candidates collected before the deadline aren't necessarily the first in
+        // sort order, so there is no safe place to resume from
+        return (Some(TruncationReason::Deadline), None);
+    }
+
+    let truncated = if verification_cut {
+        Some(TruncationReason::Deadline)
+    } else if candidates_capped && !candidates_exhausted && !more_hits {
+        Some(TruncationReason::Candidates)
+    } else {
+        None
+    };
+    let next_cursor = filtered_next_cursor(more_hits, candidates_exhausted && !verification_cut, last_result, last_checked);
+    (truncated, next_cursor)
+}
+
+/// One page of docs from SearchEngine::collect_page
+struct CollectedPage {
+    total_hits: usize,
+    docs: Vec<(SortKey, DocAddress)>,
+    /// Cursor for the next page (None on the last page)
+    next_cursor: Option<String>,
+    truncated: Option<TruncationReason>,
+}
+
+/// Queries kept in the result cache
+const RESULT_CACHE_CAPACITY: usize = 64;
+
+/// Pages of docs collected for a first page, so that the following ones come from the result cache
+const RESULT_CACHE_PREFETCH_PAGES: usize = 4;
+
+/// Words a wildcard expands to when the budget sets no limit; verifying a page
+/// reads the postings of every one of them
+const WILDCARD_MAX_EXPANSION: usize = 100_000;
+
+/// Wildcard expansions listed with their document frequencies in an explain report
+const EXPLAIN_MAX_EXPANSIONS: usize = 100;
+
+/// Longest selection aligned by find_similar_passages
+const SIMILAR_MAX_SPAN: usize = 200;
+
+/// Lemma assigned to tokens without a definition (see TokenCache)
+pub(crate) const UNKNOWN_LEMMA: &str = "unknown";
+
+/// A token's lemma for alignment; unknown lemmas match nothing
+fn aligned_lemma(token: &Token) -> AlignedToken<&str> {
+    AlignedToken(Some(token.lemma.as_str()).filter(|lemma| *lemma != UNKNOWN_LEMMA))
+}
+
 pub struct SearchEngine {
     index: Index,
     schema: Schema,
+    result_cache: ResultCache,
 }
 
 impl SearchEngine {
@@ -256,7 +703,17 @@ impl SearchEngine {
         let index = Index::open_in_dir(index_path)?;
         index.tokenizers().register("whitespace", tantivy::tokenizer::WhitespaceTokenizer::default());
         let schema = index.schema();
-        Ok(Self { index, schema })
+        Ok(Self { index, schema, result_cache: ResultCache::new(RESULT_CACHE_CAPACITY) })
+    }
+
+    /// Hit/miss counters of the search result cache
+    pub fn result_cache_stats(&self) -> ResultCacheStats {
+        self.result_cache.stats()
+    }
+
+    /// Drop all cached hit lists, e.g. when the index is replaced
+    pub fn clear_result_cache(&self) {
+        self.result_cache.clear();
     }
 
     pub fn search(
@@ -266,6 +723,7 @@ impl SearchEngine {
         filters: &SearchFilters,
         limit: usize,
         offset: usize,
+        options: &SearchOptions,
     ) -> Result<SearchResults> {
         let start = std::time::Instant::now();
 
@@ -296,133 +754,47 @@ impl SearchEngine {
             query_terms.insert(token_stream.token().text.clone());
         }
 
-        let text_query: Box<dyn Query> = if query_terms.len() > 1 {
-            // Multi-word query: use PhraseQuery for all modes
-            let terms: Vec<Term> = normalized_query
-                .split_whitespace()
-                .map(|word| Term::from_field_text(search_field, word))
-                .collect();
-            Box::new(PhraseQuery::new(terms))
-        } else {
-            let query_parser = QueryParser::for_index(&self.index, vec![search_field]);
-            query_parser.parse_query(&normalized_query)?
-        };
-
-        let final_query: Box<dyn Query> = if let Some(ref book_ids) = filters.book_ids {
-            if book_ids.is_empty() {
-                text_query
-            } else {
-                let id_field = self.schema.get_field("text_id").unwrap();
-                let book_id_queries: Vec<(Occur, Box<dyn Query>)> = book_ids
-                    .iter()
-                    .map(|&id| {
-                        let term = Term::from_field_u64(id_field, id);
-                        let term_query: Box<dyn Query> =
-                            Box::new(TermQuery::new(term, IndexRecordOption::Basic));
-                        (Occur::Should, term_query)
-                    })
-                    .collect();
-                let book_ids_query = BooleanQuery::new(book_id_queries);
-                let combined = BooleanQuery::new(vec![
-                    (Occur::Must, text_query),
-                    (Occur::Must, Box::new(book_ids_query)),
-                ]);
-                Box::new(combined)
-            }
-        } else {
-            text_query
-        };
+        let text_query = self.build_simple_query(&normalized_query, search_field, query_terms.len() > 1)?;
 
-        let id_field = self.schema.get_field("text_id").unwrap();
-        let part_index_field = self.schema.get_field("part_index").unwrap();
-        let page_id_field = self.schema.get_field("page_id").unwrap();
-        let author_id_field = self.schema.get_field("author_id").unwrap();
-        let genre_id_field = self.schema.get_field("genre_id").unwrap();
-        let death_ah_field = self.schema.get_field("death_ah").unwrap();
-        let century_ah_field = self.schema.get_field("century_ah").unwrap();
-        let part_label_field = self.schema.get_field("part_label").unwrap();
-        let page_number_field = self.schema.get_field("page_number").unwrap();
-        let body_field = self.schema.get_field("body").unwrap();
+        let final_query = self.with_book_filter(text_query, filters);
 
         // Sort by death_ah at the index level to ensure proper sorting
         // across ALL matching documents, not just the top N by relevance score
-        let (total_hits, top_docs) =
-            searcher.search(&*final_query, &(Count, TopDocs::with_limit(limit + offset).order_by_u64_field("death_ah", tantivy::Order::Asc)))?;
+        let cache_key = result_cache_key("search", &(mode, &normalized_query, filters));
+        let CollectedPage { total_hits, docs: page_docs, next_cursor, truncated } =
+            self.collect_page(&searcher, &*final_query, cache_key, limit, offset, options)?;
 
-        // Extract all results (already sorted by death_ah from the collector)
+        // Extract results for the requested page (already sorted by death_ah from the collector)
         let mut results = Vec::new();
-        for (_sort_value, doc_address) in top_docs.into_iter() {
-            let doc: TantivyDocument = searcher.doc(doc_address)?;
-
-            let body = doc
-                .get_first(body_field)
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
+        for (_sort_key, doc_address) in page_docs {
+            options.control.check()?;
+            let segment_reader = searcher.segment_reader(doc_address.segment_ord);
 
-            let matched_token_indices = if !query_terms.is_empty() {
+            let (mut matched_token_indices, match_count) = if query_terms.is_empty() {
+                (Vec::new(), 0)
+            } else if query_terms.len() > 1 {
                 // For phrase searches (multiple words), only highlight consecutive matches
-                if query_terms.len() > 1 {
-                    let phrase_terms: Vec<String> = normalized_query.split_whitespace().map(|s| s.to_string()).collect();
-                    self.get_phrase_positions_limited(
-                        searcher.segment_reader(doc_address.segment_ord),
-                        doc_address.doc_id,
-                        search_field,
-                        &phrase_terms,
-                        5,
-                    )
-                } else {
-                    self.get_matched_positions_limited(
-                        searcher.segment_reader(doc_address.segment_ord),
-                        doc_address.doc_id,
-                        search_field,
-                        &query_terms,
-                        5,
-                    )
-                }
+                let phrase_terms: Vec<String> = normalized_query.split_whitespace().map(|s| s.to_string()).collect();
+                let starts = self.get_phrase_starts(segment_reader, doc_address.doc_id, search_field, &phrase_terms);
+                (phrase_positions_from_starts(&starts, phrase_terms.len()), starts.len())
             } else {
-                Vec::new()
-            };
-
-            let result = SearchResult {
-                id: doc
-                    .get_first(id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                part_index: doc
-                    .get_first(part_index_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                page_id: doc
-                    .get_first(page_id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
-                genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
-                death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
-                century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
-                part_label: doc
-                    .get_first(part_label_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                page_number: doc
-                    .get_first(page_number_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                body,
-                score: 0.0, // Relevance score not used when sorting by death_ah
-                matched_token_indices,
+                let positions = self.get_matched_positions_internal(
+                    segment_reader,
+                    doc_address.doc_id,
+                    search_field,
+                    &query_terms,
+                    None,
+                    usize::MAX,
+                );
+                let count = positions.len();
+                (positions, count)
             };
+            matched_token_indices.truncate(5);
 
-            results.push(result);
+            // Relevance score not used when sorting by death_ah
+            results.push(self.extract_result(&searcher, doc_address, 0.0, matched_token_indices, match_count, options)?);
         }
 
-        // Results are already sorted by death_ah from TopDocsByField collector
-        // Apply offset and limit
-        let results: Vec<SearchResult> = results.into_iter().skip(offset).take(limit).collect();
-
         let elapsed_ms = start.elapsed().as_millis() as u64;
 
         Ok(SearchResults {
@@ -431,9 +803,72 @@ impl SearchEngine {
             total_hits,
             results,
             elapsed_ms,
+            next_cursor,
+            truncated: truncated.is_some(),
+            truncated_reason: truncated,
+            pattern_hits: Vec::new(),
         })
     }
 
+    /// Collect one page of docs in death_ah order, starting after `options.cursor` if given.
+    /// Pages within the leading hits of a recent query come from the result cache;
+    /// `cache_key` (from result_cache_key) identifies the query, None bypasses the cache.
+    fn collect_page(
+        &self,
+        searcher: &Searcher,
+        query: &dyn Query,
+        cache_key: Option<String>,
+        limit: usize,
+        offset: usize,
+        options: &SearchOptions,
+    ) -> Result<CollectedPage> {
+        let after = options.cursor.as_deref().map(SortKey::from_cursor).transpose()?;
+
+        // The sort order is always death_ah, so the inputs and the index state identify the hit list
+        let cache_key = cache_key.map(|key| format!("{}@{}", key, index_generation(searcher)));
+        if let Some(cached) = cache_key.as_deref().and_then(|key| self.result_cache.page(key, after, offset, limit)) {
+            let next_cursor = if cached.has_more {
+                cached.docs.last().map(|(sort_key, _)| sort_key.to_cursor())
+            } else {
+                None
+            };
+            return Ok(CollectedPage { total_hits: cached.total_hits, docs: cached.docs, next_cursor, truncated: None });
+        }
+
+        // One extra doc tells us whether another page exists
+        let wanted = offset + limit + 1;
+        let collect_limit = match (&cache_key, after) {
+            (Some(_), None) => offset + limit * RESULT_CACHE_PREFETCH_PAGES + 1,
+            _ => wanted,
+        };
+        let deadline = options.budget.start();
+        let (total_hits, top_docs) = search_with_control(
+            searcher,
+            query,
+            (Count, SortKeyTopDocs::new(collect_limit, after)),
+            &options.control,
+            &deadline,
+        )?;
+        let has_more = top_docs.len() > offset + limit;
+
+        // Docs collected before the deadline aren't necessarily the first in sort order,
+        // so a cursor taken from them could skip hits
+        let truncated = deadline.was_hit().then_some(TruncationReason::Deadline);
+        let page_docs: Vec<(SortKey, DocAddress)> = top_docs.iter().skip(offset).take(limit).copied().collect();
+        let next_cursor = if has_more && truncated.is_none() {
+            page_docs.last().map(|(sort_key, _)| sort_key.to_cursor())
+        } else {
+            None
+        };
+
+        // Only a complete prefix of the hit list can serve later pages
+        if let Some(cache_key) = cache_key.filter(|_| after.is_none() && truncated.is_none()) {
+            self.result_cache.insert(cache_key, total_hits, top_docs);
+        }
+
+        Ok(CollectedPage { total_hits, docs: page_docs, next_cursor, truncated })
+    }
+
     /// Get token positions where query terms appear (limited to first N for performance)
     /// For single terms, returns all positions. For phrase queries (ordered terms),
     /// only returns positions where terms appear consecutively.
@@ -465,69 +900,39 @@ impl SearchEngine {
             return self.get_matched_positions_internal(segment_reader, doc_id, field, &terms_set, None, max_positions);
         }
 
+        let starts = self.get_phrase_starts(segment_reader, doc_id, field, phrase_terms);
+        let mut matched_positions = phrase_positions_from_starts(&starts, phrase_terms.len());
+        matched_positions.truncate(max_positions);
+        matched_positions
+    }
+
+    /// Get the start position of every consecutive occurrence of a phrase in a document.
+    /// A single-term "phrase" returns every position of that term.
+    fn get_phrase_starts(
+        &self,
+        segment_reader: &SegmentReader,
+        doc_id: u32,
+        field: Field,
+        phrase_terms: &[String],
+    ) -> Vec<u32> {
         let Ok(inverted_index) = segment_reader.inverted_index(field) else {
             return Vec::new();
         };
 
         // Collect positions for each term in the phrase
-        let mut term_positions: Vec<Vec<u32>> = Vec::new();
+        let mut term_positions: Vec<Vec<u32>> = Vec::with_capacity(phrase_terms.len());
         for term_str in phrase_terms {
             let term = Term::from_field_text(field, term_str);
-            let Ok(Some(mut postings)) = inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) else {
+            let Ok(Some(postings)) = inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) else {
                 return Vec::new(); // All terms must be present
             };
-
-            // Check current position before seeking - a fresh postings iterator
-            // points to its first document, which may already be past our target.
-            // Calling seek() when current_doc > target triggers a Tantivy assertion.
-            let current_doc = postings.doc();
-            if current_doc == tantivy::TERMINATED || current_doc > doc_id {
+            let Some(positions) = doc_positions(postings, doc_id) else {
                 return Vec::new(); // Term not in this doc
-            }
-
-            let found_doc = if current_doc == doc_id {
-                doc_id
-            } else {
-                postings.seek(doc_id)
             };
-
-            if found_doc != doc_id {
-                return Vec::new(); // Term not in this doc
-            }
-
-            let mut pos_buffer: Vec<u32> = Vec::new();
-            postings.positions(&mut pos_buffer);
-            term_positions.push(pos_buffer);
-        }
-
-        // Find consecutive sequences: position of term[0], term[0]+1 for term[1], etc.
-        let mut matched_positions: Vec<u32> = Vec::new();
-        let first_positions = &term_positions[0];
-
-        for &start_pos in first_positions {
-            let mut is_phrase_match = true;
-            for (offset, positions) in term_positions.iter().enumerate().skip(1) {
-                let expected_pos = start_pos + offset as u32;
-                if !positions.contains(&expected_pos) {
-                    is_phrase_match = false;
-                    break;
-                }
-            }
-            if is_phrase_match {
-                // Add all positions in this phrase match
-                for offset in 0..phrase_terms.len() {
-                    matched_positions.push(start_pos + offset as u32);
-                }
-            }
-            if matched_positions.len() >= max_positions {
-                break;
-            }
+            term_positions.push(positions);
         }
 
-        matched_positions.sort_unstable();
-        matched_positions.dedup();
-        matched_positions.truncate(max_positions);
-        matched_positions
+        consecutive_starts(&term_positions)
     }
 
     /// Internal implementation for getting term positions
@@ -548,23 +953,12 @@ impl SearchEngine {
 
         for term_str in query_terms {
             let term = Term::from_field_text(field, term_str);
-            let Ok(Some(mut postings)) = inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) else {
+            let Ok(Some(postings)) = inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) else {
                 continue;
             };
 
-            let current_doc = postings.doc();
-            if current_doc == tantivy::TERMINATED || current_doc > doc_id {
-                continue;
-            }
-
-            if current_doc == doc_id {
-                let mut pos_buffer: Vec<u32> = Vec::new();
-                postings.positions(&mut pos_buffer);
-                positions.extend(pos_buffer);
-            } else if postings.seek(doc_id) == doc_id {
-                let mut pos_buffer: Vec<u32> = Vec::new();
-                postings.positions(&mut pos_buffer);
-                positions.extend(pos_buffer);
+            if let Some(term_positions) = doc_positions(postings, doc_id) {
+                positions.extend(term_positions);
             }
 
             if positions.len() >= max_positions {
@@ -649,6 +1043,22 @@ impl SearchEngine {
             .try_into()?;
 
         let searcher = reader.searcher();
+
+        if let Some((score, doc_address)) = self.find_page_doc(&searcher, id, part_index, page_id)? {
+            Ok(Some(self.extract_result(&searcher, doc_address, score, Vec::new(), 0, &SearchOptions::default())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Locate the document for a single page by (text_id, part_index, page_id)
+    fn find_page_doc(
+        &self,
+        searcher: &Searcher,
+        id: u64,
+        part_index: u64,
+        page_id: u64,
+    ) -> Result<Option<(f32, DocAddress)>> {
         let id_field = self.schema.get_field("text_id").unwrap();
         let part_index_field = self.schema.get_field("part_index").unwrap();
         let page_id_field = self.schema.get_field("page_id").unwrap();
@@ -669,52 +1079,229 @@ impl SearchEngine {
         ]);
 
         let top_docs = searcher.search(&query, &TopDocs::with_limit(1))?;
+        Ok(top_docs.into_iter().next())
+    }
 
-        if let Some((score, doc_address)) = top_docs.into_iter().next() {
-            let doc: TantivyDocument = searcher.doc(doc_address)?;
-
-            let author_id_field = self.schema.get_field("author_id").unwrap();
-            let genre_id_field = self.schema.get_field("genre_id").unwrap();
-            let death_ah_field = self.schema.get_field("death_ah").unwrap();
-            let century_ah_field = self.schema.get_field("century_ah").unwrap();
-            let part_label_field = self.schema.get_field("part_label").unwrap();
-            let page_number_field = self.schema.get_field("page_number").unwrap();
-            let body_field = self.schema.get_field("body").unwrap();
-
-            let result = SearchResult {
-                id,
-                part_index,
-                page_id,
-                author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
-                genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
-                death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
-                century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
-                part_label: doc
-                    .get_first(part_label_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                page_number: doc
-                    .get_first(page_number_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                body: doc
-                    .get_first(body_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                score,
-                matched_token_indices: Vec::new(),
-            };
+    /// Every page of a book in reading order (part_index, then page_id), from the fast fields
+    fn book_pages(&self, searcher: &Searcher, id: u64) -> Result<Vec<(SortKey, DocAddress)>> {
+        let id_field = self.schema.get_field("text_id").unwrap();
+        let query = TermQuery::new(Term::from_field_u64(id_field, id), IndexRecordOption::Basic);
+        Ok(searcher.search(&query, &SortKeyTopDocs::new(usize::MAX, None))?)
+    }
 
-            Ok(Some(result))
-        } else {
-            Ok(None)
-        }
+    /// A stored text field of a page
+    fn stored_text(&self, searcher: &Searcher, doc_address: DocAddress, field: &str) -> Result<String> {
+        let doc: TantivyDocument = searcher.doc(doc_address)?;
+        let field = self.schema.get_field(field).unwrap();
+        Ok(doc.get_first(field).and_then(|v| v.as_str()).unwrap_or("").to_string())
     }
 
-    pub fn doc_count(&self) -> Result<u64> {
+    /// Parts of a book with their labels and page ranges, in order
+    pub fn book_parts(&self, id: u64) -> Result<Vec<BookPart>> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let pages = self.book_pages(&searcher, id)?;
+        let mut parts = Vec::new();
+        for part_pages in pages.chunk_by(|(a, _), (b, _)| a.part_index == b.part_index) {
+            let (first, first_address) = part_pages[0];
+            let (last, last_address) = part_pages[part_pages.len() - 1];
+            parts.push(BookPart {
+                part_index: first.part_index,
+                part_label: self.stored_text(&searcher, first_address, "part_label")?,
+                first_page_id: first.page_id,
+                last_page_id: last.page_id,
+                page_count: part_pages.len(),
+                first_page_number: self.stored_text(&searcher, first_address, "page_number")?,
+                last_page_number: self.stored_text(&searcher, last_address, "page_number")?,
+            });
+        }
+        Ok(parts)
+    }
+
+    /// Pages of a book within a range of parts
+    fn parts_query(&self, id: u64, from_part: Bound<u64>, to_part: Bound<u64>) -> BooleanQuery {
+        let id_field = self.schema.get_field("text_id").unwrap();
+        let part_index_field = self.schema.get_field("part_index").unwrap();
+        let part_term = |bound: Bound<u64>| bound.map(|part_index| Term::from_field_u64(part_index_field, part_index));
+        BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(Term::from_field_u64(id_field, id), IndexRecordOption::Basic)) as Box<dyn Query>,
+            ),
+            (Occur::Must, Box::new(RangeQuery::new(part_term(from_part), part_term(to_part)))),
+        ])
+    }
+
+    /// Every page of one part of a book in page order, from the fast fields
+    fn part_pages(&self, searcher: &Searcher, id: u64, part_index: u64) -> Result<Vec<(SortKey, DocAddress)>> {
+        let query = self.parts_query(id, Bound::Included(part_index), Bound::Included(part_index));
+        Ok(searcher.search(&query, &SortKeyTopDocs::new(usize::MAX, None))?)
+    }
+
+    /// The page after or before a page of a book, across parts; None at either end.
+    /// The page itself need not exist, so gaps in page_id are skipped.
+    pub fn adjacent_page(&self, id: u64, part_index: u64, page_id: u64, direction: PageDirection) -> Result<Option<SearchResult>> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        // Seek within the part, then to the closest part on that side
+        let pages = self.part_pages(&searcher, id, part_index)?;
+        let adjacent = match direction {
+            PageDirection::Next => match pages.iter().find(|(key, _)| key.page_id > page_id) {
+                Some(&page) => Some(page),
+                None => {
+                    let later_parts = self.parts_query(id, Bound::Excluded(part_index), Bound::Unbounded);
+                    searcher.search(&later_parts, &SortKeyTopDocs::new(1, None))?.into_iter().next()
+                }
+            },
+            PageDirection::Previous => match pages.iter().rev().find(|(key, _)| key.page_id < page_id) {
+                Some(&page) => Some(page),
+                None => {
+                    let earlier_parts = self.parts_query(id, Bound::Unbounded, Bound::Excluded(part_index));
+                    let previous_part = searcher.search(&earlier_parts, &TopDocs::with_limit(1).order_by_u64_field("part_index", Order::Desc))?;
+                    match previous_part.first() {
+                        Some(&(previous_part, _)) => self.part_pages(&searcher, id, previous_part)?.last().copied(),
+                        None => None,
+                    }
+                }
+            },
+        };
+        adjacent
+            .map(|(_, doc_address)| self.extract_result(&searcher, doc_address, 0.0, Vec::new(), 0, &SearchOptions::default()))
+            .transpose()
+    }
+
+    /// The page of a book with a printed page number, in the part with the given label
+    /// (any part if None); the first in reading order if several match.
+    /// Where page_number is indexed only the pages with the number are read.
+    pub fn page_by_number(&self, id: u64, part_label: Option<&str>, page_number: &str) -> Result<Option<SearchResult>> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let page_number_field = self.schema.get_field("page_number").unwrap();
+        let pages = if self.schema.get_field_entry(page_number_field).is_indexed() {
+            let id_field = self.schema.get_field("text_id").unwrap();
+            let spellings: Vec<Term> = page_number_spellings(page_number)
+                .iter()
+                .map(|spelling| Term::from_field_text(page_number_field, spelling))
+                .collect();
+            let query = BooleanQuery::new(vec![
+                (
+                    Occur::Must,
+                    Box::new(TermQuery::new(Term::from_field_u64(id_field, id), IndexRecordOption::Basic)) as Box<dyn Query>,
+                ),
+                (Occur::Must, Box::new(TermSetQuery::new(spellings))),
+            ]);
+            searcher.search(&query, &SortKeyTopDocs::new(usize::MAX, None))?
+        } else {
+            self.book_pages(&searcher, id)?
+        };
+
+        for part_pages in pages.chunk_by(|(a, _), (b, _)| a.part_index == b.part_index) {
+            if let Some(label) = part_label {
+                let part_label = self.stored_text(&searcher, part_pages[0].1, "part_label")?;
+                if !same_part_label(&part_label, label) {
+                    continue;
+                }
+            }
+            for &(_, doc_address) in part_pages {
+                if same_page_number(&self.stored_text(&searcher, doc_address, "page_number")?, page_number) {
+                    return self
+                        .extract_result(&searcher, doc_address, 0.0, Vec::new(), 0, &SearchOptions::default())
+                        .map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Build a SearchResult from a stored document
+    fn extract_result(
+        &self,
+        searcher: &Searcher,
+        doc_address: DocAddress,
+        score: f32,
+        matched_token_indices: Vec<u32>,
+        match_count: usize,
+        options: &SearchOptions,
+    ) -> Result<SearchResult> {
+        let doc: TantivyDocument = searcher.doc(doc_address)?;
+
+        let id_field = self.schema.get_field("text_id").unwrap();
+        let part_index_field = self.schema.get_field("part_index").unwrap();
+        let page_id_field = self.schema.get_field("page_id").unwrap();
+        let author_id_field = self.schema.get_field("author_id").unwrap();
+        let genre_id_field = self.schema.get_field("genre_id").unwrap();
+        let death_ah_field = self.schema.get_field("death_ah").unwrap();
+        let century_ah_field = self.schema.get_field("century_ah").unwrap();
+        let part_label_field = self.schema.get_field("part_label").unwrap();
+        let page_number_field = self.schema.get_field("page_number").unwrap();
+        let body_field = self.schema.get_field("body").unwrap();
+
+        let mut body = doc
+            .get_first(body_field)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let mut snippets = Vec::new();
+        if let Some(snippet_options) = &options.snippets {
+            snippets = build_snippets(&body, &matched_token_indices, snippet_options);
+            if !options.include_body {
+                // Empty body is skipped when serializing
+                body.clear();
+            }
+        }
+
+        Ok(SearchResult {
+            id: doc
+                .get_first(id_field)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            part_index: doc
+                .get_first(part_index_field)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            page_id: doc
+                .get_first(page_id_field)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
+            genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
+            death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
+            century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
+            part_label: doc
+                .get_first(part_label_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            page_number: doc
+                .get_first(page_number_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+            body,
+            score,
+            matched_token_indices,
+            match_count,
+            snippets,
+            matched_patterns: Vec::new(),
+        })
+    }
+
+    pub fn doc_count(&self) -> Result<u64> {
         let reader = self
             .index
             .reader_builder()
@@ -945,6 +1532,122 @@ impl SearchEngine {
         }
     }
 
+    /// Require one of the filter's book IDs, if any are given
+    fn with_book_filter(&self, text_query: Box<dyn Query>, filters: &SearchFilters) -> Box<dyn Query> {
+        let Some(book_ids) = filters.book_ids.as_ref().filter(|ids| !ids.is_empty()) else {
+            return text_query;
+        };
+
+        let id_field = self.schema.get_field("text_id").unwrap();
+        let book_id_queries: Vec<(Occur, Box<dyn Query>)> = book_ids
+            .iter()
+            .map(|&id| {
+                let term = Term::from_field_u64(id_field, id);
+                let term_query: Box<dyn Query> =
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+                (Occur::Should, term_query)
+            })
+            .collect();
+        let book_ids_query = BooleanQuery::new(book_id_queries);
+        Box::new(BooleanQuery::new(vec![
+            (Occur::Must, text_query),
+            (Occur::Must, Box::new(book_ids_query)),
+        ]))
+    }
+
+    /// Text query of a simple search over an already normalized query
+    fn build_simple_query(&self, normalized_query: &str, search_field: Field, multi_word: bool) -> Result<Box<dyn Query>> {
+        if multi_word {
+            // Multi-word query: use PhraseQuery for all modes
+            let terms: Vec<Term> = normalized_query
+                .split_whitespace()
+                .map(|word| Term::from_field_text(search_field, word))
+                .collect();
+            Ok(Box::new(PhraseQuery::new(terms)))
+        } else {
+            let query_parser = QueryParser::for_index(&self.index, vec![search_field]);
+            Ok(query_parser.parse_query(normalized_query)?)
+        }
+    }
+
+    /// Text query of a combined search: every AND term and at least one OR term
+    fn build_combined_query(&self, and_terms: &[SearchTerm], or_terms: &[SearchTerm]) -> Result<Box<dyn Query>> {
+        let query: Box<dyn Query> = if and_terms.len() == 1 && or_terms.is_empty() {
+            self.build_term_query(&and_terms[0])?
+        } else if and_terms.is_empty() && or_terms.len() == 1 {
+            self.build_term_query(&or_terms[0])?
+        } else {
+            let mut must_clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+            for term in and_terms {
+                let term_query = self.build_term_query(term)?;
+                must_clauses.push((Occur::Must, term_query));
+            }
+            if !or_terms.is_empty() {
+                let mut or_clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+                for term in or_terms {
+                    let term_query = self.build_term_query(term)?;
+                    or_clauses.push((Occur::Should, term_query));
+                }
+                let or_query = BooleanQuery::new(or_clauses);
+                must_clauses.push((Occur::Must, Box::new(or_query)));
+            }
+            Box::new(BooleanQuery::new(must_clauses))
+        };
+        Ok(query)
+    }
+
+    /// Phrase query for a multi-word name pattern, term query for a single word
+    fn build_name_pattern_query(&self, pattern: &str) -> Option<Box<dyn Query>> {
+        let surface_field = self.schema.get_field("surface_text").unwrap();
+        let normalized = normalize_arabic(pattern);
+        let words: Vec<&str> = normalized.split_whitespace().collect();
+
+        match words.as_slice() {
+            [] => None,
+            [word] => {
+                let term = Term::from_field_text(surface_field, word);
+                Some(Box::new(TermQuery::new(term, IndexRecordOption::WithFreqsAndPositions)))
+            }
+            _ => {
+                let terms: Vec<Term> = words
+                    .iter()
+                    .map(|word| Term::from_field_text(surface_field, word))
+                    .collect();
+                Some(Box::new(PhraseQuery::new(terms)))
+            }
+        }
+    }
+
+    /// Text query of a name search: each form's patterns become a Should clause,
+    /// multiple forms are combined with Must (AND). None if no form has a pattern.
+    fn build_name_query(&self, patterns_by_form: &[Vec<String>]) -> Option<Box<dyn Query>> {
+        let mut form_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+        for patterns in patterns_by_form {
+            if patterns.is_empty() {
+                continue;
+            }
+
+            // Build OR query for all patterns in this form
+            let pattern_queries: Vec<(Occur, Box<dyn Query>)> = patterns
+                .iter()
+                .filter_map(|pattern| self.build_name_pattern_query(pattern))
+                .map(|query| (Occur::Should, query))
+                .collect();
+
+            if !pattern_queries.is_empty() {
+                let form_query = BooleanQuery::new(pattern_queries);
+                form_queries.push((Occur::Must, Box::new(form_query)));
+            }
+        }
+
+        match form_queries.len() {
+            0 => None,
+            1 => form_queries.pop().map(|(_, query)| query),
+            _ => Some(Box::new(BooleanQuery::new(form_queries))),
+        }
+    }
+
     fn build_term_query(&self, term: &SearchTerm) -> Result<Box<dyn Query>> {
         let search_field = match term.mode {
             SearchMode::Surface => self.schema.get_field("surface_text").unwrap(),
@@ -1025,6 +1728,56 @@ impl SearchEngine {
         normalized.split_whitespace().map(|s| s.to_string()).collect()
     }
 
+    /// All match positions of a search term on one page, plus its occurrence count
+    /// (phrase occurrences for phrase terms, token positions otherwise)
+    fn term_matches(&self, segment_reader: &SegmentReader, doc_id: u32, term: &SearchTerm) -> (Vec<u32>, usize) {
+        let field = self.get_search_field(term.mode);
+        if self.is_phrase_search(term) {
+            let phrase_words = self.extract_phrase_terms(term);
+            let starts = self.get_phrase_starts(segment_reader, doc_id, field, &phrase_words);
+            (phrase_positions_from_starts(&starts, phrase_words.len()), starts.len())
+        } else {
+            let query_terms = self.extract_query_terms(term);
+            let positions = self.get_matched_positions_internal(segment_reader, doc_id, field, &query_terms, None, usize::MAX);
+            let count = positions.len();
+            (positions, count)
+        }
+    }
+
+    /// Positions of term1/term2 pairs within max_distance on one page.
+    /// The count is the number of term1 occurrences with a term2 occurrence in range.
+    fn proximity_matches(
+        &self,
+        segment_reader: &SegmentReader,
+        doc_id: u32,
+        term1: &SearchTerm,
+        term2: &SearchTerm,
+        max_distance: usize,
+    ) -> (Vec<u32>, usize) {
+        let (pos1, _) = self.term_matches(segment_reader, doc_id, term1);
+        let (pos2, _) = self.term_matches(segment_reader, doc_id, term2);
+
+        let mut matched_positions: Vec<u32> = Vec::new();
+        let mut anchors = 0;
+        for &p1 in &pos1 {
+            let mut anchored = false;
+            for &p2 in &pos2 {
+                if p1.abs_diff(p2) as usize <= max_distance {
+                    matched_positions.push(p1);
+                    matched_positions.push(p2);
+                    anchored = true;
+                }
+            }
+            if anchored {
+                anchors += 1;
+            }
+        }
+
+        matched_positions.sort_unstable();
+        matched_positions.dedup();
+        (matched_positions, anchors)
+    }
+
     pub fn get_match_positions_combined(
         &self,
         id: u64,
@@ -1197,6 +1950,7 @@ impl SearchEngine {
         filters: &SearchFilters,
         limit: usize,
         offset: usize,
+        options: &SearchOptions,
     ) -> Result<SearchResults> {
         let start = std::time::Instant::now();
 
@@ -1214,156 +1968,42 @@ impl SearchEngine {
                 total_hits: 0,
                 results: Vec::new(),
                 elapsed_ms: 0,
+                next_cursor: None,
+                truncated: false,
+                truncated_reason: None,
+                pattern_hits: Vec::new(),
             });
         }
 
-        let text_query: Box<dyn Query> = if and_terms.len() == 1 && or_terms.is_empty() {
-            self.build_term_query(&and_terms[0])?
-        } else if and_terms.is_empty() && or_terms.len() == 1 {
-            self.build_term_query(&or_terms[0])?
-        } else {
-            let mut must_clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-            for term in and_terms {
-                let term_query = self.build_term_query(term)?;
-                must_clauses.push((Occur::Must, term_query));
-            }
-            if !or_terms.is_empty() {
-                let mut or_clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-                for term in or_terms {
-                    let term_query = self.build_term_query(term)?;
-                    or_clauses.push((Occur::Should, term_query));
-                }
-                let or_query = BooleanQuery::new(or_clauses);
-                must_clauses.push((Occur::Must, Box::new(or_query)));
-            }
-            Box::new(BooleanQuery::new(must_clauses))
-        };
-
-        let final_query: Box<dyn Query> = if let Some(ref book_ids) = filters.book_ids {
-            if book_ids.is_empty() {
-                text_query
-            } else {
-                let id_field = self.schema.get_field("text_id").unwrap();
-                let book_id_queries: Vec<(Occur, Box<dyn Query>)> = book_ids
-                    .iter()
-                    .map(|&id| {
-                        let term = Term::from_field_u64(id_field, id);
-                        let term_query: Box<dyn Query> =
-                            Box::new(TermQuery::new(term, IndexRecordOption::Basic));
-                        (Occur::Should, term_query)
-                    })
-                    .collect();
-                let book_ids_query = BooleanQuery::new(book_id_queries);
-                let combined = BooleanQuery::new(vec![
-                    (Occur::Must, text_query),
-                    (Occur::Must, Box::new(book_ids_query)),
-                ]);
-                Box::new(combined)
-            }
-        } else {
-            text_query
-        };
+        let text_query = self.build_combined_query(and_terms, or_terms)?;
 
-        let id_field = self.schema.get_field("text_id").unwrap();
-        let part_index_field = self.schema.get_field("part_index").unwrap();
-        let page_id_field = self.schema.get_field("page_id").unwrap();
-        let author_id_field = self.schema.get_field("author_id").unwrap();
-        let genre_id_field = self.schema.get_field("genre_id").unwrap();
-        let death_ah_field = self.schema.get_field("death_ah").unwrap();
-        let century_ah_field = self.schema.get_field("century_ah").unwrap();
-        let part_label_field = self.schema.get_field("part_label").unwrap();
-        let page_number_field = self.schema.get_field("page_number").unwrap();
-        let body_field = self.schema.get_field("body").unwrap();
+        let final_query = self.with_book_filter(text_query, filters);
 
         // Sort by death_ah at Tantivy level - this is the ONLY correct way to get global ordering
-        let (total_hits, top_docs) =
-            searcher.search(&*final_query, &(Count, TopDocs::with_limit(limit + offset).order_by_u64_field("death_ah", tantivy::Order::Asc)))?;
-
-        // Collect docs to process, preserving the death_ah order from Tantivy
-        let docs_to_process: Vec<(u64, DocAddress)> = top_docs.into_iter().skip(offset).take(limit).collect();
-
-        // Collect phrase search terms separately for special handling
-        let phrase_terms: Vec<&SearchTerm> = and_terms.iter().chain(or_terms.iter())
-            .filter(|t| self.is_phrase_search(t))
-            .collect();
-        let non_phrase_terms: Vec<&SearchTerm> = and_terms.iter().chain(or_terms.iter())
-            .filter(|t| !self.is_phrase_search(t))
-            .collect();
+        let cache_key = result_cache_key("combined", &(and_terms, or_terms, filters));
+        let CollectedPage { total_hits, docs: docs_to_process, next_cursor, truncated } =
+            self.collect_page(&searcher, &*final_query, cache_key, limit, offset, options)?;
 
         // Process docs in order (already sorted by death_ah from Tantivy)
         let mut results = Vec::new();
-        for (_sort_value, doc_address) in docs_to_process {
-            let doc: TantivyDocument = searcher.doc(doc_address)?;
+        for (_sort_key, doc_address) in docs_to_process {
+            options.control.check()?;
             let segment_reader = searcher.segment_reader(doc_address.segment_ord);
 
-            // Get positions for non-phrase terms
+            // Highlight a preview of each term's matches, but count all of them
             let mut matched: Vec<u32> = Vec::new();
-            for term in &non_phrase_terms {
-                let field = self.get_search_field(term.mode);
-                let query_terms = self.extract_query_terms(term);
-                let positions = self.get_matched_positions_limited(
-                    segment_reader,
-                    doc_address.doc_id,
-                    field,
-                    &query_terms,
-                    5,
-                );
-                matched.extend(positions);
-            }
-
-            // Get positions for phrase terms
-            for term in &phrase_terms {
-                let field = self.get_search_field(term.mode);
-                let phrase_words = self.extract_phrase_terms(term);
-                let phrase_positions = self.get_phrase_positions_limited(
-                    segment_reader,
-                    doc_address.doc_id,
-                    field,
-                    &phrase_words,
-                    5,
-                );
-                matched.extend(phrase_positions);
+            let mut match_count = 0;
+            for term in and_terms.iter().chain(or_terms.iter()) {
+                let (positions, count) = self.term_matches(segment_reader, doc_address.doc_id, term);
+                matched.extend(positions.into_iter().take(5));
+                match_count += count;
             }
 
             matched.sort_unstable();
             matched.dedup();
 
-            let result = SearchResult {
-                id: doc
-                    .get_first(id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                part_index: doc
-                    .get_first(part_index_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                page_id: doc
-                    .get_first(page_id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
-                genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
-                death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
-                century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
-                part_label: doc
-                    .get_first(part_label_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                page_number: doc
-                    .get_first(page_number_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                body: doc
-                    .get_first(body_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                score: 0.0, // Not using relevance score when sorting by death_ah
-                matched_token_indices: matched,
-            };
-            results.push(result);
+            // Not using relevance score when sorting by death_ah
+            results.push(self.extract_result(&searcher, doc_address, 0.0, matched, match_count, options)?);
         }
 
         // Results already sorted by death_ah from Tantivy - no post-sort needed
@@ -1410,9 +2050,14 @@ impl SearchEngine {
             total_hits,
             results,
             elapsed_ms,
+            next_cursor,
+            truncated: truncated.is_some(),
+            truncated_reason: truncated,
+            pattern_hits: Vec::new(),
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn proximity_search(
         &self,
         term1: &SearchTerm,
@@ -1421,6 +2066,7 @@ impl SearchEngine {
         filters: &SearchFilters,
         limit: usize,
         offset: usize,
+        options: &SearchOptions,
     ) -> Result<SearchResults> {
         let start = std::time::Instant::now();
 
@@ -1433,87 +2079,48 @@ impl SearchEngine {
 
         // Overfetch significantly to account for proximity filtering.
         // Many candidates won't pass the distance check, so we need a high cap.
-        let overfetch_limit = ((limit + offset) * 50).max(5000);
+        let deadline = options.budget.start();
+        let (overfetch_limit, candidates_capped) = options.budget.cap_candidates(((limit + offset) * 50).max(5000));
         let term1_query = self.build_term_query(term1)?;
         let term2_query = self.build_term_query(term2)?;
         let text_query = BooleanQuery::new(vec![(Occur::Must, term1_query), (Occur::Must, term2_query)]);
 
-        let final_query: Box<dyn Query> = if let Some(ref book_ids) = filters.book_ids {
-            if book_ids.is_empty() {
-                Box::new(text_query)
-            } else {
-                let id_field = self.schema.get_field("text_id").unwrap();
-                let book_id_queries: Vec<(Occur, Box<dyn Query>)> = book_ids
-                    .iter()
-                    .map(|&id| {
-                        let term = Term::from_field_u64(id_field, id);
-                        let term_query: Box<dyn Query> =
-                            Box::new(TermQuery::new(term, IndexRecordOption::Basic));
-                        (Occur::Should, term_query)
-                    })
-                    .collect();
-                let book_ids_query = BooleanQuery::new(book_id_queries);
-
-                let combined = BooleanQuery::new(vec![
-                    (Occur::Must, Box::new(text_query)),
-                    (Occur::Must, Box::new(book_ids_query)),
-                ]);
-                Box::new(combined)
-            }
-        } else {
-            Box::new(text_query)
-        };
-
-        let field1 = self.get_search_field(term1.mode);
-        let field2 = self.get_search_field(term2.mode);
-        let query_terms1 = self.extract_query_terms(term1);
-        let query_terms2 = self.extract_query_terms(term2);
-
-        let id_field = self.schema.get_field("text_id").unwrap();
-        let part_index_field = self.schema.get_field("part_index").unwrap();
-        let page_id_field = self.schema.get_field("page_id").unwrap();
-        let author_id_field = self.schema.get_field("author_id").unwrap();
-        let genre_id_field = self.schema.get_field("genre_id").unwrap();
-        let death_ah_field = self.schema.get_field("death_ah").unwrap();
-        let century_ah_field = self.schema.get_field("century_ah").unwrap();
-        let part_label_field = self.schema.get_field("part_label").unwrap();
-        let page_number_field = self.schema.get_field("page_number").unwrap();
-        let body_field = self.schema.get_field("body").unwrap();
+        let final_query = self.with_book_filter(Box::new(text_query), filters);
 
         // Sort by death_ah at Tantivy level - candidates come in chronological order
-        let top_docs = searcher.search(&*final_query, &TopDocs::with_limit(overfetch_limit).order_by_u64_field("death_ah", tantivy::Order::Asc))?;
+        let after = options.cursor.as_deref().map(SortKey::from_cursor).transpose()?;
+        let top_docs = search_with_control(
+            &searcher,
+            &*final_query,
+            SortKeyTopDocs::new(overfetch_limit, after),
+            &options.control,
+            &deadline,
+        )?;
+        let collection_cut = deadline.was_hit();
+        let candidates_exhausted = top_docs.len() < overfetch_limit;
 
         let mut results = Vec::new();
         let mut skipped = 0;
         let mut total_matches = 0;
+        let mut last_result = None;
+        let mut last_checked = after;
+        let mut verification_cut = false;
+
+        let candidates = top_docs.len();
+        for (checked, (sort_key, doc_address)) in top_docs.into_iter().enumerate() {
+            options.control.check()?;
+            if checked > 0 && checked % PROGRESS_INTERVAL == 0 {
+                options.control.report(&SearchProgress { checked, candidates, hits: total_matches });
+            }
+            if deadline.expired() {
+                verification_cut = true;
+                break;
+            }
+            last_checked = Some(sort_key);
 
-        for (_sort_value, doc_address) in top_docs {
             let segment_reader = searcher.segment_reader(doc_address.segment_ord);
-            let pos1 = self.get_matched_positions_limited(
-                segment_reader,
-                doc_address.doc_id,
-                field1,
-                &query_terms1,
-                100,
-            );
-            let pos2 = self.get_matched_positions_limited(
-                segment_reader,
-                doc_address.doc_id,
-                field2,
-                &query_terms2,
-                100,
-            );
-
-            let mut matched_positions: Vec<u32> = Vec::new();
-            for &p1 in &pos1 {
-                for &p2 in &pos2 {
-                    let dist = if p1 > p2 { p1 - p2 } else { p2 - p1 } as usize;
-                    if dist <= max_distance {
-                        matched_positions.push(p1);
-                        matched_positions.push(p2);
-                    }
-                }
-            }
+            let (mut matched_positions, match_count) =
+                self.proximity_matches(segment_reader, doc_address.doc_id, term1, term2, max_distance);
 
             if matched_positions.is_empty() {
                 continue;
@@ -1527,53 +2134,31 @@ impl SearchEngine {
             }
 
             if results.len() >= limit {
+                // A resumed page only needs to know that another hit exists
+                if after.is_some() {
+                    break;
+                }
                 continue;
             }
 
-            let doc: TantivyDocument = searcher.doc(doc_address)?;
-            matched_positions.sort_unstable();
-            matched_positions.dedup();
             matched_positions.truncate(50);
 
-            let result = SearchResult {
-                id: doc
-                    .get_first(id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                part_index: doc
-                    .get_first(part_index_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                page_id: doc
-                    .get_first(page_id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
-                genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
-                death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
-                century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
-                part_label: doc
-                    .get_first(part_label_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                page_number: doc
-                    .get_first(page_number_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                body: doc
-                    .get_first(body_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                score: 0.0, // Not using relevance score when sorting by death_ah
-                matched_token_indices: matched_positions,
-            };
-
-            results.push(result);
+            // Not using relevance score when sorting by death_ah
+            results.push(self.extract_result(&searcher, doc_address, 0.0, matched_positions, match_count, options)?);
+            last_result = Some(sort_key);
         }
 
+        let more_hits = total_matches > offset + results.len();
+        let (truncated, next_cursor) = verified_page_end(
+            more_hits,
+            candidates_exhausted,
+            candidates_capped,
+            collection_cut,
+            verification_cut,
+            last_result,
+            last_checked,
+        );
+
         // Results already in death_ah order from Tantivy - no post-sort needed
 
         let elapsed_ms = start.elapsed().as_millis() as u64;
@@ -1584,20 +2169,27 @@ impl SearchEngine {
             total_hits: total_matches,
             results,
             elapsed_ms,
+            next_cursor,
+            truncated: truncated.is_some(),
+            truncated_reason: truncated,
+            pattern_hits: Vec::new(),
         })
     }
 
     /// Name search - search for Arabic personal names using pattern matching
-    /// Each form is a list of patterns (with proclitic expansion already applied)
+    /// Each form is a structured name or a list of patterns (with proclitic expansion already applied)
     /// Multiple forms use AND logic (all must match on the same page)
     pub fn name_search(
         &self,
-        patterns_by_form: &[Vec<String>],
+        forms: &[NameSearchForm],
         filters: &SearchFilters,
         limit: usize,
         offset: usize,
+        options: &SearchOptions,
     ) -> Result<SearchResults> {
         let start = std::time::Instant::now();
+        let deadline = options.budget.start();
+        let patterns_by_form: Vec<Vec<String>> = forms.iter().map(NameSearchForm::search_patterns).collect::<Result<_>>()?;
 
         if patterns_by_form.is_empty() || patterns_by_form.iter().all(|p| p.is_empty()) {
             return Ok(SearchResults {
@@ -1606,6 +2198,10 @@ impl SearchEngine {
                 total_hits: 0,
                 results: Vec::new(),
                 elapsed_ms: 0,
+                next_cursor: None,
+                truncated: false,
+                truncated_reason: None,
+                pattern_hits: Vec::new(),
             });
         }
 
@@ -1618,158 +2214,57 @@ impl SearchEngine {
 
         let surface_field = self.schema.get_field("surface_text").unwrap();
 
-        // Build the query: each form's patterns become a Should clause,
-        // multiple forms are combined with Must (AND)
-        let mut form_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-
-        for patterns in patterns_by_form {
-            if patterns.is_empty() {
-                continue;
-            }
-
-            // Build OR query for all patterns in this form
-            let mut pattern_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-            for pattern in patterns {
-                let normalized = normalize_arabic(pattern);
-                let words: Vec<&str> = normalized.split_whitespace().collect();
-
-                let pattern_query: Box<dyn Query> = if words.len() > 1 {
-                    // Phrase query for multi-word patterns
-                    let terms: Vec<Term> = words
-                        .iter()
-                        .map(|word| Term::from_field_text(surface_field, word))
-                        .collect();
-                    Box::new(PhraseQuery::new(terms))
-                } else if !words.is_empty() {
-                    // Single term query
-                    let term = Term::from_field_text(surface_field, words[0]);
-                    Box::new(TermQuery::new(term, IndexRecordOption::WithFreqsAndPositions))
-                } else {
-                    continue;
-                };
-
-                pattern_queries.push((Occur::Should, pattern_query));
-            }
-
-            if !pattern_queries.is_empty() {
-                let form_query = BooleanQuery::new(pattern_queries);
-                form_queries.push((Occur::Must, Box::new(form_query)));
-            }
-        }
-
-        if form_queries.is_empty() {
+        let Some(text_query) = self.build_name_query(&patterns_by_form) else {
             return Ok(SearchResults {
                 query: String::new(),
                 mode: SearchMode::Surface,
                 total_hits: 0,
                 results: Vec::new(),
                 elapsed_ms: 0,
+                next_cursor: None,
+                truncated: false,
+                truncated_reason: None,
+                pattern_hits: Vec::new(),
             });
-        }
-
-        let text_query: Box<dyn Query> = if form_queries.len() == 1 {
-            form_queries.pop().unwrap().1
-        } else {
-            Box::new(BooleanQuery::new(form_queries))
         };
 
         // Apply book_ids filter if provided
-        let final_query: Box<dyn Query> = if let Some(ref book_ids) = filters.book_ids {
-            if book_ids.is_empty() {
-                text_query
-            } else {
-                let id_field = self.schema.get_field("text_id").unwrap();
-                let book_id_queries: Vec<(Occur, Box<dyn Query>)> = book_ids
-                    .iter()
-                    .map(|&id| {
-                        let term = Term::from_field_u64(id_field, id);
-                        let term_query: Box<dyn Query> =
-                            Box::new(TermQuery::new(term, IndexRecordOption::Basic));
-                        (Occur::Should, term_query)
-                    })
-                    .collect();
-                let book_ids_query = BooleanQuery::new(book_id_queries);
-                let combined = BooleanQuery::new(vec![
-                    (Occur::Must, text_query),
-                    (Occur::Must, Box::new(book_ids_query)),
-                ]);
-                Box::new(combined)
-            }
-        } else {
-            text_query
-        };
-
-        let id_field = self.schema.get_field("text_id").unwrap();
-        let part_index_field = self.schema.get_field("part_index").unwrap();
-        let page_id_field = self.schema.get_field("page_id").unwrap();
-        let author_id_field = self.schema.get_field("author_id").unwrap();
-        let genre_id_field = self.schema.get_field("genre_id").unwrap();
-        let death_ah_field = self.schema.get_field("death_ah").unwrap();
-        let century_ah_field = self.schema.get_field("century_ah").unwrap();
-        let part_label_field = self.schema.get_field("part_label").unwrap();
-        let page_number_field = self.schema.get_field("page_number").unwrap();
-        let body_field = self.schema.get_field("body").unwrap();
+        let final_query = self.with_book_filter(text_query, filters);
 
         // Sort by death_ah at Tantivy level - the ONLY correct way to get global ordering
-        let (total_hits, top_docs) =
-            searcher.search(&*final_query, &(Count, TopDocs::with_limit(limit + offset).order_by_u64_field("death_ah", tantivy::Order::Asc)))?;
+        let cache_key = result_cache_key("names", &(&patterns_by_form, filters));
+        let CollectedPage { total_hits, docs: page_docs, next_cursor, truncated } =
+            self.collect_page(&searcher, &*final_query, cache_key, limit, offset, options)?;
 
         let mut results = Vec::new();
-        for (_sort_value, doc_address) in top_docs.into_iter().skip(offset).take(limit) {
-            let doc: TantivyDocument = searcher.doc(doc_address)?;
-
-            // Get token positions for highlighting (using first form's patterns)
-            let matched_token_indices = if let Some(patterns) = patterns_by_form.first() {
-                self.get_name_pattern_positions(
-                    searcher.segment_reader(doc_address.segment_ord),
-                    doc_address.doc_id,
-                    surface_field,
-                    patterns,
-                    5,
-                )
-            } else {
-                Vec::new()
-            };
-
-            let result = SearchResult {
-                id: doc
-                    .get_first(id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                part_index: doc
-                    .get_first(part_index_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                page_id: doc
-                    .get_first(page_id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
-                genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
-                death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
-                century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
-                part_label: doc
-                    .get_first(part_label_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                page_number: doc
-                    .get_first(page_number_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                body: doc
-                    .get_first(body_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                score: 0.0, // Not using relevance score when sorting by death_ah
-                matched_token_indices,
-            };
+        for (_sort_key, doc_address) in page_docs {
+            options.control.check()?;
+            // Every form must match, so highlight the patterns of all forms
+            let occurrences = self.name_pattern_occurrences(
+                searcher.segment_reader(doc_address.segment_ord),
+                doc_address.doc_id,
+                surface_field,
+                patterns_by_form
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(form, patterns)| patterns.iter().map(move |pattern| (form, pattern))),
+            );
+            let (mut matched_token_indices, match_count) = occurrence_positions(&occurrences);
+            matched_token_indices.truncate(5);
 
+            // Not using relevance score when sorting by death_ah
+            let mut result = self.extract_result(&searcher, doc_address, 0.0, matched_token_indices, match_count, options)?;
+            result.matched_patterns = occurrences;
             results.push(result);
         }
 
+        // Counted once per search rather than for every further page
+        let pattern_hits = if options.pattern_hits && offset == 0 && options.cursor.is_none() {
+            self.name_pattern_hits(&searcher, &*final_query, &patterns_by_form, options, &deadline)?
+        } else {
+            Vec::new()
+        };
+
         // Results already sorted by death_ah from Tantivy - no post-sort needed
 
         let elapsed_ms = start.elapsed().as_millis() as u64;
@@ -1788,9 +2283,43 @@ impl SearchEngine {
             total_hits,
             results,
             elapsed_ms,
+            next_cursor,
+            truncated: truncated.is_some(),
+            truncated_reason: truncated,
+            pattern_hits,
         })
     }
 
+    /// Result pages matched by each name pattern, most first; patterns without hits,
+    /// or not fully counted before the deadline, are left out
+    fn name_pattern_hits(
+        &self,
+        searcher: &Searcher,
+        results_query: &dyn Query,
+        patterns_by_form: &[Vec<String>],
+        options: &SearchOptions,
+        deadline: &Deadline,
+    ) -> Result<Vec<PatternHits>> {
+        let mut hits = Vec::new();
+        for (form, patterns) in patterns_by_form.iter().enumerate() {
+            for pattern in patterns {
+                if deadline.expired() {
+                    break;
+                }
+                let Some(pattern_query) = self.build_name_pattern_query(pattern) else {
+                    continue;
+                };
+                let query = BooleanQuery::new(vec![(Occur::Must, results_query.box_clone()), (Occur::Must, pattern_query)]);
+                let pages = search_with_control(searcher, &query, Count, &options.control, deadline)?;
+                if pages > 0 && !deadline.was_hit() {
+                    hits.push(PatternHits { form, pattern: pattern.clone(), pages });
+                }
+            }
+        }
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.pages));
+        Ok(hits)
+    }
+
     /// Get token positions matching any of the given name patterns
     fn get_name_pattern_positions(
         &self,
@@ -1800,47 +2329,48 @@ impl SearchEngine {
         patterns: &[String],
         max_positions: usize,
     ) -> Vec<u32> {
-        let mut all_positions: HashSet<u32> = HashSet::new();
+        let (mut positions, _) = self.name_pattern_matches(segment_reader, doc_id, field, patterns);
+        positions.truncate(max_positions);
+        positions
+    }
 
-        for pattern in patterns {
-            let normalized = normalize_arabic(pattern);
-            let words: Vec<&str> = normalized.split_whitespace().collect();
+    /// All token positions matched by the given name patterns on one page, plus the
+    /// number of distinct places a pattern starts (overlapping patterns count once)
+    fn name_pattern_matches(
+        &self,
+        segment_reader: &SegmentReader,
+        doc_id: u32,
+        field: Field,
+        patterns: &[String],
+    ) -> (Vec<u32>, usize) {
+        let occurrences =
+            self.name_pattern_occurrences(segment_reader, doc_id, field, patterns.iter().map(|pattern| (0, pattern)));
+        occurrence_positions(&occurrences)
+    }
 
-            if words.len() > 1 {
-                // Phrase query - get consecutive positions
-                let phrase_words: Vec<String> = words.iter().map(|s| s.to_string()).collect();
-                let positions = self.get_phrase_positions_limited(
-                    segment_reader,
-                    doc_id,
-                    field,
-                    &phrase_words,
-                    max_positions,
-                );
-                all_positions.extend(positions);
-            } else if !words.is_empty() {
-                // Single term
-                let mut query_terms = HashSet::new();
-                query_terms.insert(words[0].to_string());
-                let positions = self.get_matched_positions_internal(
-                    segment_reader,
-                    doc_id,
-                    field,
-                    &query_terms,
-                    None,
-                    max_positions,
-                );
-                all_positions.extend(positions);
+    /// Where each (form, pattern) occurs on one page; patterns not found are left out
+    fn name_pattern_occurrences<'a>(
+        &self,
+        segment_reader: &SegmentReader,
+        doc_id: u32,
+        field: Field,
+        patterns: impl IntoIterator<Item = (usize, &'a String)>,
+    ) -> Vec<PatternMatch> {
+        let mut occurrences = Vec::new();
+        for (form, pattern) in patterns {
+            let normalized = normalize_arabic(pattern);
+            let words: Vec<String> = normalized.split_whitespace().map(|s| s.to_string()).collect();
+            if words.is_empty() {
+                continue;
             }
 
-            if all_positions.len() >= max_positions {
-                break;
+            // Single-word patterns behave like a one-term phrase
+            let starts = self.get_phrase_starts(segment_reader, doc_id, field, &words);
+            if !starts.is_empty() {
+                occurrences.push(PatternMatch { form, pattern: pattern.clone(), starts, len: words.len() });
             }
         }
-
-        let mut result: Vec<u32> = all_positions.into_iter().collect();
-        result.sort();
-        result.truncate(max_positions);
-        result
+        occurrences
     }
 
     /// Get match positions for name patterns on a specific page
@@ -1915,6 +2445,7 @@ impl SearchEngine {
         filters: &SearchFilters,
         limit: usize,
         offset: usize,
+        options: &SearchOptions,
     ) -> Result<SearchResults> {
         let start = std::time::Instant::now();
 
@@ -1928,7 +2459,7 @@ impl SearchEngine {
 
         // If no wildcard, fall back to regular search
         if !query_info.has_wildcard {
-            return self.search(query, SearchMode::Surface, filters, limit, offset);
+            return self.search(query, SearchMode::Surface, filters, limit, offset, options);
         }
 
         let reader = self
@@ -1940,137 +2471,108 @@ impl SearchEngine {
 
         let surface_field = self.schema.get_field("surface_text").unwrap();
 
+        // Expand the wildcard up front so the number of terms it matches, and the
+        // postings read to verify each page, stay capped
+        let deadline = options.budget.start();
+        let max_terms = options.budget.max_expanded_terms.unwrap_or(WILDCARD_MAX_EXPANSION);
+        let (expanded_words, expansion_cut) = self.expand_wildcard_words(&searcher, surface_field, &query_info, max_terms);
+        let expansion_truncated = expansion_cut.then_some(TruncationReason::ExpandedTerms);
+        // Verification only needs to skip words when the expansion was cut short
+        let restricted_words = Some(&expanded_words).filter(|_| expansion_cut);
+
         // Build the query based on wildcard type and position
-        let wildcard_query = self.build_wildcard_query(
-            &query_info,
-            surface_field,
-        )?;
+        let wildcard_query = self.build_wildcard_query(&query_info, surface_field, Some(&expanded_words))?;
 
         // Apply book_ids filter if provided
-        let final_query: Box<dyn Query> = if let Some(ref book_ids) = filters.book_ids {
-            if book_ids.is_empty() {
-                wildcard_query
-            } else {
-                let id_field = self.schema.get_field("text_id").unwrap();
-                let book_id_queries: Vec<(Occur, Box<dyn Query>)> = book_ids
-                    .iter()
-                    .map(|&id| {
-                        let term = Term::from_field_u64(id_field, id);
-                        let term_query: Box<dyn Query> =
-                            Box::new(TermQuery::new(term, IndexRecordOption::Basic));
-                        (Occur::Should, term_query)
-                    })
-                    .collect();
-                let book_ids_query = BooleanQuery::new(book_id_queries);
-                let combined = BooleanQuery::new(vec![
-                    (Occur::Must, wildcard_query),
-                    (Occur::Must, Box::new(book_ids_query)),
-                ]);
-                Box::new(combined)
-            }
-        } else {
-            wildcard_query
-        };
-
-        let id_field = self.schema.get_field("text_id").unwrap();
-        let part_index_field = self.schema.get_field("part_index").unwrap();
-        let page_id_field = self.schema.get_field("page_id").unwrap();
-        let author_id_field = self.schema.get_field("author_id").unwrap();
-        let genre_id_field = self.schema.get_field("genre_id").unwrap();
-        let death_ah_field = self.schema.get_field("death_ah").unwrap();
-        let century_ah_field = self.schema.get_field("century_ah").unwrap();
-        let part_label_field = self.schema.get_field("part_label").unwrap();
-        let page_number_field = self.schema.get_field("page_number").unwrap();
-        let body_field = self.schema.get_field("body").unwrap();
+        let final_query = self.with_book_filter(wildcard_query, filters);
 
         // Phase 1: Execute query to get candidate documents sorted by death_ah
         // We overfetch to account for Phase 2 filtering
         let overfetch = if query_info.terms.len() > 1 { 10 } else { 1 };
-        let (total_hits, top_docs) =
-            searcher.search(&*final_query, &(Count, TopDocs::with_limit((limit + offset) * overfetch).order_by_u64_field("death_ah", tantivy::Order::Asc)))?;
+        let (candidate_limit, candidates_capped) = options.budget.cap_candidates((limit + offset) * overfetch);
+        let after = options.cursor.as_deref().map(SortKey::from_cursor).transpose()?;
+        let (total_hits, top_docs) = search_with_control(
+            &searcher,
+            &*final_query,
+            (Count, SortKeyTopDocs::new(candidate_limit, after)),
+            &options.control,
+            &deadline,
+        )?;
+        let collection_cut = deadline.was_hit();
+        let candidates_exhausted = top_docs.len() < candidate_limit;
 
         let mut results = Vec::new();
         let mut verified_count = 0;
+        let mut last_result = None;
+        let mut last_checked = after;
+        let mut verification_cut = false;
+        // Wildcard expansions are per segment term dictionary, so compute them once per segment
+        let mut expansions: HashMap<u32, Vec<TermInfo>> = HashMap::new();
 
-        for (_sort_value, doc_address) in top_docs.into_iter() {
-            // Phase 2: For multi-word queries, verify adjacency
-            if query_info.terms.len() > 1 {
-                let segment_reader = searcher.segment_reader(doc_address.segment_ord);
+        let mut matches = |doc_address: DocAddress| {
+            let segment_reader = searcher.segment_reader(doc_address.segment_ord);
+            let wildcard_terms = expansions
+                .entry(doc_address.segment_ord)
+                .or_insert_with(|| self.expand_wildcard_terms(segment_reader, surface_field, &query_info, restricted_words));
+            self.wildcard_matches(segment_reader, doc_address.doc_id, surface_field, &query_info, wildcard_terms)
+        };
 
-                // Get positions for all terms
-                let positions = self.get_wildcard_positions(
-                    segment_reader,
-                    doc_address.doc_id,
-                    surface_field,
-                    &query_info,
-                    5,
-                );
+        let candidates = top_docs.len();
+        for (checked, (sort_key, doc_address)) in top_docs.into_iter().enumerate() {
+            options.control.check()?;
+            if checked > 0 && checked % PROGRESS_INTERVAL == 0 {
+                options.control.report(&SearchProgress { checked, candidates, hits: verified_count });
+            }
+            if deadline.expired() {
+                verification_cut = true;
+                break;
+            }
+            last_checked = Some(sort_key);
 
-                // Verify adjacency - all terms must appear consecutively
-                if !self.verify_adjacency(&positions, query_info.terms.len()) {
+            // Phase 2: For multi-word queries, verify adjacency - all terms must appear consecutively.
+            // A single word matches on every candidate, so only the pages returned read its positions.
+            let verified = if query_info.terms.len() > 1 {
+                let (matched_token_indices, match_count) = matches(doc_address);
+                if match_count == 0 {
                     continue;
                 }
-            }
+                Some((matched_token_indices, match_count))
+            } else {
+                None
+            };
 
             verified_count += 1;
             if verified_count <= offset {
                 continue;
             }
             if results.len() >= limit {
+                // A resumed page only needs to know that another hit exists
+                if after.is_some() {
+                    break;
+                }
                 continue;
             }
 
-            let doc: TantivyDocument = searcher.doc(doc_address)?;
-
-            // Get matched positions for highlighting
-            let segment_reader = searcher.segment_reader(doc_address.segment_ord);
-            let matched_token_indices = self.get_wildcard_positions(
-                segment_reader,
-                doc_address.doc_id,
-                surface_field,
-                &query_info,
-                5,
-            );
+            let (mut matched_token_indices, match_count) = verified.unwrap_or_else(|| matches(doc_address));
+            matched_token_indices.truncate(5);
 
-            let result = SearchResult {
-                id: doc
-                    .get_first(id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                part_index: doc
-                    .get_first(part_index_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                page_id: doc
-                    .get_first(page_id_field)
-                    .and_then(|v| v.as_u64())
-                    .unwrap_or(0),
-                author_id: doc.get_first(author_id_field).and_then(|v| v.as_u64()),
-                genre_id: doc.get_first(genre_id_field).and_then(|v| v.as_u64()),
-                death_ah: doc.get_first(death_ah_field).and_then(|v| v.as_u64()),
-                century_ah: doc.get_first(century_ah_field).and_then(|v| v.as_u64()),
-                part_label: doc
-                    .get_first(part_label_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                page_number: doc
-                    .get_first(page_number_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                body: doc
-                    .get_first(body_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
-                score: 0.0, // Not using relevance score when sorting by death_ah
-                matched_token_indices,
-            };
-
-            results.push(result);
+            // Not using relevance score when sorting by death_ah
+            results.push(self.extract_result(&searcher, doc_address, 0.0, matched_token_indices, match_count, options)?);
+            last_result = Some(sort_key);
         }
 
+        let more_hits = verified_count > offset + results.len();
+        let (verified_truncated, next_cursor) = verified_page_end(
+            more_hits,
+            candidates_exhausted,
+            candidates_capped,
+            collection_cut,
+            verification_cut,
+            last_result,
+            last_checked,
+        );
+        let truncated = verified_truncated.or(expansion_truncated);
+
         // Results already sorted by death_ah from Tantivy - no post-sort needed
 
         let elapsed_ms = start.elapsed().as_millis() as u64;
@@ -2081,19 +2583,28 @@ impl SearchEngine {
             total_hits: if query_info.terms.len() > 1 { verified_count } else { total_hits },
             results,
             elapsed_ms,
+            next_cursor,
+            truncated: truncated.is_some(),
+            truncated_reason: truncated,
+            pattern_hits: Vec::new(),
         })
     }
 
     /// Build a Tantivy query for wildcard search
+    /// The wildcard term uses the pre-expanded words when given, a RegexQuery otherwise.
     fn build_wildcard_query(
         &self,
         query_info: &WildcardQueryInfo,
         field: Field,
+        expanded_words: Option<&BTreeSet<String>>,
     ) -> Result<Box<dyn Query>> {
         let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
 
         for (i, term) in query_info.terms.iter().enumerate() {
-            if i == query_info.wildcard_term_index {
+            if let (true, Some(words)) = (i == query_info.wildcard_term_index, expanded_words) {
+                let terms = words.iter().map(|word| Term::from_field_text(field, word));
+                clauses.push((Occur::Must, Box::new(TermSetQuery::new(terms))));
+            } else if i == query_info.wildcard_term_index {
                 // Build regex query for wildcard term
                 // Escape special regex chars in the Arabic text
                 let escape_for_regex = |s: &str| -> String {
@@ -2140,138 +2651,730 @@ impl SearchEngine {
         }
     }
 
-    /// Get positions for wildcard query terms in a document
-    fn get_wildcard_positions(
+    /// Run any search type described by a MatchQuery
+    pub fn run_query(
         &self,
-        segment_reader: &SegmentReader,
-        doc_id: u32,
-        field: Field,
-        query_info: &WildcardQueryInfo,
-        max_positions: usize,
-    ) -> Vec<u32> {
-        let mut all_positions: Vec<u32> = Vec::new();
+        query: &MatchQuery,
+        filters: &SearchFilters,
+        limit: usize,
+        offset: usize,
+        options: &SearchOptions,
+    ) -> Result<SearchResults> {
+        match query {
+            MatchQuery::Simple { query, mode } => self.search(query, *mode, filters, limit, offset, options),
+            MatchQuery::Combined { and_terms, or_terms } => {
+                self.combined_search(and_terms, or_terms, filters, limit, offset, options)
+            }
+            MatchQuery::Proximity { term1, term2, distance } => {
+                self.proximity_search(term1, term2, *distance, filters, limit, offset, options)
+            }
+            MatchQuery::Name { forms } => self.name_search(forms, filters, limit, offset, options),
+            MatchQuery::Wildcard { query } => self.wildcard_search(query, filters, limit, offset, options),
+        }
+    }
 
-        let Ok(inverted_index) = segment_reader.inverted_index(field) else {
-            return all_positions;
+    /// Get every match position of a query on a specific page, paged by offset/limit.
+    /// Search results only carry a short preview; this returns the full list for any search type.
+    pub fn get_all_match_positions(
+        &self,
+        id: u64,
+        part_index: u64,
+        page_id: u64,
+        query: &MatchQuery,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<MatchPositions> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let Some((_score, doc_address)) = self.find_page_doc(&searcher, id, part_index, page_id)? else {
+            return Ok(MatchPositions { match_count: 0, total_positions: 0, offset, positions: Vec::new() });
         };
+        let segment_reader = searcher.segment_reader(doc_address.segment_ord);
+        let doc_id = doc_address.doc_id;
+        let surface_field = self.schema.get_field("surface_text").unwrap();
 
-        // For non-wildcard terms, get exact positions
-        for (i, term_str) in query_info.terms.iter().enumerate() {
-            if i == query_info.wildcard_term_index {
-                continue; // Handle wildcard term separately below
+        let (positions, match_count) = match query {
+            MatchQuery::Simple { query, mode } => {
+                self.term_matches(segment_reader, doc_id, &SearchTerm { query: query.clone(), mode: *mode })
+            }
+            MatchQuery::Combined { and_terms, or_terms } => {
+                let mut all_positions: HashSet<u32> = HashSet::new();
+                let mut match_count = 0;
+                for term in and_terms.iter().chain(or_terms.iter()) {
+                    let (positions, count) = self.term_matches(segment_reader, doc_id, term);
+                    all_positions.extend(positions);
+                    match_count += count;
+                }
+                let mut positions: Vec<u32> = all_positions.into_iter().collect();
+                positions.sort_unstable();
+                (positions, match_count)
+            }
+            MatchQuery::Proximity { term1, term2, distance } => {
+                self.proximity_matches(segment_reader, doc_id, term1, term2, *distance)
+            }
+            MatchQuery::Name { forms } => {
+                let patterns: Vec<String> = forms.iter().map(NameSearchForm::search_patterns).collect::<Result<Vec<_>>>()?.concat();
+                self.name_pattern_matches(segment_reader, doc_id, surface_field, &patterns)
+            }
+            MatchQuery::Wildcard { query } => {
+                let query_info = parse_wildcard_query(&normalize_arabic(query));
+                if query_info.has_wildcard {
+                    let wildcard_terms = self.expand_wildcard_terms(segment_reader, surface_field, &query_info, None);
+                    self.wildcard_matches(segment_reader, doc_id, surface_field, &query_info, &wildcard_terms)
+                } else {
+                    // Same fallback to a plain surface search as wildcard_search
+                    self.term_matches(segment_reader, doc_id, &SearchTerm { query: query.clone(), mode: SearchMode::Surface })
+                }
             }
+        };
 
-            let term = Term::from_field_text(field, term_str);
-            let Ok(Some(mut postings)) = inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) else {
-                continue;
+        let total_positions = positions.len();
+        let positions: Vec<u32> = positions
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(MatchPositions { match_count, total_positions, offset, positions })
+    }
+
+    /// Start positions of a search term on the first `max_pages` pages containing it, in death_ah order
+    pub fn term_occurrences(&self, term: &SearchTerm, filters: &SearchFilters, max_pages: usize) -> Result<TermOccurrences> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let query = self.with_book_filter(self.build_term_query(term)?, filters);
+        let (total_pages, top_docs) = searcher.search(&*query, &(Count, SortKeyTopDocs::new(max_pages, None)))?;
+
+        let field = self.get_search_field(term.mode);
+        let phrase_words = self.extract_phrase_terms(term);
+        let query_terms = self.extract_query_terms(term);
+        let mut pages = Vec::with_capacity(top_docs.len());
+        for (sort_key, doc_address) in top_docs {
+            let segment_reader = searcher.segment_reader(doc_address.segment_ord);
+            let starts = if phrase_words.len() > 1 {
+                self.get_phrase_starts(segment_reader, doc_address.doc_id, field, &phrase_words)
+            } else {
+                self.get_matched_positions_internal(segment_reader, doc_address.doc_id, field, &query_terms, None, usize::MAX)
             };
+            pages.push(PageOccurrences {
+                id: sort_key.text_id,
+                part_index: sort_key.part_index,
+                page_id: sort_key.page_id,
+                starts,
+            });
+        }
+
+        Ok(TermOccurrences { total_pages, term_len: phrase_words.len().max(1), pages })
+    }
+
+    /// The first `max_pages` pages where some pattern of every form occurs (as in name search),
+    /// in death_ah order, with the total number of such pages
+    pub fn pattern_pages(&self, patterns_by_form: &[Vec<String>], filters: &SearchFilters, max_pages: usize) -> Result<(usize, Vec<SortKey>)> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+
+        let Some(pattern_query) = self.build_name_query(patterns_by_form) else {
+            return Ok((0, Vec::new()));
+        };
+        let query = self.with_book_filter(pattern_query, filters);
+        let (total_pages, top_docs) = searcher.search(&*query, &(Count, SortKeyTopDocs::new(max_pages, None)))?;
+        Ok((total_pages, top_docs.into_iter().map(|(sort_key, _)| sort_key).collect()))
+    }
+
+    /// Pages, books and occurrences of a name (any of its patterns) under the book filter
+    pub fn count_name_mentions(&self, patterns: &[String], filters: &SearchFilters, control: &SearchControl) -> Result<NameMentions> {
+        let occurrences = self.name_occurrences(patterns, filters, usize::MAX, control)?;
+        let books: HashSet<u64> = occurrences.pages.iter().map(|(key, _)| key.text_id).collect();
+        let mentions = occurrences.pages.iter().map(|(_, starts)| starts.len()).sum();
+        Ok(NameMentions { pages: occurrences.total_pages, books: books.len(), mentions })
+    }
 
-            let current_doc = postings.doc();
-            if current_doc == tantivy::TERMINATED || current_doc > doc_id {
+    /// Pages where a name (any of its patterns) occurs under the book filter, in result order,
+    /// with the token positions where it starts; at most `max_pages` of them
+    pub fn name_occurrences(
+        &self,
+        patterns: &[String],
+        filters: &SearchFilters,
+        max_pages: usize,
+        control: &SearchControl,
+    ) -> Result<NameOccurrences> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let surface_field = self.schema.get_field("surface_text").unwrap();
+
+        let Some(name_query) = self.build_name_query(&[patterns.to_vec()]) else {
+            return Ok(NameOccurrences { total_pages: 0, pages: Vec::new() });
+        };
+        let query = self.with_book_filter(name_query, filters);
+        let fruit = searcher.search(&*query, &control.collector((Count, SortKeyTopDocs::new(max_pages, None))));
+        control.check()?;
+        let (total_pages, docs) = fruit?;
+
+        let mut pages = Vec::with_capacity(docs.len());
+        for (sort_key, doc_address) in docs {
+            control.check()?;
+            let found = self.name_pattern_occurrences(
+                searcher.segment_reader(doc_address.segment_ord),
+                doc_address.doc_id,
+                surface_field,
+                patterns.iter().map(|pattern| (0, pattern)),
+            );
+            let mut starts: Vec<u32> = found.into_iter().flat_map(|occurrence| occurrence.starts).collect();
+            starts.sort_unstable();
+            starts.dedup();
+            pages.push((sort_key, starts));
+        }
+
+        Ok(NameOccurrences { total_pages, pages })
+    }
+
+    /// Number of tokens in a mode's index field and the total occurrences of each term in it.
+    /// The term info gives the document frequency but not the total, so the frequencies
+    /// are summed a block at a time from the term's postings, skipping the doc IDs.
+    pub fn term_frequencies(&self, mode: SearchMode, terms: &[String]) -> Result<(u64, Vec<u64>)> {
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let field = self.get_search_field(mode);
+
+        let mut total_tokens = 0;
+        let mut frequencies = vec![0u64; terms.len()];
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            total_tokens += inverted_index.total_num_tokens();
+            for (frequency, term) in frequencies.iter_mut().zip(terms) {
+                let Some(term_info) = inverted_index.get_term_info(&Term::from_field_text(field, term))? else {
+                    continue;
+                };
+                let mut blocks = inverted_index.read_block_postings_from_terminfo(&term_info, IndexRecordOption::WithFreqs)?;
+                while !blocks.docs().is_empty() {
+                    *frequency += blocks.freqs().iter().map(|&freq| freq as u64).sum::<u64>();
+                    blocks.advance();
+                }
+            }
+        }
+
+        Ok((total_tokens, frequencies))
+    }
+
+    /// Other places where a selected span of a page is quoted or paraphrased.
+    /// Pages sharing enough of the span's rarest lemmas are aligned with the span
+    /// over their lemma tokens and ranked by alignment score.
+    pub fn find_similar_passages(&self, token_cache: &TokenCache, request: &SimilarPassagesRequest) -> Result<SimilarPassages> {
+        let start = std::time::Instant::now();
+        let options = &request.options;
+
+        let page_tokens = token_cache.get(&PageKey::new(request.book_id, request.page_id))?;
+        let span_start = request.token_range.start.min(page_tokens.len());
+        let span_end = request.token_range.end.clamp(span_start, page_tokens.len()).min(span_start + SIMILAR_MAX_SPAN);
+        let span: Vec<AlignedToken<&str>> = page_tokens[span_start..span_end].iter().map(aligned_lemma).collect();
+
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let lemma_field = self.get_search_field(SearchMode::Lemma);
+
+        // Rarest lemmas first; lemmas missing from the index can't find anything
+        let mut lemmas: Vec<(u64, &str)> = Vec::new();
+        for lemma in span.iter().filter_map(|token| token.0).collect::<BTreeSet<_>>() {
+            let doc_freq = searcher.doc_freq(&Term::from_field_text(lemma_field, lemma))?;
+            if doc_freq > 0 {
+                lemmas.push((doc_freq, lemma));
+            }
+        }
+        lemmas.sort();
+        lemmas.truncate(options.query_lemmas.max(1));
+        let query_lemmas: Vec<String> = lemmas.iter().map(|(_, lemma)| lemma.to_string()).collect();
+        if query_lemmas.is_empty() {
+            return Ok(SimilarPassages { query_lemmas, candidates: 0, passages: Vec::new(), elapsed_ms: start.elapsed().as_millis() as u64 });
+        }
+
+        let clauses: Vec<(Occur, Box<dyn Query>)> = query_lemmas
+            .iter()
+            .map(|lemma| {
+                let term = Term::from_field_text(lemma_field, lemma);
+                (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)) as Box<dyn Query>)
+            })
+            .collect();
+        let required = ((query_lemmas.len() as f64 * options.min_lemma_share).ceil() as usize).clamp(1, query_lemmas.len());
+        let text_query: Box<dyn Query> = Box::new(BooleanQuery::with_minimum_required_clauses(clauses, required));
+        let query = self.with_book_filter(text_query, &request.filters);
+        let top_docs = searcher.search(&*query, &TopDocs::with_limit(options.max_candidates.max(1)))?;
+
+        let mut passages = Vec::new();
+        for (score, doc_address) in &top_docs {
+            let mut result = self.extract_result(&searcher, *doc_address, *score, Vec::new(), 0, &SearchOptions::default())?;
+            let is_source_page = result.id == request.book_id && result.page_id == request.page_id;
+            if is_source_page && !options.include_source_page {
                 continue;
             }
 
-            if current_doc == doc_id {
-                let mut pos_buffer: Vec<u32> = Vec::new();
-                postings.positions(&mut pos_buffer);
-                all_positions.extend(pos_buffer);
-            } else if postings.seek(doc_id) == doc_id {
-                let mut pos_buffer: Vec<u32> = Vec::new();
-                postings.positions(&mut pos_buffer);
-                all_positions.extend(pos_buffer);
+            let tokens = token_cache.get(&PageKey::new(result.id, result.page_id))?;
+            let target: Vec<AlignedToken<&str>> = tokens.iter().map(aligned_lemma).collect();
+            let Some(alignment) = align(&span, &target) else {
+                continue;
+            };
+            let similarity = similarity(&alignment, span.len());
+            // The selection always aligns perfectly with itself
+            let is_selection = is_source_page && alignment.target_range.start == span_start + alignment.source_range.start;
+            if similarity < options.min_similarity || is_selection {
+                continue;
             }
+
+            result.matched_token_indices = alignment.pairs.iter().map(|&(_, target)| target as u32).collect();
+            result.match_count = alignment.pairs.len();
+            passages.push(SimilarPassage {
+                result,
+                similarity,
+                target_range: alignment.target_range,
+                source_range: span_start + alignment.source_range.start..span_start + alignment.source_range.end,
+                aligned_tokens: alignment.pairs.iter().map(|&(source, target)| (span_start + source, target)).collect(),
+            });
         }
 
-        // For wildcard term, scan the term dictionary to find matching terms
-        if query_info.has_wildcard {
-            let prefix = &query_info.prefix;
-            let suffix = query_info.suffix.as_deref();
+        passages.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| a.result.death_ah.unwrap_or(u64::MAX).cmp(&b.result.death_ah.unwrap_or(u64::MAX)))
+        });
+        passages.truncate(options.limit);
 
-            // Get the term dictionary and iterate through terms starting with prefix
-            let term_dict = inverted_index.terms();
-            let prefix_bytes = prefix.as_bytes();
+        Ok(SimilarPassages { query_lemmas, candidates: top_docs.len(), passages, elapsed_ms: start.elapsed().as_millis() as u64 })
+    }
 
-            // Create a range starting from the prefix
-            let mut term_stream = term_dict.range().ge(prefix_bytes).into_stream().unwrap();
+    /// Report how a search is normalized, which Tantivy query it runs, how often its
+    /// terms occur and how long each phase of the report takes. With a page, also
+    /// list where each term matches on it.
+    pub fn explain(&self, request: &ExplainRequest) -> Result<QueryExplanation> {
+        let mut timings = Vec::new();
+        let mut phase_start = std::time::Instant::now();
+        let mut end_phase = |phase: &str, timings: &mut Vec<PhaseTiming>| {
+            timings.push(PhaseTiming { phase: phase.to_string(), micros: phase_start.elapsed().as_micros() as u64 });
+            phase_start = std::time::Instant::now();
+        };
 
-            while term_stream.advance() {
-                let term_bytes = term_stream.key();
+        let reader = self
+            .index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        let searcher = reader.searcher();
+        let surface_field = self.schema.get_field("surface_text").unwrap();
 
-                // Check if term still starts with our prefix
-                if !term_bytes.starts_with(prefix_bytes) {
-                    break; // Past our prefix range
+        let explain_term = |role: &str, term: &SearchTerm| {
+            let normalized = match term.mode {
+                SearchMode::Root => normalize_root_query(&term.query),
+                SearchMode::Surface => normalize_arabic(&term.query),
+                SearchMode::Lemma => term.query.clone(),
+            };
+            let mut words: Vec<&str> = Vec::new();
+            for word in normalized.split_whitespace() {
+                if !words.contains(&word) {
+                    words.push(word);
                 }
+            }
+            TermExplanation {
+                role: role.to_string(),
+                query: term.query.clone(),
+                mode: term.mode,
+                field: self.schema.get_field_name(self.get_search_field(term.mode)).to_string(),
+                terms: words.iter().map(|word| TermFrequency { term: word.to_string(), doc_freq: 0 }).collect(),
+                normalized,
+                expanded_terms: None,
+            }
+        };
 
-                // Convert to string for suffix check
-                let Ok(term_str) = std::str::from_utf8(term_bytes) else {
-                    continue;
-                };
+        // Wildcard words in full, for matching them on the page
+        let mut expanded_words: Option<BTreeSet<String>> = None;
+        let mut terms: Vec<TermExplanation> = Vec::new();
+        let text_query: Option<Box<dyn Query>> = match &request.query {
+            MatchQuery::Simple { query, mode } => {
+                let term = explain_term("query", &SearchTerm { query: query.clone(), mode: *mode });
+                let field = self.get_search_field(*mode);
+                let text_query = self.build_simple_query(&term.normalized, field, term.terms.len() > 1)?;
+                terms.push(term);
+                Some(text_query)
+            }
+            MatchQuery::Combined { and_terms, or_terms } => {
+                terms.extend(and_terms.iter().map(|term| explain_term("and", term)));
+                terms.extend(or_terms.iter().map(|term| explain_term("or", term)));
+                if and_terms.is_empty() && or_terms.is_empty() {
+                    None
+                } else {
+                    Some(self.build_combined_query(and_terms, or_terms)?)
+                }
+            }
+            MatchQuery::Proximity { term1, term2, .. } => {
+                terms.push(explain_term("term1", term1));
+                terms.push(explain_term("term2", term2));
+                Some(Box::new(BooleanQuery::new(vec![
+                    (Occur::Must, self.build_term_query(term1)?),
+                    (Occur::Must, self.build_term_query(term2)?),
+                ])))
+            }
+            MatchQuery::Name { forms } => {
+                let forms: Vec<Vec<String>> = forms.iter().map(NameSearchForm::search_patterns).collect::<Result<_>>()?;
+                for (i, patterns) in forms.iter().enumerate() {
+                    let role = format!("form {}", i + 1);
+                    terms.extend(patterns.iter().map(|pattern| {
+                        explain_term(&role, &SearchTerm { query: pattern.clone(), mode: SearchMode::Surface })
+                    }));
+                }
+                self.build_name_query(&forms)
+            }
+            MatchQuery::Wildcard { query } => {
+                let query_info = parse_wildcard_query(&normalize_arabic(query));
+                if query_info.has_wildcard {
+                    // Expand as far as a search with the default budget would
+                    let max_terms = SearchBudget::default().max_expanded_terms.unwrap_or(WILDCARD_MAX_EXPANSION);
+                    let (words, _) = self.expand_wildcard_words(&searcher, surface_field, &query_info, max_terms);
+                    for (i, word) in query_info.terms.iter().enumerate() {
+                        let role = format!("word {}", i + 1);
+                        let mut term = explain_term(&role, &SearchTerm { query: word.clone(), mode: SearchMode::Surface });
+                        if i == query_info.wildcard_term_index {
+                            term.terms = words
+                                .iter()
+                                .take(EXPLAIN_MAX_EXPANSIONS)
+                                .map(|word| TermFrequency { term: word.clone(), doc_freq: 0 })
+                                .collect();
+                            term.expanded_terms = Some(words.len());
+                        }
+                        terms.push(term);
+                    }
+                    let text_query = self.build_wildcard_query(&query_info, surface_field, Some(&words))?;
+                    expanded_words = Some(words);
+                    Some(text_query)
+                } else {
+                    // Same fallback to a plain surface search as wildcard_search
+                    let term = explain_term("query", &SearchTerm { query: query.clone(), mode: SearchMode::Surface });
+                    let text_query = self.build_simple_query(&term.normalized, surface_field, term.terms.len() > 1)?;
+                    terms.push(term);
+                    Some(text_query)
+                }
+            }
+        };
+        let final_query = text_query.map(|text_query| self.with_book_filter(text_query, &request.filters));
+        end_phase("parse", &mut timings);
 
-                // For internal wildcards (أح*مد), check suffix
-                let matches = match suffix {
-                    Some(suf) => term_str.ends_with(suf),
-                    None => true, // Prefix-only wildcard matches anything starting with prefix
-                };
+        for term in &mut terms {
+            let field = self.schema.get_field(&term.field)?;
+            for frequency in &mut term.terms {
+                frequency.doc_freq = searcher.doc_freq(&Term::from_field_text(field, &frequency.term))?;
+            }
+        }
+        end_phase("doc_freqs", &mut timings);
 
-                if !matches {
-                    continue;
+        let candidate_hits = match &final_query {
+            Some(query) => searcher.search(&**query, &Count)?,
+            None => 0,
+        };
+        end_phase("count_candidates", &mut timings);
+
+        let page = match &request.page {
+            Some(page) => {
+                let page_doc = self.find_page_doc(&searcher, page.id, page.part_index, page.page_id)?;
+                let mut term_positions = Vec::new();
+                let mut matches_query = false;
+                if let Some((_score, doc_address)) = page_doc {
+                    let segment_reader = searcher.segment_reader(doc_address.segment_ord);
+                    matches_query = final_query
+                        .as_ref()
+                        .is_some_and(|query| query.explain(&searcher, doc_address).is_ok());
+                    for term in &terms {
+                        let field = self.schema.get_field(&term.field)?;
+                        if let (Some(words), Some(_)) = (&expanded_words, term.expanded_terms) {
+                            // All expansions of the wildcard word together
+                            let words: HashSet<String> = words.iter().cloned().collect();
+                            term_positions.push(TermPositions {
+                                role: term.role.clone(),
+                                term: term.normalized.clone(),
+                                positions: self.get_matched_positions_internal(segment_reader, doc_address.doc_id, field, &words, None, usize::MAX),
+                            });
+                            continue;
+                        }
+                        for frequency in &term.terms {
+                            let words = HashSet::from([frequency.term.clone()]);
+                            term_positions.push(TermPositions {
+                                role: term.role.clone(),
+                                term: frequency.term.clone(),
+                                positions: self.get_matched_positions_internal(segment_reader, doc_address.doc_id, field, &words, None, usize::MAX),
+                            });
+                        }
+                    }
                 }
+                let matches = self.get_all_match_positions(page.id, page.part_index, page.page_id, &request.query, 0, None)?;
+                end_phase("page", &mut timings);
+                Some(PageExplanation {
+                    id: page.id,
+                    part_index: page.part_index,
+                    page_id: page.page_id,
+                    found: page_doc.is_some(),
+                    matches_query,
+                    terms: term_positions,
+                    matches,
+                })
+            }
+            None => None,
+        };
 
-                // This term matches our wildcard pattern - get its positions
-                let term = Term::from_field_text(field, term_str);
-                let Ok(Some(mut postings)) = inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) else {
-                    continue;
-                };
+        let filters = &request.filters;
+        let ignored = [
+            ("author_id", filters.author_id.is_some()),
+            ("genre_id", filters.genre_id.is_some()),
+            ("death_ah_min", filters.death_ah_min.is_some()),
+            ("death_ah_max", filters.death_ah_max.is_some()),
+            ("century_ah", filters.century_ah.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| name.to_string())
+        .collect();
 
-                let current_doc = postings.doc();
-                if current_doc == tantivy::TERMINATED || current_doc > doc_id {
-                    continue;
-                }
+        Ok(QueryExplanation {
+            terms,
+            query_tree: final_query.map(|query| format!("{:#?}", query)).unwrap_or_default(),
+            filters: ExplainedFilters {
+                book_ids: filters.book_ids.clone().filter(|ids| !ids.is_empty()),
+                ignored,
+            },
+            candidate_hits,
+            explain_timings: timings,
+            page,
+        })
+    }
 
-                if current_doc == doc_id {
-                    let mut pos_buffer: Vec<u32> = Vec::new();
-                    postings.positions(&mut pos_buffer);
-                    all_positions.extend(pos_buffer);
-                } else if postings.seek(doc_id) == doc_id {
-                    let mut pos_buffer: Vec<u32> = Vec::new();
-                    postings.positions(&mut pos_buffer);
-                    all_positions.extend(pos_buffer);
-                }
+    /// Visit the dictionary terms of one segment matching the wildcard term (prefix + optional
+    /// suffix), in term order. Stops early when `visit` returns false.
+    fn visit_wildcard_terms(
+        &self,
+        segment_reader: &SegmentReader,
+        field: Field,
+        query_info: &WildcardQueryInfo,
+        mut visit: impl FnMut(&[u8], &TermInfo) -> bool,
+    ) {
+        if !query_info.has_wildcard {
+            return;
+        }
 
-                // Limit scanning to avoid performance issues
-                if all_positions.len() >= max_positions * 10 {
-                    break;
-                }
+        let Ok(inverted_index) = segment_reader.inverted_index(field) else {
+            return;
+        };
+
+        let prefix_bytes = query_info.prefix.as_bytes();
+        let suffix = query_info.suffix.as_deref();
+
+        // Iterate through terms starting from the prefix
+        let Ok(mut term_stream) = inverted_index.terms().range().ge(prefix_bytes).into_stream() else {
+            return;
+        };
+
+        while term_stream.advance() {
+            let term_bytes = term_stream.key();
+
+            // Check if term still starts with our prefix
+            if !term_bytes.starts_with(prefix_bytes) {
+                break; // Past our prefix range
+            }
+
+            // For internal wildcards (أح*مد), check suffix
+            let matches = match suffix {
+                Some(suf) => std::str::from_utf8(term_bytes).is_ok_and(|term_str| term_str.ends_with(suf)),
+                None => true, // Prefix-only wildcard matches anything starting with prefix
+            };
+
+            if matches && !visit(term_bytes, term_stream.value()) {
+                break;
             }
         }
+    }
 
-        all_positions.sort_unstable();
-        all_positions.dedup();
-        all_positions.truncate(max_positions);
-        all_positions
+    /// Find the dictionary terms of one segment matching the wildcard term,
+    /// optionally restricted to an already expanded word list
+    fn expand_wildcard_terms(
+        &self,
+        segment_reader: &SegmentReader,
+        field: Field,
+        query_info: &WildcardQueryInfo,
+        allowed: Option<&BTreeSet<String>>,
+    ) -> Vec<TermInfo> {
+        let mut term_infos: Vec<TermInfo> = Vec::new();
+        self.visit_wildcard_terms(segment_reader, field, query_info, |term_bytes, term_info| {
+            let is_allowed = match allowed {
+                Some(words) => std::str::from_utf8(term_bytes).is_ok_and(|word| words.contains(word)),
+                None => true,
+            };
+            if is_allowed {
+                term_infos.push(term_info.clone());
+            }
+            true
+        });
+        term_infos
     }
 
-    /// Verify that positions form a consecutive sequence (for phrase-like queries)
-    fn verify_adjacency(&self, positions: &[u32], num_terms: usize) -> bool {
-        if positions.is_empty() || num_terms <= 1 {
-            return true;
+    /// Expand the wildcard term over all segments into at most `max_terms` words
+    /// (the first in term order). The flag is set when more words matched.
+    fn expand_wildcard_words(
+        &self,
+        searcher: &Searcher,
+        field: Field,
+        query_info: &WildcardQueryInfo,
+        max_terms: usize,
+    ) -> (BTreeSet<String>, bool) {
+        let mut words: BTreeSet<String> = BTreeSet::new();
+        let mut truncated = false;
+
+        for segment_reader in searcher.segment_readers() {
+            self.visit_wildcard_terms(segment_reader, field, query_info, |term_bytes, _| {
+                let Ok(word) = std::str::from_utf8(term_bytes) else {
+                    return true;
+                };
+                // Terms come in order, so once the set is full nothing later in this segment fits
+                if words.len() >= max_terms && words.last().is_some_and(|last| word > last.as_str()) {
+                    truncated = true;
+                    return false;
+                }
+                words.insert(word.to_string());
+                if words.len() > max_terms {
+                    words.pop_last();
+                    truncated = true;
+                }
+                true
+            });
         }
 
-        // For multi-word wildcard queries, check if we have consecutive positions
-        // This is a simplified check - positions should include indices like N, N+1, N+2...
-        for window in positions.windows(num_terms) {
-            let mut is_consecutive = true;
-            for i in 1..window.len() {
-                if window[i] != window[i - 1] + 1 {
-                    is_consecutive = false;
-                    break;
-                }
+        (words, truncated)
+    }
+
+    /// All match positions of a wildcard query on one page, plus its occurrence count.
+    /// Multi-word queries only match where every term appears consecutively.
+    fn wildcard_matches(
+        &self,
+        segment_reader: &SegmentReader,
+        doc_id: u32,
+        field: Field,
+        query_info: &WildcardQueryInfo,
+        wildcard_terms: &[TermInfo],
+    ) -> (Vec<u32>, usize) {
+        let Ok(inverted_index) = segment_reader.inverted_index(field) else {
+            return (Vec::new(), 0);
+        };
+
+        // The exact words first: a page without one of them needs no expansion postings
+        let mut term_positions: Vec<Vec<u32>> = vec![Vec::new(); query_info.terms.len()];
+        for (i, term_str) in query_info.terms.iter().enumerate() {
+            if i == query_info.wildcard_term_index {
+                continue;
+            }
+            let term = Term::from_field_text(field, term_str);
+            term_positions[i] = match inverted_index.read_postings(&term, IndexRecordOption::WithFreqsAndPositions) {
+                Ok(Some(postings)) => doc_positions(postings, doc_id).unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            if term_positions[i].is_empty() {
+                return (Vec::new(), 0);
             }
-            if is_consecutive {
-                return true;
+        }
+
+        // Union of the positions of every expanded term
+        let merged = &mut term_positions[query_info.wildcard_term_index];
+        for term_info in wildcard_terms {
+            let Ok(postings) = inverted_index.read_postings_from_terminfo(term_info, IndexRecordOption::WithFreqsAndPositions) else {
+                continue;
+            };
+            if let Some(positions) = doc_positions(postings, doc_id) {
+                merged.extend(positions);
             }
         }
+        merged.sort_unstable();
+        merged.dedup();
+        if merged.is_empty() {
+            return (Vec::new(), 0);
+        }
+
+        let starts = consecutive_starts(&term_positions);
+        (phrase_positions_from_starts(&starts, query_info.terms.len()), starts.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::doc;
+
+    /// One book with a gap in its parts: part 0 has pages 1-3 and part 2 pages 4-6,
+    /// printed from 1 in each part, with page 5 missing
+    fn engine(page_number_options: TextOptions) -> SearchEngine {
+        let mut builder = Schema::builder();
+        let [text_id, part_index, page_id, death_ah] =
+            ["text_id", "part_index", "page_id", "death_ah"].map(|name| builder.add_u64_field(name, INDEXED | STORED | FAST));
+        for name in ["author_id", "genre_id", "century_ah"] {
+            builder.add_u64_field(name, INDEXED | STORED | FAST);
+        }
+        let part_label = builder.add_text_field("part_label", STRING | STORED);
+        let page_number = builder.add_text_field("page_number", page_number_options);
+        let body = builder.add_text_field("body", STORED);
+        let index = Index::create_in_ram(builder.build());
+
+        let mut writer = index.writer_with_num_threads(1, 15_000_000).unwrap();
+        for (part, label, page, number) in [(0u64, "01", 1u64, "1"), (0, "01", 2, "2"), (0, "01", 3, "3"), (2, "03", 4, "01"), (2, "03", 6, "3")] {
+            writer
+                .add_document(doc!(text_id => 7u64, part_index => part, page_id => page, death_ah => 310u64, part_label => label, page_number => number, body => ""))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        let schema = index.schema();
+        SearchEngine { index, schema, result_cache: ResultCache::new(RESULT_CACHE_CAPACITY) }
+    }
 
-        false
+    #[test]
+    fn test_page_navigation() {
+        for page_number_options in [STRING | STORED, TextOptions::from(STORED)] {
+            let engine = engine(page_number_options);
+            let adjacent = |part_index: u64, page_id: u64, direction: PageDirection| {
+                engine.adjacent_page(7, part_index, page_id, direction).unwrap().map(|page| (page.part_index, page.page_id))
+            };
+            assert_eq!(adjacent(0, 2, PageDirection::Next), Some((0, 3)));
+            // Across the part boundary in both directions, skipping the missing part
+            assert_eq!(adjacent(0, 3, PageDirection::Next), Some((2, 4)));
+            assert_eq!(adjacent(2, 4, PageDirection::Previous), Some((0, 3)));
+            // Over the missing page, and from a page that does not exist
+            assert_eq!(adjacent(2, 4, PageDirection::Next), Some((2, 6)));
+            assert_eq!(adjacent(2, 5, PageDirection::Previous), Some((2, 4)));
+            assert_eq!(adjacent(0, 1, PageDirection::Previous), None);
+            assert_eq!(adjacent(2, 6, PageDirection::Next), None);
+
+            let by_number = |part_label: Option<&str>, page_number: &str| {
+                engine.page_by_number(7, part_label, page_number).unwrap().map(|page| (page.part_index, page.page_id))
+            };
+            assert_eq!(by_number(None, "3"), Some((0, 3)));
+            assert_eq!(by_number(Some("3"), "3"), Some((2, 6)));
+            assert_eq!(by_number(Some("03"), "1"), Some((2, 4)));
+            assert_eq!(by_number(Some("01"), "002"), Some((0, 2)));
+            assert_eq!(by_number(Some("2"), "1"), None);
+        }
     }
 }
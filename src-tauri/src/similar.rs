@@ -0,0 +1,175 @@
+//! Similar passages for a selected span of text
+//!
+//! Candidates come from a lemma query over the span's rarest lemmas (see
+//! SearchEngine::find_similar_passages); each is re-ranked by local alignment
+//! (Smith-Waterman) of the span's lemmas against the candidate page's, which
+//! tolerates the insertions, omissions and reorderings of a paraphrase.
+
+use crate::search::{SearchFilters, SearchResult};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Alignment scores: equal lemmas, different lemmas, skipped token
+const MATCH_SCORE: i32 = 2;
+const MISMATCH_SCORE: i32 = -1;
+const GAP_SCORE: i32 = -1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarPassagesRequest {
+    pub book_id: u64,
+    pub page_id: u64,
+    /// Selected tokens of the page, as indexed by get_page_tokens
+    pub token_range: Range<usize>,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    #[serde(default)]
+    pub options: SimilarPassagesOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimilarPassagesOptions {
+    /// Rarest lemmas of the span used to find candidates
+    pub query_lemmas: usize,
+    /// Fraction of those lemmas a candidate page must contain
+    pub min_lemma_share: f64,
+    /// Candidate pages aligned against the span
+    pub max_candidates: usize,
+    /// Minimum alignment score relative to a perfect copy of the span
+    pub min_similarity: f64,
+    pub limit: usize,
+    /// Also report other places on the selected page itself
+    pub include_source_page: bool,
+}
+
+impl Default for SimilarPassagesOptions {
+    fn default() -> Self {
+        Self {
+            query_lemmas: 8,
+            min_lemma_share: 0.5,
+            max_candidates: 300,
+            min_similarity: 0.3,
+            limit: 50,
+            include_source_page: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarPassage {
+    /// The candidate page; matched_token_indices holds its aligned tokens
+    pub result: SearchResult,
+    /// Alignment score relative to a perfect copy of the span (0–1)
+    pub similarity: f64,
+    /// Aligned range on the candidate page
+    pub target_range: Range<usize>,
+    /// Aligned range within the selected page
+    pub source_range: Range<usize>,
+    /// Pairs of (selected page token, candidate page token) with equal lemmas
+    pub aligned_tokens: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarPassages {
+    /// Lemmas the candidate query was built from, rarest first
+    pub query_lemmas: Vec<String>,
+    pub candidates: usize,
+    pub passages: Vec<SimilarPassage>,
+    pub elapsed_ms: u64,
+}
+
+/// Best local alignment of two token sequences (lemmas, or surface hashes for text reuse)
+#[derive(Debug, Clone)]
+pub(crate) struct Alignment {
+    pub score: i32,
+    pub source_range: Range<usize>,
+    pub target_range: Range<usize>,
+    pub pairs: Vec<(usize, usize)>,
+}
+
+/// A token to align: lemma or surface hash. Tokens without one (unknown lemma,
+/// no surface) match nothing, not even each other.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AlignedToken<T>(pub Option<T>);
+
+impl<T: PartialEq> PartialEq for AlignedToken<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.is_some() && self.0 == other.0
+    }
+}
+
+/// Smith-Waterman alignment of `source` against `target`. None if nothing matches.
+pub(crate) fn align<T: PartialEq>(source: &[T], target: &[T]) -> Option<Alignment> {
+    let width = target.len() + 1;
+    let mut scores = vec![0i32; (source.len() + 1) * width];
+    let mut best = (0, 0, 0);
+    for i in 1..=source.len() {
+        for j in 1..=target.len() {
+            let pair = if source[i - 1] == target[j - 1] { MATCH_SCORE } else { MISMATCH_SCORE };
+            let score = (scores[(i - 1) * width + j - 1] + pair)
+                .max(scores[(i - 1) * width + j] + GAP_SCORE)
+                .max(scores[i * width + j - 1] + GAP_SCORE)
+                .max(0);
+            scores[i * width + j] = score;
+            if score > best.0 {
+                best = (score, i, j);
+            }
+        }
+    }
+    if best.0 == 0 {
+        return None;
+    }
+
+    let (score, mut i, mut j) = best;
+    let (source_end, target_end) = (i, j);
+    let mut pairs = Vec::new();
+    while i > 0 && j > 0 && scores[i * width + j] > 0 {
+        let current = scores[i * width + j];
+        let pair = if source[i - 1] == target[j - 1] { MATCH_SCORE } else { MISMATCH_SCORE };
+        if current == scores[(i - 1) * width + j - 1] + pair {
+            if pair == MATCH_SCORE {
+                pairs.push((i - 1, j - 1));
+            }
+            i -= 1;
+            j -= 1;
+        } else if current == scores[(i - 1) * width + j] + GAP_SCORE {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    pairs.reverse();
+
+    Some(Alignment { score, source_range: i..source_end, target_range: j..target_end, pairs })
+}
+
+/// Alignment score relative to that of the span aligned with itself
+pub(crate) fn similarity(alignment: &Alignment, span_len: usize) -> f64 {
+    alignment.score as f64 / (MATCH_SCORE as f64 * span_len.max(1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_paraphrase() {
+        let source = ["قال", "رسول", "الله", "إنما", "الأعمال", "بالنيات"];
+        // Quoted with an extra word inside and text on both sides
+        let target = ["حدثنا", "فلان", "قال", "رسول", "الله", "صلى", "إنما", "الأعمال", "بالنيات", "و"];
+
+        let alignment = align(&source, &target).unwrap();
+        assert_eq!(alignment.source_range, 0..6);
+        assert_eq!(alignment.target_range, 2..9);
+        assert_eq!(alignment.pairs.len(), 6);
+        assert_eq!(alignment.pairs[3], (3, 6));
+        assert!((similarity(&alignment, source.len()) - 11.0 / 12.0).abs() < 1e-9);
+
+        assert!(align(&source, &["كتاب", "باب"]).is_none());
+
+        // Unanalysed tokens don't match each other
+        let unknown = [AlignedToken(None), AlignedToken(Some("قال")), AlignedToken(None)];
+        let alignment = align(&unknown, &unknown).unwrap();
+        assert_eq!(alignment.pairs, [(1, 1)]);
+    }
+}
@@ -0,0 +1,274 @@
+//! Server-side snippet generation
+//!
+//! Builds short text windows around matched tokens so search results don't
+//! need to carry the full page body. Token counting mirrors buildCharToTokenMap
+//! in src/utils/arabicTokenizer.ts, which in turn matches the Python
+//! preprocessing: punctuation, Latin letters and digits are skipped, and any
+//! other non-Arabic character ends the current token.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Punctuation symbols that Python strips (must match process_batch.py)
+const PUNCT_SYMBOLS: &[char] = &[
+    // Common punctuation
+    '.', ',', '،', ':', ';', '!', '?', '؟', '؛', '«', '»', '"', '\'', '(', ')', '[', ']', '{', '}',
+    '⦗', '⦘', '﴾', '﴿', '/', '\\', '–', '—', '-', '_', '…', '·', '•', '●', '○', '◦',
+    // Arabic-specific punctuation
+    '۔', '؍', '٫', '٬', '٭',
+    // Mathematical and misc symbols
+    '±', '×', '÷', '=', '≠', '<', '>', '≤', '≥', '∞', '∑', '∏', '√', '∫', '∂', '∇',
+    // Other punctuation marks
+    '¡', '¿', '†', '‡', '§', '¶', '©', '®', '™', '°', '′', '″', '‴',
+];
+
+/// Snippet window sizes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnippetOptions {
+    /// Tokens of context before the first match in a window
+    pub tokens_before: usize,
+    /// Tokens of context after the first match in a window
+    pub tokens_after: usize,
+    /// Maximum number of windows per result
+    pub max_snippets: usize,
+}
+
+impl Default for SnippetOptions {
+    /// Same window the result list used to cut on the frontend (50 tokens, match within the first 5)
+    fn default() -> Self {
+        Self {
+            tokens_before: 5,
+            tokens_after: 44,
+            max_snippets: 1,
+        }
+    }
+}
+
+/// Character range within a snippet's text (start inclusive, end exclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A window of page text around one or more matches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    /// Plain text of the window (HTML stripped)
+    pub text: String,
+    /// First token index covered by the window
+    pub start_token: u32,
+    /// Token index just past the window
+    pub end_token: u32,
+    /// Whether text was cut before the window
+    pub truncated_start: bool,
+    /// Whether text was cut after the window
+    pub truncated_end: bool,
+    /// Matched token ranges, as character offsets into `text`
+    pub highlights: Vec<HighlightRange>,
+}
+
+fn is_latin_letter(c: char) -> bool {
+    c.is_ascii_alphabetic() || ('\u{00C0}'..='\u{024F}').contains(&c) || ('\u{1E00}'..='\u{1EFF}').contains(&c)
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('\u{0660}'..='\u{0669}').contains(&c) || ('\u{06F0}'..='\u{06F9}').contains(&c)
+}
+
+/// Characters that Python strips before tokenizing (strip_punct, strip_latin, strip_digits)
+fn should_skip_for_token_counting(c: char) -> bool {
+    PUNCT_SYMBOLS.contains(&c) || is_latin_letter(c) || is_digit(c)
+}
+
+fn is_tashkil(c: char) -> bool {
+    ('\u{064B}'..='\u{065F}').contains(&c) || c == '\u{0670}'
+}
+
+fn is_arabic_letter(c: char) -> bool {
+    let in_range = ('\u{0600}'..='\u{06FF}').contains(&c)
+        || ('\u{0750}'..='\u{077F}').contains(&c)
+        || ('\u{08A0}'..='\u{08FF}').contains(&c);
+    in_range && !is_tashkil(c)
+}
+
+/// Strip HTML tags from page text, turning <br> into newlines
+pub fn strip_html(html: &str) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(open) = rest.find('<') {
+        result.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('>') else {
+            // Unterminated tag - keep it as literal text
+            rest = &rest[open..];
+            break;
+        };
+
+        let inner = rest[open + 1..open + close].to_lowercase();
+        let is_br = inner
+            .strip_prefix("br")
+            .map(|tail| {
+                let tail = tail.trim_start();
+                tail.is_empty() || tail == "/"
+            })
+            .unwrap_or(false);
+        if is_br {
+            result.push('\n');
+        }
+
+        rest = &rest[open + close + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Map each character of plain text to the index of the token it belongs to
+pub fn char_to_token_map(chars: &[char]) -> Vec<Option<u32>> {
+    let mut char_to_token = Vec::with_capacity(chars.len());
+    let mut current_token: u32 = 0;
+    let mut in_word = false;
+
+    for &c in chars {
+        if should_skip_for_token_counting(c) {
+            // Stripped characters don't end the current word
+            char_to_token.push(None);
+        } else if is_arabic_letter(c) || is_tashkil(c) {
+            char_to_token.push(Some(current_token));
+            in_word = true;
+        } else {
+            // Whitespace and other characters are true word boundaries
+            char_to_token.push(None);
+            if in_word {
+                current_token += 1;
+                in_word = false;
+            }
+        }
+    }
+
+    char_to_token
+}
+
+/// Build snippet windows around the matched tokens of a page body.
+/// Windows never overlap; a match already inside a window doesn't start a new one.
+pub fn build_snippets(body: &str, matched_token_indices: &[u32], options: &SnippetOptions) -> Vec<Snippet> {
+    let text = strip_html(body);
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return Vec::new();
+    }
+
+    let char_to_token = char_to_token_map(&chars);
+    let total_tokens = char_to_token.iter().flatten().max().map(|&t| t + 1).unwrap_or(0);
+    let matched: HashSet<u32> = matched_token_indices.iter().copied().collect();
+
+    let mut centers: Vec<u32> = matched_token_indices.to_vec();
+    centers.sort_unstable();
+    centers.dedup();
+    if centers.is_empty() {
+        centers.push(0);
+    }
+
+    // Token windows, in page order
+    let mut windows: Vec<(u32, u32)> = Vec::new();
+    for center in centers {
+        if windows.len() >= options.max_snippets.max(1) {
+            break;
+        }
+        let previous_end = windows.last().map(|&(_, end)| end).unwrap_or(0);
+        if !windows.is_empty() && center < previous_end {
+            continue;
+        }
+        let start = center.saturating_sub(options.tokens_before as u32).max(previous_end);
+        let end = total_tokens.min(center.saturating_add(options.tokens_after as u32 + 1));
+        if start < end {
+            windows.push((start, end));
+        }
+    }
+
+    let mut snippets = Vec::new();
+    for (start_token, end_token) in windows {
+        let in_window = |token: &Option<u32>| token.is_some_and(|t| t >= start_token && t < end_token);
+        let (Some(start), Some(last)) = (
+            char_to_token.iter().position(in_window),
+            char_to_token.iter().rposition(in_window),
+        ) else {
+            continue;
+        };
+
+        snippets.push(Snippet {
+            text: chars[start..=last].iter().collect(),
+            start_token,
+            end_token,
+            truncated_start: start_token > 0,
+            truncated_end: end_token < total_tokens,
+            highlights: highlight_ranges(&char_to_token[start..=last], &matched),
+        });
+    }
+
+    // Matches past the end of the text - fall back to the whole page like the frontend did
+    if snippets.is_empty() {
+        snippets.push(Snippet {
+            text,
+            start_token: 0,
+            end_token: total_tokens,
+            truncated_start: false,
+            truncated_end: false,
+            highlights: highlight_ranges(&char_to_token, &matched),
+        });
+    }
+
+    snippets
+}
+
+/// Contiguous character ranges whose tokens are in the matched set
+fn highlight_ranges(char_to_token: &[Option<u32>], matched: &HashSet<u32>) -> Vec<HighlightRange> {
+    let mut ranges: Vec<HighlightRange> = Vec::new();
+    let mut current: Option<HighlightRange> = None;
+
+    for (i, token) in char_to_token.iter().enumerate() {
+        if token.is_some_and(|t| matched.contains(&t)) {
+            match current.as_mut() {
+                Some(range) => range.end = i + 1,
+                None => current = Some(HighlightRange { start: i, end: i + 1 }),
+            }
+        } else if let Some(range) = current.take() {
+            ranges.push(range);
+        }
+    }
+
+    ranges.extend(current);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(strip_html("قال<br/>حدثنا <span class=\"x\">فلان</span>"), "قال\nحدثنا فلان");
+        assert_eq!(strip_html("a < b"), "a < b");
+    }
+
+    #[test]
+    fn test_snippet_window_and_highlights() {
+        let body = "حدثنا محمد بن عبد الله، قال: أخبرنا مالك عن نافع";
+        let snippets = build_snippets(body, &[5], &SnippetOptions { tokens_before: 1, tokens_after: 1, max_snippets: 1 });
+
+        assert_eq!(snippets.len(), 1);
+        let snippet = &snippets[0];
+        assert_eq!(snippet.text, "الله، قال: أخبرنا");
+        assert_eq!((snippet.start_token, snippet.end_token), (4, 7));
+        assert!(snippet.truncated_start && snippet.truncated_end);
+
+        let highlighted: Vec<String> = snippet
+            .highlights
+            .iter()
+            .map(|r| snippet.text.chars().skip(r.start).take(r.end - r.start).collect())
+            .collect();
+        assert_eq!(highlighted, vec!["قال"]);
+    }
+}
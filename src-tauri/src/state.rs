@@ -2,7 +2,11 @@
 
 use crate::cache::TokenCache;
 use crate::downloader::get_settings_db_path;
+use crate::frequency::FrequencyStore;
+use crate::quran::{QuranStore, QURAN_FILE};
+use crate::reuse::ReuseIndex;
 use crate::search::SearchEngine;
+use crate::toponyms::{GazetteerStore, GAZETTEER_FILE};
 use anyhow::Result;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -14,6 +18,10 @@ const DEFAULT_CACHE_CAPACITY: usize = 1000;
 pub struct AppState {
     pub search_engine: Arc<SearchEngine>,
     pub token_cache: Arc<TokenCache>,
+    pub frequencies: Arc<FrequencyStore>,
+    pub text_reuse: Arc<ReuseIndex>,
+    pub gazetteer: Arc<GazetteerStore>,
+    pub quran: Arc<QuranStore>,
     pub db_path: PathBuf,
     pub settings_db_path: PathBuf,
     pub data_dir: PathBuf,
@@ -33,6 +41,14 @@ impl AppState {
         let search_engine = Arc::new(SearchEngine::open(&index_path)?);
         // TokenCache loads tokens from SQLite corpus.db
         let token_cache = Arc::new(TokenCache::new(db_path.clone(), DEFAULT_CACHE_CAPACITY));
+        // Frequency cache lives with the corpus, so a corpus update starts it afresh
+        let frequencies = Arc::new(FrequencyStore::new(db_path.clone(), data_dir.join("frequencies.db")));
+        // Text reuse index sits next to tantivy_index and is built on demand
+        let text_reuse = Arc::new(ReuseIndex::new(db_path.clone(), data_dir.join("reuse_index.db")));
+        // Gazetteer from the corpus, else the bundled one, read on the first place search
+        let gazetteer = Arc::new(GazetteerStore::new(data_dir.join(GAZETTEER_FILE)));
+        // Likewise the Qur'an text, indexed on the first citation lookup
+        let quran = Arc::new(QuranStore::new(data_dir.join(QURAN_FILE)));
 
         // Initialize settings database (create if missing)
         Self::init_settings_db(&settings_db_path)?;
@@ -40,6 +56,10 @@ impl AppState {
         Ok(Self {
             search_engine,
             token_cache,
+            frequencies,
+            text_reuse,
+            gazetteer,
+            quran,
             db_path,
             settings_db_path,
             data_dir,
@@ -0,0 +1,60 @@
+//! Corpus databases for tests
+//!
+//! The corpus.db tables the corpus readers use (books, page_tokens,
+//! token_definitions, lemmas, roots), empty, in a temporary directory that is
+//! removed with the corpus.
+
+use rusqlite::{params, Connection};
+use std::path::PathBuf;
+
+const SCHEMA: &str = r#"
+    CREATE TABLE books (id INTEGER PRIMARY KEY, author_id INTEGER, genre_id INTEGER, death_ah INTEGER, century_ah INTEGER);
+    CREATE TABLE page_tokens (book_id INTEGER, page_id INTEGER, token_ids BLOB);
+    CREATE TABLE token_definitions (id INTEGER PRIMARY KEY, surface TEXT, lemma_id INTEGER, root_id INTEGER);
+    CREATE TABLE lemmas (id INTEGER PRIMARY KEY, lemma TEXT);
+    CREATE TABLE roots (id INTEGER PRIMARY KEY, root TEXT);
+"#;
+
+/// A corpus.db in `kashshaf-{name}-{pid}` under the temp directory
+pub struct TestCorpus {
+    pub dir: PathBuf,
+    pub conn: Connection,
+}
+
+impl TestCorpus {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("kashshaf-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let conn = Connection::open(dir.join("corpus.db")).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        Self { dir, conn }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join("corpus.db")
+    }
+
+    /// Add books by ID only
+    pub fn add_books(&self, ids: impl IntoIterator<Item = u64>) {
+        for id in ids {
+            self.conn.execute("INSERT INTO books (id) VALUES (?1)", [id]).unwrap();
+        }
+    }
+
+    pub fn add_page(&self, book_id: u64, page_id: u64, token_ids: &[u32]) {
+        self.conn
+            .execute("INSERT INTO page_tokens VALUES (?1, ?2, ?3)", params![book_id, page_id, token_blob(token_ids)])
+            .unwrap();
+    }
+}
+
+impl Drop for TestCorpus {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Token IDs as stored in page_tokens: little-endian u32s
+pub fn token_blob(ids: &[u32]) -> Vec<u8> {
+    ids.iter().flat_map(|id| id.to_le_bytes()).collect()
+}
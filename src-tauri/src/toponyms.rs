@@ -0,0 +1,378 @@
+//! Place names from a gazetteer
+//!
+//! The gazetteer is a GeoJSON file in the corpus data directory, as published by
+//! al-Thurayya (OpenITI): one point per place with its canonical Arabic name,
+//! other spellings and coordinates, under `properties.cornuData` or directly in
+//! `properties`. Without one, the gazetteer bundled with the app is used: the
+//! main cities and regions of the classical sources, in the same format. A place
+//! is searched for by all its spellings and its nisbas (بغداد: البغدادي); nisbas
+//! the gazetteer does not list are derived from the name. Mentions of places can
+//! be exported as GeoJSON points for mapping.
+
+use crate::control::{SearchControl, SearchProgress};
+use crate::downloader::bundled_resource;
+use crate::names::{with_proclitics, NameSearchForm};
+use crate::persons::NameMentions;
+use crate::search::{normalize_arabic, SearchEngine, SearchFilters};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// File name of the gazetteer in the corpus data directory
+pub const GAZETTEER_FILE: &str = "gazetteer.geojson";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Place {
+    /// Gazetteer ID (al-Thurayya URI, e.g. BAGHDAD_443E333N_S)
+    pub uri: String,
+    pub name: String,
+    /// Other spellings, normalized
+    pub variants: Vec<String>,
+    /// Listed in the gazetteer, or derived from the name
+    pub nisbas: Vec<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+}
+
+impl Place {
+    /// Name, variants and nisbas, normalized, once each
+    pub fn surface_forms(&self) -> Vec<String> {
+        let mut forms: Vec<String> = Vec::new();
+        for form in std::iter::once(&self.name).chain(&self.variants).chain(&self.nisbas) {
+            let form = normalize(form);
+            if !form.is_empty() && !forms.contains(&form) {
+                forms.push(form);
+            }
+        }
+        forms
+    }
+
+    /// Surface forms with every proclitic variant (ببغداد، والبغدادي ...)
+    pub fn search_patterns(&self) -> Vec<String> {
+        let mut patterns: Vec<String> = Vec::new();
+        for pattern in self.surface_forms().iter().flat_map(|form| with_proclitics(form)) {
+            if !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+        patterns
+    }
+}
+
+fn normalize(text: &str) -> String {
+    normalize_arabic(text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Nisba of a one-word place name: البصرة -> البصري، بخارى -> البخاري، بغداد -> البغدادي.
+/// Irregular nisbas (الري: الرازي) have to come from the gazetteer.
+pub fn derive_nisba(name: &str) -> Option<String> {
+    let name = normalize(name);
+    if name.is_empty() || name.contains(' ') {
+        return None;
+    }
+    let base = name.strip_prefix("ال").unwrap_or(&name);
+    let base = base
+        .strip_suffix("اء")
+        .or_else(|| base.strip_suffix(['ة', 'ه', 'ا', 'ي']))
+        .unwrap_or(base);
+    (base.chars().count() >= 2).then(|| format!("ال{}ي", base))
+}
+
+/// A property given as a list or as a string of names separated by commas, semicolons or |
+fn names_value(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).map(normalize).filter(|name| !name.is_empty()).collect(),
+        Some(Value::String(names)) => names
+            .split([',', '،', ';', '؛', '|'])
+            .map(normalize)
+            .filter(|name| !name.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A coordinate given as a number or a string
+fn coordinate(value: Option<&Value>) -> Option<f64> {
+    match value? {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn first<'a>(properties: &'a Map<String, Value>, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().find_map(|key| properties.get(*key))
+}
+
+fn place_from_feature(feature: &Value) -> Option<Place> {
+    let properties = feature.get("properties")?;
+    let properties = properties.get("cornuData").unwrap_or(properties).as_object()?;
+
+    let name = first(properties, &["toponym_arabic", "name"]).and_then(Value::as_str).map(normalize)?;
+    if name.is_empty() {
+        return None;
+    }
+    let uri = first(properties, &["cornu_URI", "uri", "id"])
+        .and_then(|uri| match uri {
+            Value::String(uri) => Some(uri.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        })
+        .unwrap_or_else(|| name.clone());
+    let variants = names_value(first(properties, &["toponym_arabic_other", "variants"]))
+        .into_iter()
+        .filter(|variant| *variant != name)
+        .collect();
+    let mut nisbas = names_value(first(properties, &["nisbas", "nisba"]));
+    if nisbas.is_empty() {
+        nisbas.extend(derive_nisba(&name));
+    }
+
+    // GeoJSON points are [lon, lat]
+    let point = feature.pointer("/geometry/coordinates").and_then(Value::as_array);
+    let lon = point.and_then(|point| point.first()?.as_f64()).or_else(|| coordinate(properties.get("coord_lon")));
+    let lat = point.and_then(|point| point.get(1)?.as_f64()).or_else(|| coordinate(properties.get("coord_lat")));
+
+    Some(Place { uri, name, variants, nisbas, lat, lon })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Gazetteer {
+    pub places: Vec<Place>,
+}
+
+impl Gazetteer {
+    /// Places of a GeoJSON FeatureCollection; features without an Arabic name are skipped
+    pub fn from_geojson(geojson: &str) -> Result<Self> {
+        let collection: Value = serde_json::from_str(geojson)?;
+        let features = collection
+            .get("features")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("Gazetteer is not a GeoJSON FeatureCollection"))?;
+        Ok(Self { places: features.iter().filter_map(place_from_feature).collect() })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let geojson = std::fs::read_to_string(path).with_context(|| format!("No gazetteer at {}", path.display()))?;
+        Self::from_geojson(&geojson).with_context(|| format!("Invalid gazetteer {}", path.display()))
+    }
+
+    /// A place by URI, or by any of its names or nisbas
+    pub fn find(&self, key: &str) -> Option<&Place> {
+        if let Some(place) = self.places.iter().find(|place| place.uri == key) {
+            return Some(place);
+        }
+        let key = normalize(key);
+        self.places.iter().find(|place| place.surface_forms().contains(&key))
+    }
+
+    /// Places whose URI or a name contains the query; exact matches first
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Place> {
+        let query = normalize(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let lower = query.to_lowercase();
+        let mut found: Vec<(bool, &Place)> = self
+            .places
+            .iter()
+            .filter_map(|place| {
+                let forms = place.surface_forms();
+                let exact = forms.contains(&query);
+                (exact || place.uri.to_lowercase().contains(&lower) || forms.iter().any(|form| form.contains(&query)))
+                    .then_some((exact, place))
+            })
+            .collect();
+        found.sort_by_key(|(exact, _)| !exact);
+        found.into_iter().take(limit).map(|(_, place)| place.clone()).collect()
+    }
+
+    /// Resolve places in name search forms to their patterns
+    pub fn resolve_place_forms(&self, forms: Vec<NameSearchForm>) -> Result<Vec<NameSearchForm>> {
+        forms
+            .into_iter()
+            .map(|form| match form {
+                NameSearchForm::Place { place } => {
+                    let place = self.find(&place).ok_or_else(|| anyhow!("Place {} not in the gazetteer", place))?;
+                    Ok(NameSearchForm::Patterns(place.search_patterns()))
+                }
+                form => Ok(form),
+            })
+            .collect()
+    }
+}
+
+/// The corpus gazetteer, or the bundled one if the corpus has none, read on first use
+pub struct GazetteerStore {
+    path: PathBuf,
+    loaded: Mutex<Option<Arc<Gazetteer>>>,
+}
+
+impl GazetteerStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, loaded: Mutex::new(None) }
+    }
+
+    pub fn get(&self) -> Result<Arc<Gazetteer>> {
+        let mut loaded = self.loaded.lock().map_err(|_| anyhow!("Gazetteer lock poisoned"))?;
+        if let Some(gazetteer) = loaded.as_ref() {
+            return Ok(gazetteer.clone());
+        }
+        let path = if self.path.exists() { Some(self.path.clone()) } else { bundled_resource(GAZETTEER_FILE) };
+        let path = path.ok_or_else(|| anyhow!("No gazetteer at {} and none bundled with the app", self.path.display()))?;
+        let gazetteer = Arc::new(Gazetteer::load(&path)?);
+        *loaded = Some(gazetteer.clone());
+        Ok(gazetteer)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaceMentionsRequest {
+    /// Place URIs or names; every place in the gazetteer if empty
+    #[serde(default)]
+    pub places: Vec<String>,
+    #[serde(default)]
+    pub subcorpus: crate::frequency::Subcorpus,
+    /// Write the mentioned places to this file as GeoJSON
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaceMentions {
+    #[serde(flatten)]
+    pub place: Place,
+    #[serde(flatten)]
+    pub counts: NameMentions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaceMentionCounts {
+    /// Mentioned places, most mentioned first
+    pub places: Vec<PlaceMentions>,
+    pub elapsed_ms: u64,
+}
+
+impl PlaceMentionCounts {
+    /// Places with coordinates as a FeatureCollection of points
+    pub fn to_geojson(&self) -> Value {
+        let features: Vec<Value> = self
+            .places
+            .iter()
+            .filter_map(|mentions| {
+                let place = &mentions.place;
+                Some(json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [place.lon?, place.lat?] },
+                    "properties": {
+                        "uri": place.uri,
+                        "name": place.name,
+                        "pages": mentions.counts.pages,
+                        "books": mentions.counts.books,
+                        "mentions": mentions.counts.mentions,
+                    },
+                }))
+            })
+            .collect();
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+
+    pub fn write_geojson(&self, path: &Path) -> Result<()> {
+        let geojson = serde_json::to_string_pretty(&self.to_geojson())?;
+        std::fs::write(path, geojson).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// The places of a request: the listed ones in order, or the whole gazetteer
+pub fn requested_places(gazetteer: &Gazetteer, places: &[String]) -> Result<Vec<Place>> {
+    if places.is_empty() {
+        return Ok(gazetteer.places.clone());
+    }
+    places
+        .iter()
+        .map(|key| gazetteer.find(key).cloned().ok_or_else(|| anyhow!("Place {} not in the gazetteer", key)))
+        .collect()
+}
+
+/// Mentions of each place within the given books (`None`: the whole corpus); places
+/// never mentioned are left out. Progress is reported per place, as for persons.
+pub fn count_place_mentions(
+    engine: &SearchEngine,
+    places: &[Place],
+    book_ids: Option<&[u64]>,
+    control: &SearchControl,
+) -> Result<PlaceMentionCounts> {
+    let start = std::time::Instant::now();
+    let filters = SearchFilters { book_ids: book_ids.map(<[u64]>::to_vec), ..Default::default() };
+    // An empty subcorpus would otherwise mean no book filter at all
+    let no_books = book_ids.is_some_and(|ids| ids.is_empty());
+
+    let mut mentioned = Vec::new();
+    for (checked, place) in places.iter().enumerate() {
+        control.check()?;
+        if !no_books {
+            let counts = engine.count_name_mentions(&place.search_patterns(), &filters, control)?;
+            if counts.pages > 0 {
+                mentioned.push(PlaceMentions { place: place.clone(), counts });
+            }
+        }
+        control.report(&SearchProgress { checked: checked + 1, candidates: places.len(), hits: mentioned.len() });
+    }
+    mentioned.sort_by_key(|place| std::cmp::Reverse(place.counts.mentions));
+
+    Ok(PlaceMentionCounts { places: mentioned, elapsed_ms: start.elapsed().as_millis() as u64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gazetteer_places_and_nisbas() {
+        assert_eq!(derive_nisba("بغداد").as_deref(), Some("البغدادي"));
+        assert_eq!(derive_nisba("البصرة").as_deref(), Some("البصري"));
+        assert_eq!(derive_nisba("بخارى").as_deref(), Some("البخاري"));
+        assert_eq!(derive_nisba("رأس العين"), None);
+
+        let gazetteer = Gazetteer::from_geojson(
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [44.4, 33.3]},
+                 "properties": {"cornuData": {"cornu_URI": "BAGHDAD_443E333N_S", "toponym_arabic": "بغداد",
+                                              "toponym_arabic_other": "بغداذ، مدينة السلام"}}},
+                {"type": "Feature", "properties": {"uri": "RAYY", "name": "الري", "nisbas": ["الرازي"],
+                                                   "coord_lat": "35.6", "coord_lon": "51.4"}},
+                {"type": "Feature", "properties": {"name": ""}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(gazetteer.places.len(), 2);
+
+        let baghdad = gazetteer.find("BAGHDAD_443E333N_S").unwrap();
+        assert_eq!(baghdad.surface_forms(), ["بغداد", "بغداذ", "مدينة السلام", "البغدادي"]);
+        assert!(baghdad.search_patterns().contains(&"ببغداد".to_string()));
+        assert_eq!((baghdad.lat, baghdad.lon), (Some(33.3), Some(44.4)));
+
+        let rayy = gazetteer.find("الرازي").unwrap();
+        assert_eq!(rayy.uri, "RAYY");
+        assert_eq!(rayy.lat, Some(35.6));
+        assert_eq!(gazetteer.search("بغد", 10).len(), 1);
+
+        let forms = gazetteer.resolve_place_forms(vec![NameSearchForm::Place { place: "الري".to_string() }]).unwrap();
+        assert_eq!(forms[0].search_patterns().unwrap(), rayy.search_patterns());
+        assert!(gazetteer.resolve_place_forms(vec![NameSearchForm::Place { place: "قرطبة".to_string() }]).is_err());
+
+        let bundled = Gazetteer::from_geojson(include_str!("../resources/gazetteer.geojson")).unwrap();
+        assert_eq!(bundled.find("الطبراني").unwrap().name, "طبرية");
+        assert!(bundled.places.iter().all(|place| place.lat.is_some() && place.lon.is_some()));
+
+        let counts = PlaceMentionCounts {
+            places: vec![PlaceMentions { place: rayy.clone(), counts: NameMentions { pages: 2, books: 1, mentions: 3 } }],
+            elapsed_ms: 0,
+        };
+        let geojson = counts.to_geojson();
+        assert_eq!(geojson["features"][0]["geometry"]["coordinates"], json!([51.4, 35.6]));
+        assert_eq!(geojson["features"][0]["properties"]["mentions"], 3);
+    }
+}
@@ -0,0 +1,407 @@
+//! Minimal streaming XLSX writer
+//!
+//! Writes a single worksheet of inline-string and number cells. Rows are
+//! deflated into a temporary file as they arrive, so memory stays flat for
+//! hit lists of any size; `finish` wraps the sheet and the fixed workbook
+//! parts into the zip container.
+
+use anyhow::{anyhow, Context, Result};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Excel's row limit (including the header row)
+pub const MAX_ROWS: usize = 1_048_576;
+
+/// A cell value
+pub enum Cell<'a> {
+    Text(&'a str),
+    Number(u64),
+    Empty,
+}
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#;
+
+const SHEET_START: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#;
+
+const SHEET_END: &str = "</sheetData></worksheet>";
+
+/// Tracks CRC-32 and size of everything written through it
+struct CrcWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    len: u64,
+}
+
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Streams rows into a single-sheet workbook
+pub struct XlsxWriter {
+    path: PathBuf,
+    sheet_path: PathBuf,
+    sheet: CrcWriter<DeflateEncoder<BufWriter<File>>>,
+    rows: usize,
+}
+
+/// Temporary file holding the compressed sheet until `finish`
+pub fn temp_sheet_path(path: &Path) -> PathBuf {
+    path.with_extension("xlsx.sheet.tmp")
+}
+
+impl XlsxWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let sheet_path = temp_sheet_path(path);
+        let file = File::create(&sheet_path)
+            .with_context(|| format!("Failed to create {}", sheet_path.display()))?;
+        let mut sheet = CrcWriter {
+            inner: DeflateEncoder::new(BufWriter::new(file), Compression::default()),
+            hasher: crc32fast::Hasher::new(),
+            len: 0,
+        };
+        sheet.write_all(SHEET_START.as_bytes())?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sheet_path,
+            sheet,
+            rows: 0,
+        })
+    }
+
+    pub fn write_row(&mut self, cells: &[Cell]) -> Result<()> {
+        if self.rows >= MAX_ROWS {
+            return Err(anyhow!("XLSX sheets are limited to {} rows", MAX_ROWS));
+        }
+
+        let mut row = String::from("<row>");
+        for cell in cells {
+            match cell {
+                Cell::Text(text) => {
+                    row.push_str("<c t=\"inlineStr\"><is><t xml:space=\"preserve\">");
+                    push_xml_escaped(&mut row, text);
+                    row.push_str("</t></is></c>");
+                }
+                Cell::Number(n) => {
+                    row.push_str("<c><v>");
+                    row.push_str(&n.to_string());
+                    row.push_str("</v></c>");
+                }
+                Cell::Empty => row.push_str("<c/>"),
+            }
+        }
+        row.push_str("</row>");
+
+        self.sheet.write_all(row.as_bytes())?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Assemble the zip container and remove the temporary sheet file
+    pub fn finish(self, sheet_name: &str) -> Result<()> {
+        let Self { path, sheet_path, mut sheet, .. } = self;
+        sheet.write_all(SHEET_END.as_bytes())?;
+        let crc = sheet.hasher.finalize();
+        let uncompressed_len = sheet.len;
+        sheet.inner.finish()?.flush()?;
+
+        let result = write_container(&path, &sheet_path, sheet_name, crc, uncompressed_len);
+        let _ = fs::remove_file(&sheet_path);
+        result
+    }
+}
+
+fn write_container(path: &Path, sheet_path: &Path, sheet_name: &str, sheet_crc: u32, sheet_len: u64) -> Result<()> {
+    let mut workbook = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name=""#,
+    );
+    push_xml_escaped(&mut workbook, sheet_name);
+    workbook.push_str(r#"" sheetId="1" r:id="rId1"/></sheets></workbook>"#);
+
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    zip.add_stored("[Content_Types].xml", CONTENT_TYPES.as_bytes())?;
+    zip.add_stored("_rels/.rels", ROOT_RELS.as_bytes())?;
+    zip.add_stored("xl/workbook.xml", workbook.as_bytes())?;
+    zip.add_stored("xl/_rels/workbook.xml.rels", WORKBOOK_RELS.as_bytes())?;
+
+    let compressed_len = fs::metadata(sheet_path)?.len();
+    let mut sheet = File::open(sheet_path)?;
+    zip.add_deflated("xl/worksheets/sheet1.xml", sheet_crc, compressed_len, sheet_len, &mut sheet)?;
+
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+/// Escape text for XML, dropping control characters XML 1.0 can't represent
+pub(crate) fn push_xml_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+}
+
+/// Central directory record for one zip entry
+struct ZipEntry {
+    name: &'static str,
+    method: u16,
+    crc: u32,
+    compressed_len: u32,
+    uncompressed_len: u32,
+    offset: u32,
+}
+
+/// Just enough of the zip format for an XLSX package (no zip64)
+struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    entries: Vec<ZipEntry>,
+}
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// 1980-01-01 00:00 in MS-DOS format
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+
+fn zip_u32(value: u64, what: &str) -> Result<u32> {
+    u32::try_from(value).map_err(|_| anyhow!("{} is too large for an XLSX file", what))
+}
+
+impl<W: Write> ZipWriter<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_local_header(&mut self, entry: &ZipEntry) -> Result<()> {
+        let mut header = Vec::with_capacity(30 + entry.name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        header.extend_from_slice(&0u16.to_le_bytes()); // flags
+        header.extend_from_slice(&entry.method.to_le_bytes());
+        header.extend_from_slice(&DOS_TIME.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed_len.to_le_bytes());
+        header.extend_from_slice(&entry.uncompressed_len.to_le_bytes());
+        header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(entry.name.as_bytes());
+        self.write_bytes(&header)
+    }
+
+    fn add_stored(&mut self, name: &'static str, data: &[u8]) -> Result<()> {
+        let len = zip_u32(data.len() as u64, name)?;
+        let entry = ZipEntry {
+            name,
+            method: METHOD_STORED,
+            crc: crc32fast::hash(data),
+            compressed_len: len,
+            uncompressed_len: len,
+            offset: zip_u32(self.offset, "Workbook")?,
+        };
+        self.write_local_header(&entry)?;
+        self.write_bytes(data)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Add an entry whose raw deflate stream is read from `data`
+    fn add_deflated(
+        &mut self,
+        name: &'static str,
+        crc: u32,
+        compressed_len: u64,
+        uncompressed_len: u64,
+        data: &mut impl Read,
+    ) -> Result<()> {
+        let entry = ZipEntry {
+            name,
+            method: METHOD_DEFLATED,
+            crc,
+            compressed_len: zip_u32(compressed_len, name)?,
+            uncompressed_len: zip_u32(uncompressed_len, name)?,
+            offset: zip_u32(self.offset, "Workbook")?,
+        };
+        self.write_local_header(&entry)?;
+        let copied = io::copy(data, &mut self.out)?;
+        self.offset += copied;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        let directory_offset = zip_u32(self.offset, "Workbook")?;
+        let entries = std::mem::take(&mut self.entries);
+
+        for entry in &entries {
+            let mut record = Vec::with_capacity(46 + entry.name.len());
+            record.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            record.extend_from_slice(&20u16.to_le_bytes()); // version made by
+            record.extend_from_slice(&20u16.to_le_bytes()); // version needed
+            record.extend_from_slice(&0u16.to_le_bytes()); // flags
+            record.extend_from_slice(&entry.method.to_le_bytes());
+            record.extend_from_slice(&DOS_TIME.to_le_bytes());
+            record.extend_from_slice(&DOS_DATE.to_le_bytes());
+            record.extend_from_slice(&entry.crc.to_le_bytes());
+            record.extend_from_slice(&entry.compressed_len.to_le_bytes());
+            record.extend_from_slice(&entry.uncompressed_len.to_le_bytes());
+            record.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            record.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+            record.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            record.extend_from_slice(&0u16.to_le_bytes()); // disk number
+            record.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            record.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+            record.extend_from_slice(&entry.offset.to_le_bytes());
+            record.extend_from_slice(entry.name.as_bytes());
+            self.write_bytes(&record)?;
+        }
+
+        let directory_len = zip_u32(self.offset - directory_offset as u64, "Workbook")?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        end.extend_from_slice(&0u16.to_le_bytes()); // directory disk
+        end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        end.extend_from_slice(&directory_len.to_le_bytes());
+        end.extend_from_slice(&directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.write_bytes(&end)?;
+
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// Read every entry back through the central directory, checking local
+    /// headers, sizes and CRCs against it
+    fn read_zip(bytes: &[u8]) -> Vec<(String, String)> {
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(bytes, end), 0x06054b50);
+        let count = u16_at(bytes, end + 10);
+        let directory_len = u32_at(bytes, end + 12) as usize;
+        let mut at = u32_at(bytes, end + 16) as usize;
+        assert_eq!(at + directory_len, end);
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(bytes, at), 0x02014b50);
+            let method = u16_at(bytes, at + 10);
+            let crc = u32_at(bytes, at + 16);
+            let compressed_len = u32_at(bytes, at + 20) as usize;
+            let uncompressed_len = u32_at(bytes, at + 24) as usize;
+            let name_len = u16_at(bytes, at + 28);
+            let offset = u32_at(bytes, at + 42) as usize;
+            let name = std::str::from_utf8(&bytes[at + 46..at + 46 + name_len]).unwrap().to_string();
+            at += 46 + name_len;
+
+            assert_eq!(u32_at(bytes, offset), 0x04034b50);
+            assert_eq!(u32_at(bytes, offset + 14), crc, "{}", name);
+            assert_eq!(&bytes[offset + 30..offset + 30 + name_len], name.as_bytes());
+            let data_start = offset + 30 + name_len + u16_at(bytes, offset + 28);
+            let raw = &bytes[data_start..data_start + compressed_len];
+            let data = match method as u16 {
+                METHOD_STORED => raw.to_vec(),
+                METHOD_DEFLATED => {
+                    let mut data = Vec::new();
+                    DeflateDecoder::new(raw).read_to_end(&mut data).unwrap();
+                    data
+                }
+                other => panic!("unexpected method {}", other),
+            };
+            assert_eq!(data.len(), uncompressed_len, "{}", name);
+            assert_eq!(crc32fast::hash(&data), crc, "{}", name);
+            entries.push((name, String::from_utf8(data).unwrap()));
+        }
+        assert_eq!(at, end);
+        entries
+    }
+
+    #[test]
+    fn test_workbook_round_trip() {
+        let dir = std::env::temp_dir().join(format!("kashshaf-xlsx-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hits.xlsx");
+
+        let mut writer = XlsxWriter::create(&path).unwrap();
+        writer.write_row(&[Cell::Text("Book"), Cell::Text("Page"), Cell::Text("Text")]).unwrap();
+        writer.write_row(&[Cell::Text("تاريخ بغداد"), Cell::Number(145), Cell::Text("حدثنا <أبو> بكر & \"عمر\"\u{1}\u{1f}\tقال")]).unwrap();
+        for page in 0..2000 {
+            writer.write_row(&[Cell::Text("الكامل"), Cell::Number(page), Cell::Empty]).unwrap();
+        }
+        writer.finish("نتائج <1>").unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert!(!temp_sheet_path(&path).exists());
+        let _ = fs::remove_dir_all(&dir);
+
+        let entries = read_zip(&bytes);
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["[Content_Types].xml", "_rels/.rels", "xl/workbook.xml", "xl/_rels/workbook.xml.rels", "xl/worksheets/sheet1.xml"]);
+        let part = |name: &str| entries.iter().find(|(n, _)| n == name).map(|(_, data)| data.as_str()).unwrap();
+
+        // Cells are inline strings: no shared strings part, and none declared
+        assert!(!part("[Content_Types].xml").contains("sharedStrings"));
+        assert!(!part("xl/_rels/workbook.xml.rels").contains("sharedStrings"));
+        assert!(part("xl/workbook.xml").contains(r#"<sheet name="نتائج &lt;1&gt;" sheetId="1""#));
+
+        let sheet = part("xl/worksheets/sheet1.xml");
+        assert!(sheet.starts_with(SHEET_START) && sheet.ends_with(SHEET_END));
+        assert_eq!(sheet.matches("<row>").count(), 2002);
+        assert!(sheet.contains(r#"<c t="inlineStr"><is><t xml:space="preserve">تاريخ بغداد</t></is></c><c><v>145</v></c>"#));
+        assert!(sheet.contains("حدثنا &lt;أبو&gt; بكر &amp; &quot;عمر&quot;\tقال</t>"));
+        assert!(!sheet.contains(['\u{1}', '\u{1f}']));
+        assert!(sheet.contains("<c><v>1999</v></c><c/></row>"));
+    }
+}
@@ -1,5 +1,5 @@
-import { useState, useEffect, useCallback, lazy, Suspense } from 'react';
-import type { SearchHistoryEntry, SavedSearchEntry, CorpusStatus, Announcement } from './types';
+import { useState, useEffect, useCallback, useRef, lazy, Suspense } from 'react';
+import type { SearchHistoryEntry, SavedSearchEntry, CorpusStatus, Announcement, CitationRequest } from './types';
 import type { AppSearchMode, CombinedSearchQuery, ProximitySearchQuery } from './types/search';
 import type { Collection } from './types/collections';
 import { MAX_RESULTS } from './constants/search';
@@ -10,7 +10,7 @@ import { useSearch } from './hooks/useSearch';
 import { useReaderNavigation } from './hooks/useReaderNavigation';
 import { Sidebar } from './components/Sidebar';
 import { ReaderPanel, ResultsPanel, HelpPanel } from './components/panels';
-import { DraggableSplitter, UpdateBanner } from './components/ui';
+import { DraggableSplitter, Toast, UpdateBanner } from './components/ui';
 import {
   TextSelectionModal,
   MetadataBrowser,
@@ -50,6 +50,7 @@ function App() {
   // Announcements state
   const [announcements, setAnnouncements] = useState<Announcement[]>([]);
   const [showAnnouncementsModal, setShowAnnouncementsModal] = useState(false);
+  const [citationMessage, setCitationMessage] = useState<string | null>(null);
   const [announcementsChecked, setAnnouncementsChecked] = useState(false);
 
   const [sidebarOpen, setSidebarOpen] = useState(true);
@@ -171,6 +172,58 @@ function App() {
     }
   }, [mode, checkingCorpus, corpusStatus?.ready, showDownloadModal]);
 
+  // Open a cited page (from a kashshaf://cite link) in the active tab
+  const openCitation = useCallback(async (request: CitationRequest) => {
+    try {
+      const citation = await api.resolveCitation(request);
+      if (citation.status === 'resolved') {
+        await handleResultClick(citation.page);
+      } else if (citation.status === 'ambiguous') {
+        // Which book? Cite one of them by ID
+        const books = citation.candidates.map(book => `${book.title} (${book.id})`).join('، ');
+        setCitationMessage(`"${request.book}" matches several books: ${books}. Cite one by its ID.`);
+      } else {
+        setCitationMessage(`Citation not found: ${request.book}, ${request.part_label ? `${request.part_label}:` : ''}${request.page_number}`);
+      }
+    } catch (err) {
+      console.error('Failed to open citation:', err);
+    }
+  }, [api, handleResultClick]);
+
+  // Desktop: open citation links, including the one the app was launched with
+  const launchCitationOpened = useRef(false);
+  useEffect(() => {
+    if (isWebTarget() || mode !== 'offline' || !corpusStatus?.ready) return;
+
+    let unlistenCitations: (() => void) | null = null;
+
+    async function listenForCitations() {
+      try {
+        const { listen } = await import('@tauri-apps/api/event');
+        unlistenCitations = await listen<CitationRequest>('open-citation', (event) => {
+          openCitation(event.payload);
+        });
+
+        if (!launchCitationOpened.current) {
+          launchCitationOpened.current = true;
+          const { getLaunchCitation } = await import('./api/tauri');
+          const request = await getLaunchCitation();
+          if (request) {
+            await openCitation(request);
+          }
+        }
+      } catch (err) {
+        console.error('Failed to listen for citation links:', err);
+      }
+    }
+
+    listenForCitations();
+
+    return () => {
+      unlistenCitations?.();
+    };
+  }, [mode, corpusStatus?.ready, openCitation]);
+
   useEffect(() => {
     localStorage.setItem('splitterRatio', splitterRatio.toString());
   }, [splitterRatio]);
@@ -615,6 +668,16 @@ function App() {
           onCreateCollection={handleCreateCollectionFromModal}
         />
 
+        {/* Citation links that could not be opened */}
+        {citationMessage && (
+          <Toast
+            message={citationMessage}
+            type="warning"
+            duration={10000}
+            onClose={() => setCitationMessage(null)}
+          />
+        )}
+
         {/* Save Collection Modal */}
         <SaveCollectionModal
           isOpen={saveCollectionModalOpen}
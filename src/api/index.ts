@@ -11,9 +11,14 @@ import type {
   SearchResults,
   BookMetadata,
   SearchResult,
+  BookPart,
+  CitationRequest,
+  CitationResolution,
+  PageDirection,
   Token,
 } from '../types';
-import type { NameSearchForm } from './tauri';
+import type { NameSearchForm, NamePatterns } from './tauri';
+import type { NameFormData } from '../utils/namePatterns';
 
 /**
  * Operating mode for the application
@@ -82,6 +87,8 @@ export interface SearchAPI {
     offset: number
   ): Promise<SearchResults>;
 
+  generateNamePatterns(forms: NameFormData[]): Promise<NamePatterns[]>;
+
   wildcardSearch(
     query: string,
     filters: SearchFilters,
@@ -96,6 +103,26 @@ export interface SearchAPI {
     pageId: number
   ): Promise<SearchResult | null>;
 
+  /** Parts (volumes) of a book with their labels and page ranges */
+  getBookParts(id: number): Promise<BookPart[]>;
+
+  /** The next or previous page of a book, across parts; null at either end */
+  getAdjacentPage(
+    id: number,
+    partIndex: number,
+    pageId: number,
+    direction: PageDirection
+  ): Promise<SearchResult | null>;
+
+  /** The page of a book with a printed page number, optionally within a part */
+  getPageByNumber(
+    id: number,
+    pageNumber: string,
+    partLabel?: string
+  ): Promise<SearchResult | null>;
+
+  resolveCitation(request: CitationRequest): Promise<CitationResolution>;
+
   getPageTokens(
     id: number,
     partIndex: number,
@@ -131,4 +158,4 @@ export interface SearchAPI {
 }
 
 // Re-export for convenience
-export type { NameSearchForm };
+export type { NameSearchForm, NamePatterns };
@@ -4,13 +4,18 @@
  * Uses Tauri commands to access local corpus data.
  */
 
-import type { SearchAPI, CombinedSearchQuery, SearchTerm, NameSearchForm } from './index';
+import type { SearchAPI, CombinedSearchQuery, SearchTerm, NameSearchForm, NamePatterns } from './index';
+import type { NameFormData } from '../utils/namePatterns';
 import type {
   SearchMode,
   SearchFilters,
   SearchResults,
   BookMetadata,
   SearchResult,
+  BookPart,
+  CitationRequest,
+  CitationResolution,
+  PageDirection,
   Token,
 } from '../types';
 import * as tauri from './tauri';
@@ -73,6 +78,10 @@ export class OfflineAPI implements SearchAPI {
     return tauri.nameSearch(forms, filters, limit, offset);
   }
 
+  async generateNamePatterns(forms: NameFormData[]): Promise<NamePatterns[]> {
+    return tauri.generateNamePatterns(forms);
+  }
+
   async wildcardSearch(
     query: string,
     filters: SearchFilters,
@@ -91,6 +100,31 @@ export class OfflineAPI implements SearchAPI {
     return result;
   }
 
+  async getBookParts(id: number): Promise<BookPart[]> {
+    return tauri.getBookParts(id);
+  }
+
+  async getAdjacentPage(
+    id: number,
+    partIndex: number,
+    pageId: number,
+    direction: PageDirection
+  ): Promise<SearchResult | null> {
+    return tauri.getAdjacentPage(id, partIndex, pageId, direction);
+  }
+
+  async getPageByNumber(
+    id: number,
+    pageNumber: string,
+    partLabel?: string
+  ): Promise<SearchResult | null> {
+    return tauri.getPageByNumber(id, pageNumber, partLabel);
+  }
+
+  async resolveCitation(request: CitationRequest): Promise<CitationResolution> {
+    return tauri.resolveCitation(request);
+  }
+
   async getPageTokens(
     id: number,
     partIndex: number,
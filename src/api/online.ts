@@ -5,13 +5,18 @@
  * Base URL: https://api.kashshaf.com
  */
 
-import type { SearchAPI, CombinedSearchQuery, SearchTerm, NameSearchForm } from './index';
+import type { SearchAPI, CombinedSearchQuery, SearchTerm, NameSearchForm, NamePatterns } from './index';
+import type { NameFormData } from '../utils/namePatterns';
 import type {
   SearchMode,
   SearchFilters,
   SearchResults,
   BookMetadata,
   SearchResult,
+  BookPart,
+  CitationRequest,
+  CitationResolution,
+  PageDirection,
   Token,
 } from '../types';
 import { stripPunctuation } from '../utils/sanitize';
@@ -190,6 +195,13 @@ export class OnlineAPI implements SearchAPI {
     });
   }
 
+  async generateNamePatterns(forms: NameFormData[]): Promise<NamePatterns[]> {
+    return fetchAPI<NamePatterns[]>('/names/patterns', {
+      method: 'POST',
+      body: JSON.stringify(forms),
+    });
+  }
+
   async wildcardSearch(
     query: string,
     filters: SearchFilters,
@@ -229,6 +241,54 @@ export class OnlineAPI implements SearchAPI {
     }
   }
 
+  async getBookParts(id: number): Promise<BookPart[]> {
+    return fetchAPI<BookPart[]>(`/book/parts?${new URLSearchParams({ id: String(id) })}`);
+  }
+
+  async getAdjacentPage(
+    id: number,
+    partIndex: number,
+    pageId: number,
+    direction: PageDirection
+  ): Promise<SearchResult | null> {
+    const params = new URLSearchParams({
+      id: String(id),
+      part_index: String(partIndex),
+      page_id: String(pageId),
+      direction,
+    });
+
+    return fetchAPI<SearchResult | null>(`/page/adjacent?${params}`);
+  }
+
+  async getPageByNumber(
+    id: number,
+    pageNumber: string,
+    partLabel?: string
+  ): Promise<SearchResult | null> {
+    const params = new URLSearchParams({
+      id: String(id),
+      page_number: pageNumber,
+    });
+    if (partLabel) {
+      params.set('part_label', partLabel);
+    }
+
+    return fetchAPI<SearchResult | null>(`/page/by-number?${params}`);
+  }
+
+  async resolveCitation(request: CitationRequest): Promise<CitationResolution> {
+    const params = new URLSearchParams({
+      book: request.book,
+      page_number: request.page_number,
+    });
+    if (request.part_label) {
+      params.set('part_label', request.part_label);
+    }
+
+    return fetchAPI<CitationResolution>(`/cite?${params}`);
+  }
+
   async getPageTokens(
     id: number,
     partIndex: number,
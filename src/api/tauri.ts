@@ -5,6 +5,10 @@ import type {
   SearchResults,
   BookMetadata,
   SearchResult,
+  BookPart,
+  CitationRequest,
+  CitationResolution,
+  PageDirection,
   Token,
   TokenField,
   AppStats,
@@ -14,6 +18,7 @@ import type {
   AppUpdateStatus,
   CorpusStatus,
 } from '../types';
+import type { NameFormData } from '../utils/namePatterns';
 import { stripPunctuation } from '../utils/sanitize';
 
 export async function search(
@@ -35,6 +40,40 @@ export async function getPage(
   return invoke('get_page', { id, partIndex, pageId });
 }
 
+/** Parts (volumes) of a book with their labels and page ranges */
+export async function getBookParts(id: number): Promise<BookPart[]> {
+  return invoke('get_book_parts', { id });
+}
+
+/** The next or previous page of a book, across parts; null at either end */
+export async function getAdjacentPage(
+  id: number,
+  partIndex: number,
+  pageId: number,
+  direction: PageDirection
+): Promise<SearchResult | null> {
+  return invoke('get_adjacent_page', { id, partIndex, pageId, direction });
+}
+
+/** The page of a book with a printed page number, optionally within a part */
+export async function getPageByNumber(
+  id: number,
+  pageNumber: string,
+  partLabel?: string
+): Promise<SearchResult | null> {
+  return invoke('get_page_by_number', { id, partLabel, pageNumber });
+}
+
+/** Resolve a citation (book, volume, printed page) to a page */
+export async function resolveCitation(request: CitationRequest): Promise<CitationResolution> {
+  return invoke('resolve_citation', { request });
+}
+
+/** Citation of the kashshaf:// link the app was launched with */
+export async function getLaunchCitation(): Promise<CitationRequest | null> {
+  return invoke('get_launch_citation');
+}
+
 /** Load all book metadata at once - for caching in frontend */
 export async function getAllBooks(): Promise<BookMetadata[]> {
   return invoke('get_all_books');
@@ -229,9 +268,26 @@ export async function combinedSearch(
 
 /**
  * Name search - search for Arabic personal names using pattern matching
+ * A form is a structured name (expanded by the engine), its generated patterns, a registered person
+ * or a gazetteer place (its spellings and nisbas)
  */
-export interface NameSearchForm {
-  patterns: string[];  // All generated patterns for this name (after proclitic expansion)
+export type NameSearchForm =
+  | { patterns: string[] }  // All generated patterns for this name (after proclitic expansion)
+  | { person_id: number }   // All forms of a person in the registry (desktop only)
+  | { place: string }       // Gazetteer place by URI or name (desktop only)
+  | NameFormData;
+
+/**
+ * Patterns the engine generates for a structured name form
+ */
+export interface NamePatterns {
+  search: string[];   // With proclitic variants
+  display: string[];  // Without proclitics, ابو/ابا/ابي collapsed to اب*
+  valid: boolean;
+}
+
+export async function generateNamePatterns(forms: NameFormData[]): Promise<NamePatterns[]> {
+  return invoke('generate_name_patterns', { forms });
 }
 
 export async function nameSearch(
@@ -1,7 +1,10 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { KunyaGroup, NasabGroup, NisbaGroup } from './NameInputGroup';
 import type { NameFormData } from '../../utils/namePatterns';
-import { createEmptyNameForm, generateDisplayPatterns, hasValidForm } from '../../utils/namePatterns';
+import { createEmptyNameForm } from '../../utils/namePatterns';
+import type { NamePatterns } from '../../api';
+import { useOperatingMode } from '../../contexts/OperatingModeContext';
+import { PATTERN_PREVIEW_DELAY_MS } from '../../constants/search';
 
 interface NameSearchFormProps {
   forms: NameFormData[];
@@ -210,10 +213,27 @@ export function NameSearchForm({
     }
   };
 
-  const isValid = hasValidForm(forms);
+  // Preview and validity come from the engine, so they show exactly what will be searched
+  const { api } = useOperatingMode();
+  const [generated, setGenerated] = useState<NamePatterns[]>([]);
 
-  // Calculate display patterns for preview
-  const displayPatterns = forms.map(form => generateDisplayPatterns(form));
+  useEffect(() => {
+    let cancelled = false;
+    const timer = setTimeout(() => {
+      api.generateNamePatterns(forms)
+        .then(patterns => {
+          if (!cancelled) setGenerated(patterns);
+        })
+        .catch(err => console.error('Failed to generate name patterns:', err));
+    }, PATTERN_PREVIEW_DELAY_MS);
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
+  }, [api, forms]);
+
+  const isValid = generated.some(patterns => patterns.valid);
+  const displayPatterns = generated.map(patterns => patterns.display);
 
   const handleKeyDown = (e: React.KeyboardEvent) => {
     if (e.key === 'Enter' && isValid && !loading) {
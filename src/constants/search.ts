@@ -5,6 +5,7 @@ export const EXPORT_MAX_RESULTS = 2000;
 
 // UI constants
 export const ROW_HEIGHT = 48;
+export const PATTERN_PREVIEW_DELAY_MS = 250;
 
 // Sidebar dimensions
 export const SIDEBAR_MIN_WIDTH = 400;
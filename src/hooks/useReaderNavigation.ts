@@ -126,62 +126,39 @@ export function useReaderNavigation(options: UseReaderNavigationOptions): UseRea
     }
   }, [updateTab, api, tabs]);
 
-  // Page navigation handler - navigate to previous/next page
+  // Page navigation handler - navigate to previous/next page.
+  // The backend knows the book's page order, so gaps in page_id and part boundaries are crossed.
   const handleNavigatePage = useCallback(async (direction: number) => {
     if (!activeTab || activeTab.currentBookId === null) return;
 
-    const newPageId = activeTab.currentPageId + direction;
-    if (newPageId < 1) return;
-
     updateTab(activeTab.id, { errorMessage: '' });
 
     try {
       const startTime = performance.now();
-      console.log('[NavDebug] Navigating to page:', {
-        bookId: activeTab.currentBookId,
-        partIndex: activeTab.currentPartIndex,
-        currentPageId: activeTab.currentPageId,
-        newPageId,
-        direction
-      });
-
-      // Fetch page and tokens separately to identify which call fails
-      let page = null;
-      let tokens: any[] = [];
-
-      try {
-        console.log('[NavDebug] Calling api.getPage...');
-        page = await api.getPage(activeTab.currentBookId, activeTab.currentPartIndex, newPageId);
-        console.log('[NavDebug] api.getPage returned:', page ? { id: page.id, part_label: page.part_label, page_number: page.page_number } : null);
-      } catch (pageErr) {
-        console.error('[NavDebug] api.getPage FAILED:', pageErr);
-        throw pageErr;
-      }
-
-      try {
-        console.log('[NavDebug] Calling api.getPageTokens...');
-        tokens = await api.getPageTokens(activeTab.currentBookId, activeTab.currentPartIndex, newPageId);
-        console.log('[NavDebug] api.getPageTokens returned:', { count: tokens.length });
-      } catch (tokenErr) {
-        console.error('[NavDebug] api.getPageTokens FAILED:', tokenErr);
-        throw tokenErr;
-      }
-
+      const page = await api.getAdjacentPage(
+        activeTab.currentBookId,
+        activeTab.currentPartIndex,
+        activeTab.currentPageId,
+        direction > 0 ? 'next' : 'previous'
+      );
+      // First or last page of the book
+      if (!page) return;
+
+      const tokens = await api.getPageTokens(page.id, page.part_index, page.page_id);
       const loadTimeMs = Math.round(performance.now() - startTime);
 
-      if (page) {
-        updateTab(activeTab.id, {
-          currentPage: {
-            bookId: page.id,
-            meta: `${page.part_label}:${page.page_number}`,
-            body: page.body ?? '',
-            loadTimeMs,
-          },
-          pageTokens: tokens,
-          currentPageId: newPageId,
-          matchedTokenIndices: [],
-        });
-      }
+      updateTab(activeTab.id, {
+        currentPage: {
+          bookId: page.id,
+          meta: `${page.part_label}:${page.page_number}`,
+          body: page.body ?? '',
+          loadTimeMs,
+        },
+        pageTokens: tokens,
+        currentPartIndex: page.part_index,
+        currentPageId: page.page_id,
+        matchedTokenIndices: [],
+      });
     } catch (err) {
       updateTab(activeTab.id, { errorMessage: `Failed to load page: ${err}` });
       console.error('Failed to load page:', err);
@@ -6,7 +6,6 @@ import type { SearchAPI, NameSearchForm as NameSearchFormAPI } from '../api';
 import { PAGE_SIZE, MAX_RESULTS, EXPORT_MAX_RESULTS } from '../constants/search';
 import { addToHistory } from '../utils/storage';
 import { useSearchTabsContext } from '../contexts/SearchTabsContext';
-import { generateSearchPatterns, generateDisplayPatterns } from '../utils/namePatterns';
 
 export interface UseSearchOptions {
   selectedBookIds: Set<number>;
@@ -236,8 +235,10 @@ export function useSearch(options: UseSearchOptions): UseSearchReturn {
 
   // Name search handler - returns displayPatterns so caller can update state
   const handleNameSearch = useCallback(async (nameFormData: NameFormData[]): Promise<{ displayPatterns: string[][] }> => {
-    const searchPatterns = nameFormData.map(form => generateSearchPatterns(form));
-    const displayPatterns = nameFormData.map(form => generateDisplayPatterns(form));
+    // Patterns come from the engine so highlighting and load-more use exactly what was searched
+    const generated = await api.generateNamePatterns(nameFormData);
+    const searchPatterns = generated.map(patterns => patterns.search);
+    const displayPatterns = generated.map(patterns => patterns.display);
 
     const label = generateNameDisplayLabel(nameFormData);
     const fullQuery = displayPatterns.map(patterns => patterns.join(' | ')).join(' ; ');
@@ -27,6 +27,23 @@ export interface SearchResult {
   score: number;
   /** Token indices that matched the search query (positions in the token array) */
   matched_token_indices: number[];
+  /** Name patterns found on the page (name search only) */
+  matched_patterns?: PatternMatch[];
+}
+
+/** A name pattern found on a result page */
+export interface PatternMatch {
+  form: number;      // Index of the name form
+  pattern: string;
+  starts: number[];  // Token position where each occurrence starts
+  len: number;       // Words in the pattern
+}
+
+/** Result pages a name pattern matches */
+export interface PatternHits {
+  form: number;
+  pattern: string;
+  pages: number;
 }
 
 export interface SearchResults {
@@ -35,6 +52,8 @@ export interface SearchResults {
   total_hits: number;
   results: SearchResult[];
   elapsed_ms: number;
+  /** Pages matched by each name pattern, most first (name search with `pattern_hits` set, first page only) */
+  pattern_hits?: PatternHits[];
 }
 
 // Combined page content with match positions (from single Tantivy query)
@@ -46,6 +65,48 @@ export interface PageWithMatches {
   matched_token_indices: number[];
 }
 
+/** A part (volume) of a book and the pages it spans */
+export interface BookPart {
+  part_index: number;
+  part_label: string;
+  first_page_id: number;
+  last_page_id: number;
+  page_count: number;
+  /** Printed page numbers of the first and last page */
+  first_page_number: string;
+  last_page_number: string;
+}
+
+export type PageDirection = 'next' | 'previous';
+
+/** A page cited by book (ID, original_id or title), volume and printed page */
+export interface CitationRequest {
+  book: string;
+  part_label?: string | null;
+  page_number: string;
+}
+
+export type CitationMatch = 'page_number' | 'page_id' | 'nearest';
+
+export interface CitedBook {
+  id: number;
+  title: string;
+  original_id: string | null;
+  paginated: boolean;
+}
+
+export interface ResolvedCitation {
+  book: CitedBook;
+  matched_by: CitationMatch;
+  page: SearchResult;
+}
+
+/** The cited page, or the books an ambiguous name matches (to cite by ID) */
+export type CitationResolution =
+  | ({ status: 'resolved' } & ResolvedCitation)
+  | { status: 'ambiguous'; candidates: CitedBook[] }
+  | { status: 'not_found' };
+
 // Token types
 export interface TokenClitic {
   type: string;